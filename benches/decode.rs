@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use png_viewer::{
+    parse::{decode, Paeth},
+    stages::{convert, inflate, parse_chunks, unfilter},
+};
+
+const PNG: &[u8] = include_bytes!("../assets/xkcd.png");
+
+fn stages(c: &mut Criterion) {
+    let parsed = parse_chunks(PNG).unwrap();
+    let filtered = inflate(&parsed).unwrap();
+    let mut unfiltered = filtered.clone();
+    unfilter(&parsed, &mut unfiltered).unwrap();
+
+    c.bench_function("parse_chunks", |b| b.iter(|| parse_chunks(PNG).unwrap()));
+    c.bench_function("inflate", |b| b.iter(|| inflate(&parsed).unwrap()));
+    c.bench_function("unfilter", |b| {
+        b.iter_batched_ref(
+            || filtered.clone(),
+            |scanlines| unfilter(&parsed, scanlines).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("convert", |b| {
+        b.iter(|| convert(&parsed, &unfiltered).unwrap())
+    });
+    c.bench_function("decode", |b| b.iter(|| decode(PNG).unwrap()));
+}
+
+/// Both Paeth formulations over every row of the image, as if each had been Paeth-filtered.
+fn paeth(c: &mut Criterion) {
+    let parsed = parse_chunks(PNG).unwrap();
+    let mut unfiltered = inflate(&parsed).unwrap();
+    unfilter(&parsed, &mut unfiltered).unwrap();
+    let row_len = parsed.row_len();
+    let bytes_per_pixel = parsed.format.bits_per_pixel().div_ceil(8);
+
+    let mut group = c.benchmark_group("paeth");
+    for paeth in Paeth::ALL {
+        group.bench_function(format!("{paeth:?}"), |b| {
+            b.iter_batched_ref(
+                || unfiltered.clone(),
+                |rows| {
+                    for start in (row_len..rows.len()).step_by(row_len) {
+                        let (above, rest) = rows.split_at_mut(start);
+                        let prev = &above[start - row_len..];
+                        paeth.unfilter(&mut rest[..row_len], prev, bytes_per_pixel);
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, stages, paeth);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nom::combinator::iterator;
+use png_viewer::parse::chunks::chunk;
+
+fuzz_target!(|data: &[u8]| {
+    // every chunk in the input, not just the first
+    let mut iter = iterator(data, chunk);
+    for _ in &mut iter {}
+    let _ = iter.finish();
+});
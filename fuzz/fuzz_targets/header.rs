@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use png_viewer::parse::header;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = header(data);
+});
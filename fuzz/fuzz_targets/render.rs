@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use png_viewer::parse::{
+    chunks::{Handlers, Policy},
+    draw, Render,
+};
+
+/// Exercises the whole pipeline without the cost of storing pixels.
+struct Null;
+
+impl Render for Null {
+    fn draw_pixel(&mut self, _x: usize, _y: usize, _color: iced::Color) {}
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = draw(data, |_, _| Null, &mut Handlers::default());
+    // the lenient policy takes recovery paths that strict decoding never reaches
+    let _ = draw(data, |_, _| Null, Handlers::default().policy(Policy::Lenient));
+});
@@ -0,0 +1,369 @@
+//! Animated PNG (APNG) frames, composited into whole images so any one of them can be shown
+//! on its own, and encoded back into an animation.
+
+use std::time::Duration;
+
+use nom::{
+    combinator::iterator,
+    number::complete::{be_u16, be_u32, u8},
+    sequence::tuple,
+};
+
+use crate::{
+    encode,
+    parse::{
+        self,
+        chunks::{self, BitDepth, Chunk, ColorType},
+        error::Error,
+        image::Image,
+    },
+};
+
+/// Frame delays are written in thousandths of a second.
+const DELAY_DEN: u16 = 1000;
+
+/// The canvas as it looks once a frame has been drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub image: Image,
+    /// How long the frame stays up before the next one.
+    pub delay: Duration,
+}
+
+/// An `fcTL` chunk: where the next frame's pixels go and what happens to them afterwards.
+struct Control {
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    delay: Duration,
+    dispose: Dispose,
+    /// Composite over the canvas rather than replacing it.
+    blend: bool,
+}
+
+enum Dispose {
+    Keep,
+    Clear,
+    Restore,
+}
+
+/// Whether `data` is an APNG, i.e. has an `acTL` chunk ahead of its image data.
+pub fn is_animated(data: &[u8]) -> bool {
+    let Ok((data, _)) = parse::header(data) else {
+        return false;
+    };
+    (&mut iterator(data, chunks::raw_chunk))
+        .map(|raw| raw.ty.0)
+        .take_while(|ty| ty != b"IDAT")
+        .any(|ty| &ty == b"acTL")
+}
+
+/// Decodes every frame of an APNG, applying each frame's blend and dispose operations.
+///
+/// Each frame's data is stood in a PNG of its own and decoded like any other, so animations
+/// support exactly the pixel formats still images do.
+pub fn frames(data: &[u8]) -> Result<Vec<Frame>, Error> {
+    let (data, _) = parse::header(data)?;
+
+    let mut ihdr = None;
+    // chunks every frame needs to decode on its own
+    let mut shared = vec![];
+    let mut controls: Vec<(Control, Vec<u8>)> = vec![];
+    for raw in &mut iterator(data, chunks::raw_chunk) {
+        let bytes: &[u8] = raw.data.into();
+        match &raw.ty.0 {
+            b"IHDR" => ihdr = Some(raw),
+            b"PLTE" | b"tRNS" => shared.push((raw.ty.0, bytes)),
+            b"fcTL" => controls.push((control(controls.len(), bytes)?, vec![])),
+            // image data ahead of the first fcTL is a default image outside the animation
+            b"IDAT" => {
+                if let Some((_, frame_data)) = controls.last_mut() {
+                    frame_data.extend_from_slice(bytes);
+                }
+            }
+            // the same as IDAT after a sequence number
+            b"fdAT" => match (controls.last_mut(), bytes.get(4..)) {
+                (Some((_, frame_data)), Some(bytes)) => frame_data.extend_from_slice(bytes),
+                _ => return Err(Error::InvalidFrame(controls.len(), "fdAT without fcTL")),
+            },
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let ihdr = ihdr.ok_or(Error::MissingCritical("IHDR"))?;
+    let Chunk::Ihdr { width, height, .. } = chunks::parse(ihdr)? else {
+        return Err(Error::MissingCritical("IHDR"));
+    };
+    if controls.is_empty() {
+        return Err(Error::MissingCritical("fcTL"));
+    }
+
+    let mut canvas = Image::new(width as usize, height as usize);
+    let mut frames = Vec::with_capacity(controls.len());
+    for (index, (control, frame_data)) in controls.into_iter().enumerate() {
+        let Control {
+            width,
+            height,
+            x,
+            y,
+            ..
+        } = control;
+        if width == 0 || height == 0 || x + width > canvas.width() || y + height > canvas.height() {
+            return Err(Error::InvalidFrame(index, "region outside the canvas"));
+        }
+
+        let mut frame_ihdr = <&[u8]>::from(ihdr.data).to_vec();
+        frame_ihdr[..4].copy_from_slice(&(width as u32).to_be_bytes());
+        frame_ihdr[4..8].copy_from_slice(&(height as u32).to_be_bytes());
+        let png = encode::write_png(
+            [(b"IHDR", frame_ihdr.as_slice())]
+                .into_iter()
+                .chain(shared.iter().map(|(ty, data)| (ty, *data)))
+                .chain([(b"IDAT", frame_data.as_slice()), (b"IEND", &[])]),
+        );
+        let image = parse::decode(&png)?;
+
+        let region = iced::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+        let previous = matches!(control.dispose, Dispose::Restore).then(|| canvas.crop(region));
+        canvas.paste(&image, x, y, control.blend);
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay: control.delay,
+        });
+
+        match control.dispose {
+            Dispose::Keep => {}
+            Dispose::Clear => canvas.paste(&Image::new(width, height), x, y, false),
+            Dispose::Restore => canvas.paste(&previous.expect("cropped above"), x, y, false),
+        }
+    }
+    Ok(frames)
+}
+
+/// Encodes `frames` as an APNG that plays `plays` times, or forever if 0.
+///
+/// The first frame doubles as the still image shown by decoders without APNG support.
+/// Each later frame stores only the region that changed since the one before, which
+/// replaces what was there.
+pub fn encode(frames: &[Frame], plays: u32, compression: u32) -> Result<Vec<u8>, Error> {
+    let first = frames.first().ok_or(Error::MissingCritical("fcTL"))?;
+    let (width, height) = (first.image.width(), first.image.height());
+    // the frames share one IHDR, so they can't each pick their own layout
+    let options = encode::Options {
+        color_type: Some(ColorType::RgbAlpha),
+        bit_depth: Some(BitDepth::Eight),
+        compression,
+        ..encode::Options::default()
+    };
+
+    let mut ihdr = vec![];
+    let mut chunks = vec![(
+        *b"acTL",
+        [frames.len() as u32, plays].map(u32::to_be_bytes).concat(),
+    )];
+    let mut sequence = 0u32;
+    let mut previous: Option<&Image> = None;
+    for (index, frame) in frames.iter().enumerate() {
+        let image = &frame.image;
+        if (image.width(), image.height()) != (width, height) {
+            return Err(Error::InvalidFrame(
+                index,
+                "size differs from the first frame",
+            ));
+        }
+        let whole = iced::Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        let region = match previous {
+            Some(previous) => previous
+                .diff(image, 0)
+                .and_then(|diff| diff.bounds)
+                // an unchanged frame still needs a region to hold its delay
+                .unwrap_or(iced::Rectangle {
+                    width: 1,
+                    height: 1,
+                    ..whole
+                }),
+            None => whole,
+        };
+
+        let png = encode::encode_with(&image.crop(region), &options)?;
+        let (frame_ihdr, data) = image_data(&png)?;
+        let delay = frame.delay.as_millis().min(u16::MAX.into()) as u16;
+        let fctl = [
+            sequence,
+            region.width as u32,
+            region.height as u32,
+            region.x as u32,
+            region.y as u32,
+        ]
+        .iter()
+        .flat_map(|n| n.to_be_bytes())
+        .chain(delay.to_be_bytes())
+        .chain(DELAY_DEN.to_be_bytes())
+        // keep the canvas as drawn, and replace the region rather than blend over it
+        .chain([0, 0])
+        .collect();
+        chunks.push((*b"fcTL", fctl));
+        sequence += 1;
+
+        if previous.is_none() {
+            ihdr = frame_ihdr;
+            chunks.push((*b"IDAT", data));
+        } else {
+            chunks.push((*b"fdAT", [&sequence.to_be_bytes()[..], &data].concat()));
+            sequence += 1;
+        }
+        previous = Some(image);
+    }
+
+    Ok(encode::write_png(
+        [(b"IHDR", ihdr.as_slice())]
+            .into_iter()
+            .chain(chunks.iter().map(|(ty, data)| (ty, data.as_slice())))
+            .chain([(b"IEND", &[][..])]),
+    ))
+}
+
+/// The IHDR and concatenated IDAT payloads of a still PNG.
+fn image_data(png: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let (data, _) = parse::header(png)?;
+    let (mut ihdr, mut idat) = (vec![], vec![]);
+    for raw in &mut iterator(data, chunks::raw_chunk) {
+        let bytes: &[u8] = raw.data.into();
+        match &raw.ty.0 {
+            b"IHDR" => ihdr = bytes.to_vec(),
+            b"IDAT" => idat.extend_from_slice(bytes),
+            _ => {}
+        }
+    }
+    Ok((ihdr, idat))
+}
+
+fn control(index: usize, input: &[u8]) -> Result<Control, Error> {
+    let (_, (_sequence, width, height, x, y, delay_num, delay_den, dispose, blend)) = tuple((
+        be_u32, be_u32, be_u32, be_u32, be_u32, be_u16, be_u16, u8, u8,
+    ))(input)?;
+
+    let dispose = match dispose {
+        0 => Dispose::Keep,
+        1 => Dispose::Clear,
+        2 => Dispose::Restore,
+        _ => return Err(Error::InvalidFrame(index, "unknown dispose operation")),
+    };
+    let blend = match blend {
+        0 => false,
+        1 => true,
+        _ => return Err(Error::InvalidFrame(index, "unknown blend operation")),
+    };
+    // a zero denominator means hundredths of a second
+    let delay_den = if delay_den == 0 { 100 } else { delay_den };
+
+    Ok(Control {
+        width: width as usize,
+        height: height as usize,
+        x: x as usize,
+        y: y as usize,
+        delay: Duration::from_secs(delay_num.into()) / delay_den.into(),
+        dispose,
+        blend,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+    /// The IHDR and zlib stream of `image` encoded as a still PNG.
+    fn still(image: &Image) -> (Vec<u8>, Vec<u8>) {
+        let png = encode::encode(image).unwrap();
+        let (data, _) = parse::header(&png).unwrap();
+        let chunks: Vec<_> = (&mut iterator(data, chunks::raw_chunk))
+            .map(|raw| <&[u8]>::from(raw.data).to_vec())
+            .collect();
+        (chunks[0].clone(), chunks[1].clone())
+    }
+
+    fn fctl(sequence: u32, size: u32, at: u32, delay: [u16; 2], ops: [u8; 2]) -> Vec<u8> {
+        [sequence, size, size, at, at]
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .chain(delay.iter().flat_map(|n| n.to_be_bytes()))
+            .chain(ops)
+            .collect()
+    }
+
+    #[test]
+    fn blend_and_dispose() -> Result<(), Error> {
+        let (ihdr, red) = still(&Image::from_pixels(2, 2, vec![RED; 4]).unwrap());
+        let blue = [0, 0, 255, 128];
+        let (_, blue_data) = still(&Image::from_pixels(1, 1, vec![blue]).unwrap());
+        let (_, green) = still(&Image::from_pixels(1, 1, vec![GREEN]).unwrap());
+        let fdat = |sequence: u32, data: &[u8]| [&sequence.to_be_bytes()[..], data].concat();
+
+        let actl = [3u32, 0].map(u32::to_be_bytes).concat();
+        let chunks: [(&[u8; 4], Vec<u8>); 8] = [
+            (b"IHDR", ihdr),
+            (b"acTL", actl),
+            (b"fcTL", fctl(0, 2, 0, [1, 10], [0, 0])),
+            (b"IDAT", red),
+            // blended over, then put back
+            (b"fcTL", fctl(1, 1, 1, [5, 0], [2, 1])),
+            (b"fdAT", fdat(2, &blue_data)),
+            (b"fcTL", fctl(3, 1, 0, [1, 10], [0, 0])),
+            (b"fdAT", fdat(4, &green)),
+        ];
+        let apng = encode::write_png(
+            chunks
+                .iter()
+                .map(|(ty, data)| (*ty, data.as_slice()))
+                .chain([(b"IEND", &[][..])]),
+        );
+        assert!(is_animated(&apng));
+        assert!(!is_animated(include_bytes!("../assets/xkcd.png")));
+
+        let frames = frames(&apng)?;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].image.pixels(), [RED; 4]);
+        assert_eq!(frames[0].delay, Duration::from_millis(100));
+
+        let mut blended = Image::from_pixels(1, 1, vec![RED]).unwrap();
+        blended.blend(&Image::from_pixels(1, 1, vec![blue]).unwrap());
+        assert_eq!(frames[1].image.pixels(), [RED, RED, RED, blended.get(0, 0)]);
+        assert_eq!(frames[1].delay, Duration::from_millis(50));
+
+        assert_eq!(frames[2].image.pixels(), [GREEN, RED, RED, RED]);
+        Ok(())
+    }
+
+    #[test]
+    fn encode_round_trip() -> Result<(), Error> {
+        let frame = |pixels, millis| Frame {
+            image: Image::from_pixels(3, 2, pixels).unwrap(),
+            delay: Duration::from_millis(millis),
+        };
+        let first = frame(vec![RED; 6], 100);
+        let changed = frame([vec![RED; 4], vec![GREEN, RED]].concat(), 250);
+        let same = frame(changed.image.pixels().to_vec(), 40);
+        let frames_in = [first, changed, same];
+
+        let apng = encode(&frames_in, 0, 6)?;
+        assert!(is_animated(&apng));
+        assert_eq!(parse::decode(&apng)?, frames_in[0].image);
+        assert_eq!(frames(&apng)?, frames_in);
+        Ok(())
+    }
+}
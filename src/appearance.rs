@@ -0,0 +1,145 @@
+use std::{fmt, io, path::PathBuf};
+
+use iced::{theme::Palette, Color, Theme};
+
+use crate::options;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeChoice {
+    #[default]
+    Dark,
+    Light,
+    /// Dark, with the user's accent color.
+    Custom,
+}
+
+impl ThemeChoice {
+    pub const ALL: [Self; 3] = [Self::Dark, Self::Light, Self::Custom];
+}
+
+impl fmt::Display for ThemeChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+            Self::Custom => "Custom",
+        })
+    }
+}
+
+/// How the viewer looks, kept between runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Appearance {
+    pub theme: ThemeChoice,
+    /// Primary color of the custom theme.
+    pub accent: Color,
+    /// Behind the image, unless `--bg` overrides it.
+    pub background: Option<Color>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            theme: ThemeChoice::default(),
+            accent: Palette::DARK.primary,
+            background: None,
+        }
+    }
+}
+
+impl Appearance {
+    /// Reads the saved appearance, falling back to the default for anything missing.
+    pub fn load() -> Self {
+        match settings_file().map(std::fs::read_to_string) {
+            Some(Ok(text)) => Self::parse(&text),
+            Some(Err(error)) if error.kind() != io::ErrorKind::NotFound => {
+                tracing::error!("from std::fs::read_to_string: {error}");
+                Self::default()
+            }
+            _ => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = settings_file()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, self.to_string())
+    }
+
+    pub fn theme(&self) -> Theme {
+        match self.theme {
+            ThemeChoice::Dark => Theme::Dark,
+            ThemeChoice::Light => Theme::Light,
+            ThemeChoice::Custom => Theme::custom(Palette {
+                primary: self.accent,
+                ..Palette::DARK
+            }),
+        }
+    }
+
+    /// `key=value` lines; unknown keys and bad values are skipped.
+    fn parse(text: &str) -> Self {
+        let mut appearance = Self::default();
+        for (key, value) in text.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.trim();
+            match key.trim() {
+                "theme" => {
+                    if let Some(theme) = ThemeChoice::ALL
+                        .into_iter()
+                        .find(|theme| theme.to_string().eq_ignore_ascii_case(value))
+                    {
+                        appearance.theme = theme;
+                    }
+                }
+                "accent" => {
+                    if let Some(color) = options::parse_color(value) {
+                        appearance.accent = color;
+                    }
+                }
+                "background" => appearance.background = options::parse_color(value),
+                _ => {}
+            }
+        }
+        appearance
+    }
+}
+
+impl fmt::Display for Appearance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "theme={}", self.theme.to_string().to_lowercase())?;
+        writeln!(f, "accent={}", to_hex(self.accent))?;
+        if let Some(background) = self.background {
+            writeln!(f, "background={}", to_hex(background))?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a color the way `--bg` takes it, `#RRGGBB`.
+pub fn to_hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn settings_file() -> Option<PathBuf> {
+    Some(options::config_dir()?.join("appearance"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let appearance = Appearance {
+            theme: ThemeChoice::Custom,
+            accent: Color::from_rgb8(0x12, 0xab, 0xef),
+            background: Some(Color::WHITE),
+        };
+        assert_eq!(Appearance::parse(&appearance.to_string()), appearance);
+        assert_eq!(Appearance::parse("theme=neon\n"), Appearance::default());
+    }
+}
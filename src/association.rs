@@ -0,0 +1,152 @@
+//! Registering the viewer as the program that opens PNGs.
+
+use std::{io, path::Path};
+
+const MIME_TYPE: &str = "image/png";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const DESKTOP_FILE: &str = "png-viewer.desktop";
+
+/// Makes double-clicking a PNG open it in this executable, for the current user only.
+pub fn register() -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    register_exe(&exe)
+}
+
+/// Adds a ProgID for the viewer and lists it under `.png`, so it shows up in "Open with"
+/// and the default apps settings. Windows doesn't let programs make themselves the default
+/// outright.
+#[cfg(target_os = "windows")]
+fn register_exe(exe: &Path) -> io::Result<()> {
+    const PROG_ID: &str = r"Software\Classes\PngViewer.png";
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let icon = format!("\"{}\",0", exe.display());
+    for (key, name, value) in [
+        (format!(r"HKCU\{PROG_ID}"), None, "PNG image"),
+        (format!(r"HKCU\{PROG_ID}\DefaultIcon"), None, icon.as_str()),
+        (
+            format!(r"HKCU\{PROG_ID}\shell\open\command"),
+            None,
+            command.as_str(),
+        ),
+        (
+            r"HKCU\Software\Classes\.png\OpenWithProgids".into(),
+            Some("PngViewer.png"),
+            "",
+        ),
+    ] {
+        let mut reg = std::process::Command::new("reg");
+        reg.args(["add", &key, "/f", "/d", value]);
+        match name {
+            Some(name) => reg.args(["/v", name]),
+            None => reg.arg("/ve"),
+        };
+        let status = reg.status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("reg add {key} failed: {status}")));
+        }
+    }
+    Ok(())
+}
+
+/// Installs a desktop entry and names it the default for PNGs in `mimeapps.list`.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn register_exe(exe: &Path) -> io::Result<()> {
+    use std::path::PathBuf;
+
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, "no home directory");
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(not_found)?;
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+
+    let applications = data.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    std::fs::write(applications.join(DESKTOP_FILE), desktop_entry(exe))?;
+
+    std::fs::create_dir_all(&config)?;
+    let mimeapps = config.join("mimeapps.list");
+    let existing = match std::fs::read_to_string(&mimeapps) {
+        Ok(existing) => existing,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error),
+    };
+    std::fs::write(mimeapps, set_default(&existing, MIME_TYPE, DESKTOP_FILE))
+}
+
+/// App bundles declare their document types in `Info.plist`, so there's nothing to do at
+/// run time.
+#[cfg(target_os = "macos")]
+fn register_exe(_exe: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "set the default from Finder's Get Info window instead",
+    ))
+}
+
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=PNG Viewer\n\
+         Exec=\"{}\" %f\n\
+         MimeType={MIME_TYPE};\n\
+         Terminal=false\n\
+         NoDisplay=false\n",
+        exe.display()
+    )
+}
+
+/// `mimeapps` with `desktop` as the default application for `mime`, leaving every other
+/// line as it was.
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+fn set_default(mimeapps: &str, mime: &str, desktop: &str) -> String {
+    const SECTION: &str = "[Default Applications]";
+    let entry = format!("{mime}={desktop}");
+    let mut lines: Vec<String> = mimeapps.lines().map(Into::into).collect();
+
+    let Some(start) = lines.iter().position(|line| line.trim() == SECTION) else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.extend([SECTION.into(), entry]);
+        return lines.join("\n") + "\n";
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let existing = lines[start + 1..end]
+        .iter()
+        .position(|line| line.split('=').next().map(str::trim) == Some(mime));
+    match existing {
+        Some(offset) => lines[start + 1 + offset] = entry,
+        None => lines.insert(start + 1, entry),
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_replaced_in_place() {
+        let mimeapps = "[Added Associations]\nimage/png=gimp.desktop;\n\n\
+                        [Default Applications]\nimage/png=eog.desktop\ntext/plain=vim.desktop\n";
+        assert_eq!(
+            set_default(mimeapps, MIME_TYPE, "png-viewer.desktop"),
+            "[Added Associations]\nimage/png=gimp.desktop;\n\n\
+             [Default Applications]\nimage/png=png-viewer.desktop\ntext/plain=vim.desktop\n"
+        );
+        assert_eq!(
+            set_default("", MIME_TYPE, "png-viewer.desktop"),
+            "[Default Applications]\nimage/png=png-viewer.desktop\n"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use png_viewer::{
+    apng::{self, Frame},
+    cli::glob,
+    parse::decode,
+};
+use std::{env, error::Error, process::ExitCode, time::Duration};
+
+const USAGE: &str =
+    "Usage: png-animate <OUT> [--delay MS] <FRAME>... [--loops N] [--compression 0-9]
+
+Assembles the FRAME images, in order, into an animated PNG written to OUT. A FRAME whose
+file name uses * or ? adds every match, sorted by name.
+--delay applies to every frame after it until the next --delay, and defaults to 100.
+--loops defaults to 0, which plays forever. Every frame must be the same size.";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut out = None;
+    let mut frames = Vec::new();
+    let mut delay = Duration::from_millis(100);
+    let mut loops = 0;
+    let mut compression = 6;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--delay" => delay = Duration::from_millis(args.next().ok_or(USAGE)?.parse()?),
+            "--loops" => loops = args.next().ok_or(USAGE)?.parse()?,
+            "--compression" => {
+                compression = args.next().ok_or(USAGE)?.parse()?;
+                if compression > 9 {
+                    return Err(format!("compression must be 0 to 9: {compression}").into());
+                }
+            }
+            _ if out.is_none() => out = Some(arg),
+            _ => {
+                let paths = glob(&arg)?;
+                if paths.is_empty() {
+                    return Err(format!("no files match {arg}").into());
+                }
+                for path in paths {
+                    let image = decode(&std::fs::read(&path)?)
+                        .map_err(|error| format!("{}: {error}", path.display()))?;
+                    frames.push(Frame { image, delay });
+                }
+            }
+        }
+    }
+    let Some(out) = out.filter(|_| !frames.is_empty()) else {
+        return Err(USAGE.into());
+    };
+
+    std::fs::write(&out, apng::encode(&frames, loops, compression)?)?;
+    eprintln!("{} frames -> {out}", frames.len());
+    Ok(())
+}
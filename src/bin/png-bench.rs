@@ -0,0 +1,117 @@
+use png_viewer::stages::{convert, inflate, parse_chunks, unfilter};
+use std::{
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "Usage: png-bench <FILE|DIR>... [--iterations N]
+
+Times chunk parsing, inflation, unfiltering and pixel conversion separately,
+averaged over N runs (default 20). Directories are searched for *.png files.";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    let mut iterations = 20u32;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => iterations = args.next().ok_or(USAGE)?.parse()?,
+            "--help" | "-h" => return Err(USAGE.into()),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() || iterations == 0 {
+        return Err(USAGE.into());
+    }
+
+    let mut paths = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            paths.extend(pngs_in(&input)?);
+        } else {
+            paths.push(input);
+        }
+    }
+
+    println!(
+        "{:<40} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "file", "parse", "inflate", "unfilter", "convert", "MP/s"
+    );
+    let mut totals = [Duration::ZERO; 4];
+    for path in &paths {
+        let times = time_stages(path, iterations)
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+        for (total, time) in totals.iter_mut().zip(times.stages) {
+            *total += time;
+        }
+        print_row(&path.display().to_string(), times.stages, times.pixels);
+    }
+    if paths.len() > 1 {
+        print_row("total", totals, 0);
+    }
+    Ok(())
+}
+
+struct Times {
+    stages: [Duration; 4],
+    pixels: usize,
+}
+
+/// Mean time spent in each stage.
+fn time_stages(path: &Path, iterations: u32) -> Result<Times, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let mut stages = [Duration::ZERO; 4];
+    let mut pixels = 0;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let parsed = parse_chunks(&data)?;
+        stages[0] += start.elapsed();
+
+        let start = Instant::now();
+        let mut scanlines = inflate(&parsed)?;
+        stages[1] += start.elapsed();
+
+        let start = Instant::now();
+        unfilter(&parsed, &mut scanlines)?;
+        stages[2] += start.elapsed();
+
+        let start = Instant::now();
+        pixels = convert(&parsed, &scanlines)?;
+        stages[3] += start.elapsed();
+    }
+    Ok(Times {
+        stages: stages.map(|total| total / iterations),
+        pixels,
+    })
+}
+
+fn print_row(name: &str, stages: [Duration; 4], pixels: usize) {
+    let millis = stages.map(|time| format!("{:.3}ms", time.as_secs_f64() * 1000.0));
+    let total = stages.iter().sum::<Duration>().as_secs_f64();
+    let throughput = if pixels > 0 && total > 0.0 {
+        format!("{:.1}", pixels as f64 / total / 1e6)
+    } else {
+        "-".into()
+    };
+    println!(
+        "{name:<40} {:>10} {:>10} {:>10} {:>10} {throughput:>10}",
+        millis[0], millis[1], millis[2], millis[3]
+    );
+}
+
+fn pngs_in(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
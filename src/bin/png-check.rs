@@ -0,0 +1,37 @@
+use png_viewer::{check::check, cli::read_input};
+use std::{env, process::ExitCode};
+
+fn main() -> ExitCode {
+    let paths: Vec<_> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: png-check <file|->...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let problems = match read_input(&path) {
+            Ok(data) => check(&data),
+            Err(error) => {
+                eprintln!("{path}: {error}");
+                failed = true;
+                continue;
+            }
+        };
+
+        if problems.is_empty() {
+            println!("{path}: OK");
+        } else {
+            failed = true;
+            for problem in problems {
+                println!("{path}: {problem}");
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
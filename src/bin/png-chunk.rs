@@ -0,0 +1,108 @@
+use nom::combinator::iterator;
+use png_viewer::{
+    cli::read_input,
+    encode::write_png,
+    parse::{chunks::raw_chunk, header},
+};
+use std::{env, error::Error, io::Write};
+
+const USAGE: &str = "Usage:
+  png-chunk <in|-> <out|-> add <TYPE> <DATA>
+  png-chunk <in|-> <out|-> replace <TYPE> <DATA>
+  png-chunk <in|-> <out|-> delete <TYPE>
+  png-chunk <in|-> <out|-> add-text <KEYWORD> <TEXT>
+
+DATA is taken literally, or read from a file if prefixed with @.
+New chunks are inserted before the first IDAT.";
+
+enum Edit {
+    Add([u8; 4], Vec<u8>),
+    Replace([u8; 4], Vec<u8>),
+    Delete([u8; 4]),
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+
+    let (input_path, output_path, edit) = match args.as_slice() {
+        [input, output, "add", ty, data] => {
+            (input, output, Edit::Add(ty_arg(ty)?, data_arg(data)?))
+        }
+        [input, output, "replace", ty, data] => {
+            (input, output, Edit::Replace(ty_arg(ty)?, data_arg(data)?))
+        }
+        [input, output, "delete", ty] => (input, output, Edit::Delete(ty_arg(ty)?)),
+        [input, output, "add-text", keyword, text] => {
+            let data = [keyword.as_bytes(), b"\0", text.as_bytes()].concat();
+            (input, output, Edit::Add(*b"tEXt", data))
+        }
+        _ => return Err(USAGE.into()),
+    };
+
+    let file_data = read_input(input_path)?;
+    let (input, _) = header(&file_data)?;
+    let mut iter = iterator(input, raw_chunk);
+    let mut chunks: Vec<([u8; 4], &[u8])> =
+        (&mut iter).map(|raw| (raw.ty.0, raw.data.into())).collect();
+    iter.finish()?;
+
+    let insert_at = |chunks: &[([u8; 4], &[u8])]| {
+        chunks
+            .iter()
+            .position(|(ty, _)| ty == b"IDAT")
+            .unwrap_or(chunks.len().saturating_sub(1))
+    };
+
+    match &edit {
+        Edit::Add(ty, data) => {
+            chunks.insert(insert_at(&chunks), (*ty, data));
+        }
+        Edit::Replace(ty, data) => {
+            let position = chunks.iter().position(|(other, _)| other == ty);
+            chunks.retain(|(other, _)| other != ty);
+            let position = position.unwrap_or_else(|| insert_at(&chunks));
+            chunks.insert(position, (*ty, data));
+        }
+        Edit::Delete(ty) => {
+            let before = chunks.len();
+            chunks.retain(|(other, _)| other != ty);
+            if chunks.len() == before {
+                return Err(format!("no {} chunk found", String::from_utf8_lossy(ty)).into());
+            }
+        }
+    }
+
+    let out = write_png(chunks.iter().map(|(ty, data)| (ty, *data)));
+    if *output_path == "-" {
+        std::io::stdout().lock().write_all(&out)?;
+    } else {
+        std::fs::write(output_path, out)?;
+    }
+    Ok(())
+}
+
+fn ty_arg(ty: &str) -> Result<[u8; 4], Box<dyn Error>> {
+    let ty: [u8; 4] = ty
+        .as_bytes()
+        .try_into()
+        .map_err(|_| format!("chunk type must be 4 letters: {ty}"))?;
+    if !ty.iter().all(u8::is_ascii_alphabetic) {
+        return Err(format!(
+            "chunk type must be 4 letters: {}",
+            String::from_utf8_lossy(&ty)
+        )
+        .into());
+    }
+    if [b"IHDR", b"IDAT", b"IEND"].contains(&&ty) {
+        return Err("IHDR, IDAT and IEND can't be edited".into());
+    }
+    Ok(ty)
+}
+
+fn data_arg(data: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    match data.strip_prefix('@') {
+        Some(path) => Ok(std::fs::read(path)?),
+        None => Ok(data.as_bytes().to_vec()),
+    }
+}
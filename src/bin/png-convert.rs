@@ -0,0 +1,185 @@
+use nom::combinator::iterator;
+use png_viewer::{
+    cli::glob,
+    encode::{encode_ppm, encode_with, write_png, Options},
+    parse::{
+        chunks::{raw_chunk, BitDepth, ColorType, Handlers, Interlace, Policy},
+        color_info, draw, header,
+        image::{Raster, Sample},
+    },
+};
+use rayon::prelude::*;
+use std::{
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const USAGE: &str = "Usage: png-convert <GLOB> <OUT_DIR> [--format png|ppm] [--strip]
+                   [--bit-depth 1|2|4|8|16] [--color-type 0|2|3|4|6]
+                   [--interlace] [--compression 0-9] [--lenient]
+
+Decodes every file matching GLOB, whose file name may use * and ?, and writes each to
+OUT_DIR under the same name. Files are converted in parallel.
+Text, time, EXIF and physical size chunks are carried over unless --strip is given.
+Color types are the numbers IHDR uses; depth and color type default to the smallest that
+keep every pixel.";
+
+/// Metadata that doesn't change how pixels look, so it's safe to carry over.
+const METADATA: [&[u8; 4]; 6] = [b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf", b"pHYs"];
+
+struct Convert {
+    options: Options,
+    ppm: bool,
+    strip: bool,
+    policy: Policy,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns whether every file converted.
+fn run() -> Result<bool, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut convert = Convert {
+        options: Options {
+            color_type: None,
+            ..Options::default()
+        },
+        ppm: false,
+        strip: false,
+        policy: Policy::Strict,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().ok_or(USAGE)?.as_str() {
+                "png" => convert.ppm = false,
+                "ppm" => convert.ppm = true,
+                other => return Err(format!("unknown format: {other}").into()),
+            },
+            "--strip" => convert.strip = true,
+            "--bit-depth" => {
+                let depth: u8 = args.next().ok_or(USAGE)?.parse()?;
+                convert.options.bit_depth = Some(BitDepth::try_from(depth)?);
+            }
+            "--color-type" => {
+                let color_type: u8 = args.next().ok_or(USAGE)?.parse()?;
+                convert.options.color_type = Some(ColorType::try_from(color_type)?);
+            }
+            "--interlace" => convert.options.interlace = Interlace::Adam7,
+            "--compression" => {
+                let level = args.next().ok_or(USAGE)?.parse()?;
+                if level > 9 {
+                    return Err(format!("compression must be 0 to 9: {level}").into());
+                }
+                convert.options.compression = level;
+            }
+            "--lenient" => convert.policy = Policy::Lenient,
+            _ => paths.push(arg),
+        }
+    }
+    let [pattern, out_dir] = paths.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let inputs = glob(pattern)?;
+    if inputs.is_empty() {
+        return Err(format!("no files match {pattern}").into());
+    }
+    std::fs::create_dir_all(out_dir)?;
+
+    let failed = inputs
+        .par_iter()
+        .filter(|input| {
+            let output = convert.output_path(input, Path::new(out_dir));
+            match convert.file(input, &output) {
+                Ok(()) => {
+                    eprintln!("{} -> {}", input.display(), output.display());
+                    false
+                }
+                Err(error) => {
+                    eprintln!("{}: {error}", input.display());
+                    true
+                }
+            }
+        })
+        .count();
+    if failed > 0 {
+        eprintln!("{failed} of {} files failed", inputs.len());
+    }
+    Ok(failed == 0)
+}
+
+impl Convert {
+    fn output_path(&self, input: &Path, out_dir: &Path) -> PathBuf {
+        let name = input.file_name().unwrap_or(input.as_os_str());
+        let output = out_dir.join(name);
+        if self.ppm {
+            output.with_extension("ppm")
+        } else {
+            output
+        }
+    }
+
+    fn file(&self, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+        if output.canonicalize().ok() == Some(input.canonicalize()?) {
+            return Err("would overwrite the input".into());
+        }
+        let data = std::fs::read(input)?;
+        let mut handlers = Handlers::default();
+        handlers.policy(self.policy);
+        // PPM is only ever written at 8 bits
+        let out = if self.ppm {
+            encode_ppm(&draw(&data, Raster::new, &mut handlers)?)
+        } else if color_info(&data)?.bit_depth == BitDepth::Sixteen {
+            self.png::<u16>(&data, &mut handlers)?
+        } else {
+            self.png::<u8>(&data, &mut handlers)?
+        };
+        for warning in handlers.warnings() {
+            eprintln!("{}: warning: {warning}", input.display());
+        }
+        std::fs::write(output, out)?;
+        Ok(())
+    }
+
+    fn png<S: Sample>(
+        &self,
+        data: &[u8],
+        handlers: &mut Handlers,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let image: Raster<S> = draw(data, Raster::new, handlers)?;
+        let encoded = encode_with(&image, &self.options)?;
+        if self.strip {
+            return Ok(encoded);
+        }
+        with_metadata(&encoded, data)
+    }
+}
+
+/// `encoded` with the metadata chunks of `original` added after its IHDR.
+fn with_metadata(encoded: &[u8], original: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (original, _) = header(original)?;
+    let metadata: Vec<([u8; 4], &[u8])> = iterator(original, raw_chunk)
+        .filter(|raw| METADATA.contains(&&raw.ty.0))
+        .map(|raw| (raw.ty.0, raw.data.into()))
+        .collect();
+
+    let (encoded, _) = header(encoded)?;
+    let mut iter = iterator(encoded, raw_chunk);
+    let mut chunks: Vec<([u8; 4], &[u8])> =
+        (&mut iter).map(|raw| (raw.ty.0, raw.data.into())).collect();
+    iter.finish()?;
+    chunks.splice(1..1, metadata);
+    Ok(write_png(chunks.iter().map(|(ty, data)| (ty, *data))))
+}
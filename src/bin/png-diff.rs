@@ -0,0 +1,79 @@
+use png_viewer::{
+    cli::read_input,
+    encode::encode,
+    parse::{decode, image::Image, Render},
+};
+use std::{env, error::Error, process::ExitCode};
+
+const USAGE: &str =
+    "Usage: png-diff <a|-> <b|-> [--tolerance N] [--diff-out FILE]\n\nExits with 1 if the images differ.";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<bool, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut tolerance = 0;
+    let mut diff_out = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tolerance" => tolerance = args.next().ok_or(USAGE)?.parse()?,
+            "--diff-out" => diff_out = Some(args.next().ok_or(USAGE)?),
+            _ => paths.push(arg),
+        }
+    }
+    let [a_path, b_path] = paths.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let a = decode(&read_input(a_path)?)?;
+    let b = decode(&read_input(b_path)?)?;
+    let diff = a.diff(&b, tolerance).ok_or_else(|| {
+        format!(
+            "dimensions differ: {}x{} vs {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )
+    })?;
+
+    println!("differing pixels: {}", diff.count);
+    println!("max channel delta: {}", diff.max_delta);
+    if let Some(bounds) = diff.bounds {
+        println!(
+            "bounding box: {},{} {}x{}",
+            bounds.x, bounds.y, bounds.width, bounds.height
+        );
+    }
+
+    if let Some(path) = diff_out {
+        // faded copy of the first image with differences marked in red
+        let mut out = Image::new(a.width(), a.height());
+        for (i, &delta) in diff.deltas.iter().enumerate() {
+            let (x, y) = (i % a.width(), i / a.width());
+            let color = if delta > tolerance {
+                let strength = 0.5 + delta as f32 / 510.0;
+                iced::Color::from_rgb(strength, 0.0, 0.0)
+            } else {
+                let [r, g, b, _] = a.get(x, y);
+                let gray = (r as f32 + g as f32 + b as f32) / (3.0 * 255.0);
+                let faded = 0.75 + gray * 0.25;
+                iced::Color::from_rgb(faded, faded, faded)
+            };
+            out.draw_pixel(x, y, color);
+        }
+        std::fs::write(path, encode(&out)?)?;
+    }
+
+    Ok(diff.count == 0)
+}
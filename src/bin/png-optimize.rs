@@ -0,0 +1,103 @@
+use flate2::{read::ZlibDecoder, Compression};
+use nom::combinator::iterator;
+use png_viewer::{
+    cli::read_input,
+    encode::{compress, filter_scanlines, write_png},
+    parse::{
+        chunks::{self, bits_per_pixel, raw_chunk, Chunk, Interlace},
+        header, Decoder,
+    },
+};
+use std::{env, error::Error, io::Read, io::Write};
+
+const USAGE: &str = "Usage: png-optimize <in|-> <out|->";
+
+/// Ancillary chunks that change how the pixels look, so they survive optimization.
+const KEEP: [&[u8; 4]; 6] = [b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"iCCP", b"sBIT"];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let [input_path, output_path] = args.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let file_data = read_input(input_path)?;
+    let (input, _) = header(&file_data)?;
+    let mut iter = iterator(input, raw_chunk);
+    let raw_chunks: Vec<_> = (&mut iter).collect();
+    iter.finish()?;
+    if raw_chunks.iter().any(|raw| &raw.ty.0 == b"acTL") {
+        return Err("animated PNGs aren't supported".into());
+    }
+
+    let (_, ihdr) = chunks::chunk(input)?;
+    let Chunk::Ihdr {
+        bit_depth,
+        color_type,
+        interlace,
+        ..
+    } = ihdr
+    else {
+        return Err("first chunk must be IHDR".into());
+    };
+
+    let filtered = if interlace == Interlace::None {
+        let bytes_per_pixel = bits_per_pixel(bit_depth, color_type)?.div_ceil(8);
+        let mut handlers = Default::default();
+        let mut decoder = Decoder::new(&file_data, &mut handlers)?;
+        let mut rows = Vec::with_capacity(decoder.height());
+        while let Some(row) = decoder.next_row()? {
+            rows.push(row.data.to_vec());
+        }
+        filter_scanlines(rows.iter().map(Vec::as_slice), bytes_per_pixel)
+    } else {
+        // passes have their own row lengths, so keep the existing filters
+        let compressed: Vec<u8> = raw_chunks
+            .iter()
+            .filter(|raw| &raw.ty.0 == b"IDAT")
+            .flat_map(|raw| <&[u8]>::from(raw.data))
+            .copied()
+            .collect();
+        let mut inflated = Vec::new();
+        ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut inflated)?;
+        inflated
+    };
+    let idat = compress(&filtered, Compression::best())?;
+
+    let mut out_chunks: Vec<([u8; 4], &[u8])> = Vec::new();
+    let mut dropped = Vec::new();
+    for raw in &raw_chunks {
+        let ty = raw.ty.0;
+        if &ty == b"IDAT" {
+            if !out_chunks.iter().any(|(ty, _)| ty == b"IDAT") {
+                out_chunks.push((ty, &idat));
+            }
+        } else if ty[0].is_ascii_uppercase() || KEEP.contains(&&ty) {
+            out_chunks.push((ty, raw.data.into()));
+        } else {
+            dropped.push(format!("{:?}", raw.ty));
+        }
+    }
+
+    let optimized = write_png(out_chunks.iter().map(|(ty, data)| (ty, *data)));
+    let before = file_data.len();
+    let out = if optimized.len() < before {
+        let after = optimized.len();
+        let saved = (before - after) as f64 / before as f64 * 100.0;
+        eprintln!("{input_path}: {before} -> {after} bytes ({saved:.1}% smaller)");
+        if !dropped.is_empty() {
+            eprintln!("dropped chunks: {}", dropped.join(", "));
+        }
+        optimized
+    } else {
+        eprintln!("{input_path}: already optimal at {before} bytes, left unchanged");
+        file_data
+    };
+
+    if output_path == "-" {
+        std::io::stdout().lock().write_all(&out)?;
+    } else {
+        std::fs::write(output_path, out)?;
+    }
+    Ok(())
+}
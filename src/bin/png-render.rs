@@ -0,0 +1,94 @@
+use png_viewer::{
+    cli::read_input,
+    color::{Curve, Profile, SRGB_CHROMATICITIES},
+    encode::{encode, encode_ppm},
+    parse::{
+        chunks::{BitDepth, Handlers, Policy},
+        color_info, draw,
+        image::{Raster, Sample},
+    },
+};
+use std::{env, error::Error, io::Write};
+
+const USAGE: &str =
+    "Usage: png-render <in|-> <out|-> [--scale FACTOR] [--gamma] [--display-profile ICC]
+                  [--lenient] [--format png|ppm]
+
+--lenient renders as much of a damaged file as possible.
+Colors are converted from the file's color space to sRGB, or to a plain 2.2 gamma display
+with --gamma, or to the ICC profile given with --display-profile.
+The format defaults to the output's extension, or PNG.";
+
+const DISPLAY_GAMMA: f32 = 2.2;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut scale = 1.0;
+    let mut display = None;
+    let mut policy = Policy::Strict;
+    let mut format = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => scale = args.next().ok_or(USAGE)?.parse::<f64>()?,
+            "--gamma" => {
+                display = Profile::new(SRGB_CHROMATICITIES, Curve::gamma(DISPLAY_GAMMA));
+            }
+            "--display-profile" => {
+                let icc = std::fs::read(args.next().ok_or(USAGE)?)?;
+                display = Some(Profile::from_icc(&icc)?);
+            }
+            "--lenient" => policy = Policy::Lenient,
+            "--format" => format = Some(args.next().ok_or(USAGE)?),
+            _ => paths.push(arg),
+        }
+    }
+    let [input_path, output_path] = paths.as_slice() else {
+        return Err(USAGE.into());
+    };
+    if scale.is_nan() || scale <= 0.0 {
+        return Err(format!("scale must be positive: {scale}").into());
+    }
+    let ppm = match format.as_deref() {
+        Some("ppm") => true,
+        Some("png") => false,
+        Some(other) => return Err(format!("unknown format: {other}").into()),
+        None => output_path.ends_with(".ppm"),
+    };
+
+    let display = display.unwrap_or_default();
+    let data = read_input(input_path)?;
+    let mut handlers = Handlers::default();
+    handlers.policy(policy).display(&display);
+    // 16-bit files stay 16-bit in PNG output; PPM is only ever written at 8 bits
+    let out = if ppm {
+        encode_ppm(&render::<u8>(&data, &mut handlers, scale)?)
+    } else if color_info(&data)?.bit_depth == BitDepth::Sixteen {
+        encode(&render::<u16>(&data, &mut handlers, scale)?)?
+    } else {
+        encode(&render::<u8>(&data, &mut handlers, scale)?)?
+    };
+    if output_path == "-" {
+        std::io::stdout().lock().write_all(&out)?;
+    } else {
+        std::fs::write(output_path, out)?;
+    }
+    Ok(())
+}
+
+fn render<S: Sample>(
+    data: &[u8],
+    handlers: &mut Handlers,
+    scale: f64,
+) -> Result<Raster<S>, Box<dyn Error>> {
+    let image = draw(data, Raster::new, handlers)?;
+    for warning in handlers.warnings() {
+        eprintln!("warning: {warning}");
+    }
+    if scale == 1.0 {
+        return Ok(image);
+    }
+    let width = ((image.width() as f64 * scale).round() as usize).max(1);
+    let height = ((image.height() as f64 * scale).round() as usize).max(1);
+    Ok(image.resize(width, height))
+}
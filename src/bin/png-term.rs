@@ -0,0 +1,101 @@
+use png_viewer::{
+    cli::read_input,
+    color, iterm2,
+    parse::{chunks::Handlers, decode_with},
+    sixel,
+};
+use std::{env, error::Error, io::Write};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+const USAGE: &str = "Usage: png-term <file|-> [--width COLUMNS] [--sixel | --iterm2]
+
+--sixel draws the image at full resolution for terminals with Sixel support.
+--iterm2 sends the file as an iTerm2 inline image.";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut file_path = None;
+    let mut columns = None;
+    let mut use_sixel = false;
+    let mut use_iterm2 = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sixel" => use_sixel = true,
+            "--iterm2" => use_iterm2 = true,
+            "--width" => columns = Some(args.next().ok_or(USAGE)?.parse::<usize>()?),
+            _ => file_path = Some(arg),
+        }
+    }
+    let file_path = file_path.ok_or(USAGE)?;
+    let data = read_input(&file_path)?;
+    let image = decode_with(&data, Handlers::default().display(color::display_profile()))?;
+
+    if use_iterm2 {
+        let mut out = std::io::stdout().lock();
+        let name = (file_path != "-").then_some(file_path.as_str());
+        writeln!(out, "{}", iterm2::inline_image(&data, name, columns))?;
+        return Ok(());
+    }
+
+    if use_sixel {
+        let mut out = std::io::stdout().lock();
+        writeln!(out, "{}", sixel::encode(&image))?;
+        return Ok(());
+    }
+
+    let (terminal_columns, terminal_rows) = terminal_size::terminal_size()
+        .map(|(width, height)| (width.0 as usize, height.0 as usize))
+        .unwrap_or((80, 24));
+    let columns = columns.unwrap_or(terminal_columns).max(1);
+    // each character cell holds two pixels stacked vertically; keep a row for the prompt
+    let max_height = terminal_rows.saturating_sub(1).max(1) * 2;
+
+    let scale = f64::min(
+        columns as f64 / image.width() as f64,
+        max_height as f64 / image.height() as f64,
+    )
+    .min(1.0);
+    let width = ((image.width() as f64 * scale) as usize).max(1);
+    let height = ((image.height() as f64 * scale) as usize).max(1);
+
+    // averages the source pixels covered by an output pixel, composited over black
+    let sample = |x: usize, y: usize| {
+        let (x0, x1) = (x * image.width() / width, (x + 1) * image.width() / width);
+        let (y0, y1) = (
+            y * image.height() / height,
+            (y + 1) * image.height() / height,
+        );
+        let (x1, y1) = (x1.max(x0 + 1), y1.max(y0 + 1));
+        let mut sum = [0u32; 3];
+        for sy in y0..y1 {
+            for &[r, g, b, a] in &image.row(sy)[x0..x1] {
+                let a = a as u32;
+                sum[0] += r as u32 * a / 255;
+                sum[1] += g as u32 * a / 255;
+                sum[2] += b as u32 * a / 255;
+            }
+        }
+        let count = ((x1 - x0) * (y1 - y0)) as u32;
+        Color::Rgb(
+            (sum[0] / count) as u8,
+            (sum[1] / count) as u8,
+            (sum[2] / count) as u8,
+        )
+    };
+
+    let mut out = StandardStream::stdout(ColorChoice::Auto);
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let mut spec = ColorSpec::new();
+            spec.set_fg(Some(sample(x, y)));
+            if y + 1 < height {
+                spec.set_bg(Some(sample(x, y + 1)));
+            }
+            out.set_color(&spec)?;
+            write!(out, "\u{2580}")?;
+        }
+        out.reset()?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
@@ -1,20 +1,224 @@
+use flate2::read::ZlibDecoder;
 use nom::combinator::iterator;
-use png_viewer::parse::*;
-use std::{env, error::Error, fs::read};
+use png_viewer::{cli::read_input, deflate, parse::*};
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, Read, Write},
+    ops::Range,
+};
+
+const USAGE: &str =
+    "Usage: print_chunks <file|-> [--verify] [--stats] [--scanlines START[..END]] [--dump-to FILE]
+                    [--dump-idat FILE] [--dump-inflated FILE] [--deflate] [--filters]
+
+--dump-idat writes the IDAT chunks' data joined together, the zlib stream as stored.
+--dump-inflated writes that stream inflated: every scanline, still filtered, after its
+filter type byte.
+--deflate lists the stream's deflate blocks: their type, size, and how much of their output
+comes from matches against earlier data.
+--filters counts the scanlines using each filter type and lists them in order.";
 
 fn main() -> Result<(), Box<dyn Error>> {
     tracing_subscriber::fmt::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .init();
-    let mut args = env::args();
-    args.next();
-    let file_path = args.next().ok_or("Missing file path arg.")?;
-    let file_data = read(file_path)?;
+
+    let mut file_path = None;
+    let mut verify = false;
+    let mut stats = false;
+    let mut scanlines = None;
+    let mut dump_to = None;
+    let mut dump_idat = None;
+    let mut dump_inflated = None;
+    let mut inspect_deflate = false;
+    let mut list_filters = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            "--stats" => stats = true,
+            "--scanlines" => scanlines = Some(parse_rows(&args.next().ok_or(USAGE)?)?),
+            "--dump-to" => dump_to = Some(args.next().ok_or(USAGE)?),
+            "--dump-idat" => dump_idat = Some(args.next().ok_or(USAGE)?),
+            "--dump-inflated" => dump_inflated = Some(args.next().ok_or(USAGE)?),
+            "--deflate" => inspect_deflate = true,
+            "--filters" => list_filters = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown option: {flag}").into());
+            }
+            _ => file_path = Some(arg),
+        }
+    }
+    let file_path = file_path.ok_or("Missing file path arg (use - for stdin).")?;
+    if dump_to.is_some() && scanlines.is_none() {
+        return Err("--dump-to needs --scanlines".into());
+    }
+    let file_data = read_input(&file_path)?;
+
     let (input, _) = header(&file_data)?;
+    if verify {
+        verify_crcs(&file_data, input)?;
+    }
+
     let mut iter = iterator(input, chunks::chunk);
     for chunk in &mut iter {
         println!("{chunk:?}");
     }
     iter.finish()?;
+
+    if stats {
+        let image = decode(&file_data)?;
+        match image.statistics() {
+            Some(statistics) => println!("{}", statistics.to_json()),
+            None => println!("null"),
+        }
+    }
+
+    if list_filters {
+        print_filters(&filters(&file_data, &mut Default::default())?);
+    }
+
+    if let Some(rows) = scanlines {
+        let out: Box<dyn Write> = match dump_to {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout().lock()),
+        };
+        dump_scanlines(&file_data, rows, out)?;
+    }
+
+    if dump_idat.is_some() || dump_inflated.is_some() || inspect_deflate {
+        let idat: Vec<u8> = iterator(input, chunks::raw_chunk)
+            .filter(|raw| raw.ty.0 == *b"IDAT")
+            .flat_map(|raw| <&[u8]>::from(raw.data))
+            .copied()
+            .collect();
+        if let Some(path) = dump_idat {
+            std::fs::write(path, &idat)?;
+        }
+        if let Some(path) = dump_inflated {
+            // whatever inflates before an error is still worth looking at
+            let mut inflated = Vec::new();
+            let result = ZlibDecoder::new(idat.as_slice()).read_to_end(&mut inflated);
+            std::fs::write(path, &inflated)?;
+            result.map_err(|error| {
+                format!("IDAT stream stops after {} bytes: {error}", inflated.len())
+            })?;
+        }
+        if inspect_deflate {
+            print_deflate(&deflate::inspect(&idat)?);
+        }
+    }
+    Ok(())
+}
+
+fn print_filters(filters: &Filters) {
+    let total = filters.sequence().len().max(1);
+    for (filter, count) in FilterType::ALL.into_iter().zip(filters.counts()) {
+        let percent = count as f64 / total as f64 * 100.0;
+        println!("{filter:?}: {count} scanlines ({percent:.1}%)");
+    }
+    let runs: Vec<_> = filters
+        .runs()
+        .into_iter()
+        .map(|(filter, count)| match count {
+            1 => format!("{filter:?}"),
+            _ => format!("{filter:?}×{count}"),
+        })
+        .collect();
+    println!("sequence: {}", runs.join(" "));
+}
+
+fn print_deflate(stream: &deflate::Stream) {
+    println!(
+        "zlib: {}-byte window, level hint {}, {}",
+        stream.window_size,
+        stream.level,
+        if stream.preset_dictionary {
+            "preset dictionary"
+        } else {
+            "no preset dictionary"
+        }
+    );
+    for (index, block) in stream.blocks.iter().enumerate() {
+        print!(
+            "block {index} {:?} at byte {}: {} -> {} bytes",
+            block.ty,
+            block.start_bit / 8,
+            block.bits.div_ceil(8),
+            block.output
+        );
+        if block.ty == deflate::BlockType::Stored {
+            println!();
+            continue;
+        }
+        let percent = block.matched as f64 / block.output.max(1) as f64 * 100.0;
+        print!(
+            ", {} literals, {} matches covering {} bytes ({percent:.1}%)",
+            block.literals, block.matches, block.matched
+        );
+        match block.mean_distance() {
+            Some(mean) => println!(", distance mean {mean:.0} max {}", block.max_distance),
+            None => println!(),
+        }
+    }
+}
+
+fn verify_crcs(file_data: &[u8], mut input: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut mismatches = 0;
+    while !input.is_empty() {
+        let offset = file_data.len() - input.len();
+        let (rest, raw) = chunks::raw_chunk(input)?;
+        if !raw.crc_matches() {
+            println!(
+                "CRC mismatch in {:?} chunk at offset {offset:#x}: stored {:08x}, computed {:08x}",
+                raw.ty,
+                raw.crc,
+                raw.computed_crc()
+            );
+            mismatches += 1;
+        }
+        input = rest;
+    }
+
+    if mismatches > 0 {
+        Err(format!("{mismatches} chunk(s) failed CRC verification").into())
+    } else {
+        println!("All CRCs verified");
+        Ok(())
+    }
+}
+
+fn parse_rows(arg: &str) -> Result<Range<usize>, Box<dyn Error>> {
+    Ok(match arg.split_once("..") {
+        Some((start, end)) => start.parse()?..end.parse()?,
+        None => {
+            let row = arg.parse()?;
+            row..row + 1
+        }
+    })
+}
+
+/// Prints the unfiltered bytes of each row in `rows`, stopping once they've been decoded.
+fn dump_scanlines(
+    file_data: &[u8],
+    rows: Range<usize>,
+    mut out: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut handlers = chunks::Handlers::default();
+    let mut decoder = Decoder::new(file_data, &mut handlers)?;
+    while let Some(row) = decoder.next_row()? {
+        if row.y >= rows.end {
+            break;
+        }
+        if rows.contains(&row.y) {
+            write!(out, "row {:>5} filter {}:", row.y, row.filter as u8)?;
+            for byte in row.data {
+                write!(out, " {byte:02x}")?;
+            }
+            writeln!(out)?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,380 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use iced::{
+    mouse,
+    widget::canvas::{self, Cache, Frame, Geometry, Path as Shape, Program, Stroke},
+    Color, Point, Rectangle, Renderer, Size, Theme, Vector,
+};
+use png_viewer::{
+    color,
+    parse::{self, chunks::Handlers, image::Image},
+};
+
+use crate::Message;
+
+const MIN_SCALE: f32 = 0.05;
+const MAX_SCALE: f32 = 8.0;
+/// Scale change per scroll step.
+const SCALE_STEP: f32 = 1.25;
+/// Gap between newly added images and the ones already on the board.
+const SPACING: f32 = 20.0;
+
+/// Images laid out freely on one large canvas for side-by-side comparison.
+///
+/// The layout is kept in a session file with one image per line:
+/// `X Y SCALE PATH`, so boards can be reopened and edited by hand.
+pub struct Board {
+    session: PathBuf,
+    items: Vec<Item>,
+    pan: Vector,
+    cache: Cache,
+}
+
+struct Item {
+    path: PathBuf,
+    position: Point,
+    scale: f32,
+    /// `None` if the file couldn't be decoded; it keeps its place on the board.
+    image: Option<Image>,
+}
+
+impl Item {
+    fn new(path: PathBuf, position: Point, scale: f32) -> Self {
+        let image = std::fs::read(&path)
+            .map_err(parse::error::Error::from)
+            .and_then(|data| {
+                parse::decode_with(&data, Handlers::default().display(color::display_profile()))
+            })
+            .map_err(|error| tracing::error!("from parse::decode: {}: {error}", path.display()))
+            .ok();
+        Self {
+            path,
+            position,
+            scale,
+            image,
+        }
+    }
+
+    fn size(&self) -> Size {
+        let (width, height) = self
+            .image
+            .as_ref()
+            .map_or((100, 100), |image| (image.width(), image.height()));
+        Size::new(width as f32 * self.scale, height as f32 * self.scale)
+    }
+
+    fn bounds(&self) -> Rectangle {
+        Rectangle::new(self.position, self.size())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let Some(image) = &self.image else {
+            frame.fill_rectangle(self.position, self.size(), Color::from_rgb(0.3, 0.1, 0.1));
+            return;
+        };
+        // shrink with averaging rather than dropping pixels
+        let (image, pixel) = if self.scale < 1.0 {
+            let size = self.size();
+            let resized = image.resize(
+                (size.width.round() as usize).max(1),
+                (size.height.round() as usize).max(1),
+            );
+            (std::borrow::Cow::Owned(resized), 1.0)
+        } else {
+            (std::borrow::Cow::Borrowed(image), self.scale)
+        };
+
+        for y in 0..image.height() {
+            for (x, &[r, g, b, a]) in image.row(y).iter().enumerate() {
+                if a == 0 {
+                    continue;
+                }
+                frame.fill_rectangle(
+                    Point::new(
+                        self.position.x + x as f32 * pixel,
+                        self.position.y + y as f32 * pixel,
+                    ),
+                    Size::new(pixel, pixel),
+                    Color::from_rgba8(r, g, b, a as f32 / 255.0),
+                );
+            }
+        }
+    }
+}
+
+impl Board {
+    /// Opens the board saved in `session`, or an empty one if the file doesn't exist yet.
+    pub fn open(session: &Path) -> io::Result<Self> {
+        // read as bytes, since the paths needn't be valid Unicode
+        let items = match std::fs::read(session) {
+            Ok(contents) => contents
+                .split(|&byte| byte == b'\n')
+                .filter(|line| !line.trim_ascii().is_empty())
+                .map(|line| {
+                    parse_item(line).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid board entry: {}", String::from_utf8_lossy(line)),
+                        )
+                    })
+                })
+                .collect::<io::Result<_>>()?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(error),
+        };
+
+        Ok(Self {
+            session: session.to_path_buf(),
+            items,
+            pan: Vector::default(),
+            cache: Cache::new(),
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = Vec::new();
+        for item in &self.items {
+            let numbers = format!("{} {} {} ", item.position.x, item.position.y, item.scale);
+            contents.extend_from_slice(numbers.as_bytes());
+            contents.extend_from_slice(&path_to_bytes(&item.path));
+            contents.push(b'\n');
+        }
+        std::fs::write(&self.session, contents)
+    }
+
+    /// Places `path` to the right of everything already on the board.
+    pub fn add(&mut self, path: PathBuf) {
+        let x = self
+            .items
+            .iter()
+            .map(|item| item.bounds().x + item.bounds().width + SPACING)
+            .fold(0.0, f32::max);
+        self.items.push(Item::new(path, Point::new(x, 0.0), 1.0));
+        self.cache.clear();
+    }
+
+    pub fn move_item(&mut self, index: usize, position: Point) {
+        if let Some(item) = self.items.get_mut(index) {
+            item.position = position;
+            self.cache.clear();
+        }
+    }
+
+    pub fn scale_item(&mut self, index: usize, zoom_in: bool) {
+        if let Some(item) = self.items.get_mut(index) {
+            let factor = if zoom_in {
+                SCALE_STEP
+            } else {
+                1.0 / SCALE_STEP
+            };
+            item.scale = (item.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+            self.cache.clear();
+        }
+    }
+
+    pub fn pan(&mut self, delta: Vector) {
+        self.pan = self.pan + delta;
+        self.cache.clear();
+    }
+
+    /// Topmost item under `point`, in board coordinates.
+    fn item_at(&self, point: Point) -> Option<usize> {
+        self.items
+            .iter()
+            .rposition(|item| item.bounds().contains(point))
+    }
+}
+
+fn parse_item(line: &[u8]) -> Option<Item> {
+    let mut fields = line.splitn(4, |&byte| byte == b' ');
+    let mut number = || {
+        std::str::from_utf8(fields.next()?)
+            .ok()?
+            .parse::<f32>()
+            .ok()
+    };
+    let (x, y, scale) = (number()?, number()?, number()?);
+    let path = fields.next()?;
+    Some(Item::new(
+        path_from_bytes(path),
+        Point::new(x, y),
+        scale.clamp(MIN_SCALE, MAX_SCALE),
+    ))
+}
+
+/// The path's bytes as the OS has them, so names that aren't valid Unicode survive a
+/// round trip through the session file.
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    // unpaired surrogates are the only thing lost here, and no real file name has them
+    match path.to_string_lossy() {
+        std::borrow::Cow::Borrowed(path) => path.as_bytes().into(),
+        std::borrow::Cow::Owned(path) => path.into_bytes().into(),
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// What the left mouse button is currently dragging.
+#[derive(Debug, Default)]
+pub enum Drag {
+    #[default]
+    None,
+    /// An item, grabbed at this offset from its corner.
+    Item(usize, Vector),
+    /// The whole board, last seen at this cursor position.
+    Pan(Point),
+}
+
+impl Program<Message> for Board {
+    type State = Drag;
+
+    fn draw(
+        &self,
+        state: &Self::State,
+        renderer: &Renderer<Theme>,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let board = self.cache.draw(renderer, bounds.size(), |frame| {
+            frame.translate(self.pan);
+            for item in &self.items {
+                item.draw(frame);
+            }
+        });
+
+        let mut frame = Frame::new(renderer, bounds.size());
+        frame.translate(self.pan);
+        for (index, item) in self.items.iter().enumerate() {
+            let dragging = matches!(state, Drag::Item(dragged, _) if *dragged == index);
+            let color = if dragging {
+                Color::from_rgb(1.0, 0.9, 0.2)
+            } else {
+                Color::from_rgba(1.0, 1.0, 1.0, 0.3)
+            };
+            frame.stroke(
+                &Shape::rectangle(item.position, item.size()),
+                Stroke::default().with_width(1.0).with_color(color),
+            );
+        }
+        vec![board, frame.into_geometry()]
+    }
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        let Some(position) = cursor.position_in(bounds) else {
+            return (canvas::event::Status::Ignored, None);
+        };
+        let on_board = position - self.pan;
+
+        let message = match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                *state = match self.item_at(on_board) {
+                    Some(index) => Drag::Item(index, on_board - self.items[index].position),
+                    None => Drag::Pan(position),
+                };
+                None
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { .. }) => match *state {
+                Drag::Item(index, grab) => Some(Message::BoardMove(index, on_board - grab)),
+                Drag::Pan(last) => {
+                    *state = Drag::Pan(position);
+                    Some(Message::BoardPan(position - last))
+                }
+                Drag::None => return (canvas::event::Status::Ignored, None),
+            },
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let moved = matches!(state, Drag::Item(..));
+                *state = Drag::None;
+                moved.then_some(Message::BoardSave)
+            }
+            canvas::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let (mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. }) =
+                    delta;
+                match self.item_at(on_board) {
+                    Some(index) if y != 0.0 => Some(Message::BoardScale(index, y > 0.0)),
+                    _ => None,
+                }
+            }
+            _ => return (canvas::event::Status::Ignored, None),
+        };
+        (canvas::event::Status::Captured, message)
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        match state {
+            Drag::Item(..) | Drag::Pan(_) => mouse::Interaction::Grabbing,
+            Drag::None if cursor.is_over(bounds) => mouse::Interaction::Grab,
+            Drag::None => mouse::Interaction::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip() -> io::Result<()> {
+        let session = std::env::temp_dir().join(format!("board-{}.txt", std::process::id()));
+        std::fs::write(&session, "12.5 -3 0.5 /no such dir/my image.png\n\n")?;
+
+        let board = Board::open(&session)?;
+        assert_eq!(board.items.len(), 1);
+        assert_eq!(board.items[0].path, Path::new("/no such dir/my image.png"));
+        assert!(board.items[0].image.is_none());
+        board.save()?;
+        let saved = std::fs::read_to_string(&session)?;
+        std::fs::remove_file(&session)?;
+
+        assert_eq!(saved, "12.5 -3 0.5 /no such dir/my image.png\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_unicode_paths_round_trip() -> io::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let session = std::env::temp_dir().join(format!("board-latin1-{}.txt", std::process::id()));
+        // "café.png" in Latin-1, as some screenshot tools still name files
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/no such dir/caf\xe9.png"));
+        let mut board = Board::open(&session)?;
+        board.add(path.to_path_buf());
+        board.save()?;
+        let reopened = Board::open(&session)?;
+        std::fs::remove_file(&session)?;
+
+        assert_eq!(reopened.items[0].path, path);
+        Ok(())
+    }
+}
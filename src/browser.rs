@@ -0,0 +1,308 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use iced::{
+    alignment, mouse,
+    widget::{
+        canvas::{self, Frame, Geometry, Program},
+        image::Handle,
+    },
+    Point, Rectangle, Renderer, Size, Theme,
+};
+use png_viewer::{
+    features,
+    parse::{self, chunks::Handlers, image::Thumbnail},
+};
+
+use crate::Message;
+
+pub const ROW_HEIGHT: f32 = 26.0;
+/// Longer side of the preview of the entry under the cursor or selected.
+pub const PREVIEW_SIDE: usize = 200;
+/// Larger files aren't previewed, since even a thumbnail needs the whole file inflated.
+const PREVIEW_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A folder's subfolders and PNGs, for picking a file without the native dialog.
+#[derive(Debug)]
+pub struct Browser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    /// Why the folder couldn't be listed, shown in place of its entries.
+    error: Option<String>,
+    /// Entry chosen with the keyboard.
+    selected: Option<usize>,
+    hovered: Option<usize>,
+    preview: Option<(PathBuf, Handle)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: OsString,
+    pub is_dir: bool,
+}
+
+impl Browser {
+    /// Lists `dir`, keeping the browser usable even if it can't be read.
+    pub fn open(dir: PathBuf) -> Self {
+        let (entries, error) = match list(&dir) {
+            Ok(entries) => (entries, None),
+            Err(error) => {
+                tracing::error!("from browser::list: {}: {error}", dir.display());
+                (vec![], Some(error.to_string()))
+            }
+        };
+        Self {
+            dir,
+            entries,
+            error,
+            selected: None,
+            hovered: None,
+            preview: None,
+        }
+    }
+
+    /// Starts in the current directory, or the home directory if there isn't one.
+    pub fn open_default() -> Self {
+        let dir = std::env::current_dir()
+            .ok()
+            .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("/"));
+        Self::open(dir)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn parent(&self) -> Option<&Path> {
+        self.dir.parent()
+    }
+
+    pub fn path(&self, index: usize) -> Option<PathBuf> {
+        Some(self.dir.join(&self.entries.get(index)?.name))
+    }
+
+    pub fn is_dir(&self, index: usize) -> bool {
+        self.entries.get(index).is_some_and(|entry| entry.is_dir)
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, index: usize) {
+        self.selected = (index < self.entries.len()).then_some(index);
+    }
+
+    /// Moves the selection by `delta` entries, stopping at either end.
+    pub fn select(&mut self, delta: isize) {
+        let Some(last) = self.entries.len().checked_sub(1) else {
+            return;
+        };
+        self.selected = Some(match self.selected {
+            Some(index) => index.saturating_add_signed(delta).min(last),
+            None if delta < 0 => last,
+            None => 0,
+        });
+    }
+
+    pub fn hover(&mut self, index: Option<usize>) {
+        self.hovered = index;
+    }
+
+    /// The file whose preview should be showing: the one under the cursor, or else the
+    /// selected one.
+    pub fn preview_target(&self) -> Option<PathBuf> {
+        let index = self.hovered.or(self.selected)?;
+        (!self.is_dir(index)).then(|| self.path(index)).flatten()
+    }
+
+    pub fn preview(&self) -> Option<&Handle> {
+        let (path, handle) = self.preview.as_ref()?;
+        (Some(path) == self.preview_target().as_ref()).then_some(handle)
+    }
+
+    pub fn has_preview(&self, path: &Path) -> bool {
+        self.preview
+            .as_ref()
+            .is_some_and(|(previewed, _)| previewed == path)
+    }
+
+    pub fn set_preview(&mut self, path: PathBuf, handle: Handle) {
+        self.preview = Some((path, handle));
+    }
+}
+
+/// Decodes a preview of `path` whose longer side fits in `max_side`, or `None` if the file
+/// is too large or doesn't decode.
+pub fn decode_thumbnail(path: &Path, max_side: usize) -> Option<Handle> {
+    if std::fs::metadata(path).ok()?.len() > PREVIEW_MAX_BYTES {
+        return None;
+    }
+    let data = std::fs::read(path)
+        .map_err(|error| tracing::error!("from std::fs::read: {error}"))
+        .ok()?;
+    #[cfg(feature = "webp")]
+    if png_viewer::webp::is_webp(&data) {
+        let image = png_viewer::webp::decode(&data)
+            .map_err(|error| tracing::debug!("No preview of {}: {error}", path.display()))
+            .ok()?;
+        let step = image.width().max(image.height()).div_ceil(max_side).max(1);
+        let image = image.resize(image.width().div_ceil(step), image.height().div_ceil(step));
+        return Some(Handle::from_pixels(
+            image.width() as u32,
+            image.height() as u32,
+            image.pixels().as_flattened().to_vec(),
+        ));
+    }
+    let mut handlers = Handlers::default();
+    handlers
+        .policy(parse::chunks::Policy::Lenient)
+        .display(png_viewer::color::display_profile());
+    let image = parse::draw(&data, |_, _| Thumbnail::new(max_side), &mut handlers)
+        .map_err(|error| tracing::debug!("No preview of {}: {error}", path.display()))
+        .ok()?
+        .into_image();
+    Some(Handle::from_pixels(
+        image.width() as u32,
+        image.height() as u32,
+        image.pixels().as_flattened().to_vec(),
+    ))
+}
+
+impl Program<Message> for Browser {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer<Theme>,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.extended_palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+        for (index, entry) in self.entries.iter().enumerate() {
+            let top = index as f32 * ROW_HEIGHT;
+            let highlight = if self.selected == Some(index) {
+                Some(palette.primary.weak.color)
+            } else if self.hovered == Some(index) {
+                Some(palette.background.weak.color)
+            } else {
+                None
+            };
+            if let Some(color) = highlight {
+                frame.fill_rectangle(
+                    Point::new(0.0, top),
+                    Size::new(bounds.width, ROW_HEIGHT),
+                    color,
+                );
+            }
+            let name = entry.name.to_string_lossy();
+            frame.fill_text(canvas::Text {
+                content: if entry.is_dir {
+                    format!("{name}/")
+                } else {
+                    name.into_owned()
+                },
+                position: Point::new(8.0, top + ROW_HEIGHT / 2.0),
+                color: palette.background.base.text,
+                size: 14.0,
+                vertical_alignment: alignment::Vertical::Center,
+                ..Default::default()
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        let index = cursor
+            .position_in(bounds)
+            .map(|position| (position.y / ROW_HEIGHT) as usize)
+            .filter(|&index| index < self.entries.len());
+        let message = match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                index.map(Message::BrowserActivate)
+            }
+            canvas::Event::Mouse(_) if index != self.hovered => Some(Message::BrowserHover(index)),
+            _ => None,
+        };
+        // scrolling still has to reach the list around the canvas
+        (canvas::event::Status::Ignored, message)
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+/// Folders first, then PNGs, each in name order. Hidden entries are left out.
+fn list(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            if name.as_encoded_bytes().starts_with(b".") {
+                return None;
+            }
+            // follows symlinks, so linked folders can be entered too
+            let is_dir = entry.path().is_dir();
+            let is_image = features::is_supported_file(Path::new(&name));
+            (is_dir || is_image).then_some(Entry { name, is_dir })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn folders_then_pngs() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("browser-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub"))?;
+        for name in ["b.png", "a.PNG", "notes.txt", ".hidden.png"] {
+            std::fs::write(dir.join(name), b"")?;
+        }
+        let browser = Browser::open(dir.clone());
+        std::fs::remove_dir_all(&dir)?;
+
+        let names: Vec<_> = browser
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.to_str().unwrap(), entry.is_dir))
+            .collect();
+        assert_eq!(names, [("sub", true), ("a.PNG", false), ("b.png", false)]);
+        assert!(browser.error().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    borrow::Cow,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use png_viewer::{
+    color,
+    parse::image::{Image, Image16, Pixels},
+};
+
+/// Decodes quicker than this aren't worth caching.
+pub const MIN_DECODE: Duration = Duration::from_secs(1);
+/// Largest compressed entry that will be stored.
+const MAX_ENTRY: u64 = 256 * 1024 * 1024;
+/// Oldest entries are evicted once the cache grows past this.
+const MAX_TOTAL: u64 = 1024 * 1024 * 1024;
+/// Marks entries of 8 and 16 bits per channel.
+const MAGIC_EIGHT: &[u8; 8] = b"PNGVRGB8";
+const MAGIC_SIXTEEN: &[u8; 8] = b"PNGVRG16";
+const ZSTD_LEVEL: i32 = 3;
+
+static GLOBAL: OnceLock<Option<Cache>> = OnceLock::new();
+
+/// Zstandard-compressed decoded pixels of slow-to-decode files, at the precision they were
+/// decoded with, so reopening them skips the decode.
+///
+/// Entries are named by a hash of the file's canonical path and record its size and
+/// modification time, so an edited file is decoded afresh, along with the display profile
+/// the pixels were converted for.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// Sets up the cache used by every decode, in the platform cache directory.
+pub fn init(enabled: bool) {
+    let cache = enabled.then(default_dir).flatten().map(Cache::new);
+    let _ = GLOBAL.set(cache);
+}
+
+pub fn global() -> Option<&'static Cache> {
+    GLOBAL.get()?.as_ref()
+}
+
+fn default_dir() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+    Some(dir?.join("png-viewer").join("decoded"))
+}
+
+/// What an entry must match to still be valid for the file.
+#[derive(Debug, PartialEq)]
+struct Stamp {
+    path: PathBuf,
+    len: u64,
+    modified: Duration,
+    /// [`color::Profile::fingerprint`] of the display the pixels were converted for.
+    profile: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> io::Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            path,
+            len: metadata.len(),
+            modified,
+            profile: color::display_profile().fingerprint(),
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        let path = self.path.as_os_str().as_encoded_bytes();
+        out.extend_from_slice(&self.len.to_le_bytes());
+        out.extend_from_slice(&self.modified.as_secs().to_le_bytes());
+        out.extend_from_slice(&self.modified.subsec_nanos().to_le_bytes());
+        out.extend_from_slice(&self.profile.to_le_bytes());
+        out.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        out.extend_from_slice(path);
+    }
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The cached pixels of `path`, if there's an entry that's still valid.
+    pub fn load(&self, path: &Path) -> Option<Pixels> {
+        let stamp = Stamp::of(path).ok()?;
+        let entry = self.entry(&stamp.path);
+        let contents = fs::read(&entry).ok()?;
+
+        let mut expected = vec![];
+        stamp.write(&mut expected);
+        let stamped = |magic: &[u8; 8]| {
+            contents
+                .strip_prefix(magic.as_slice())?
+                .strip_prefix(expected.as_slice())
+        };
+        let (sixteen, rest) = match (stamped(MAGIC_EIGHT), stamped(MAGIC_SIXTEEN)) {
+            (Some(rest), _) => (false, rest),
+            (_, Some(rest)) => (true, rest),
+            _ => {
+                // the file changed since, another path hashed to the same name, or it's
+                // from an older version
+                let _ = fs::remove_file(&entry);
+                return None;
+            }
+        };
+        let (size, compressed) = rest.split_at_checked(8)?;
+        let width = u32::from_le_bytes(size[..4].try_into().ok()?) as usize;
+        let height = u32::from_le_bytes(size[4..].try_into().ok()?) as usize;
+        let bytes = zstd::decode_all(compressed)
+            .map_err(|error| tracing::error!("from zstd::decode_all: {error}"))
+            .ok()?;
+        if sixteen {
+            let pixels = bytes
+                .chunks_exact(8)
+                .map(|pixel| {
+                    std::array::from_fn(|c| u16::from_le_bytes([pixel[2 * c], pixel[2 * c + 1]]))
+                })
+                .collect();
+            Image16::from_pixels(width, height, pixels).map(Pixels::Sixteen)
+        } else {
+            let pixels = bytes
+                .chunks_exact(4)
+                .map(|pixel| pixel.try_into().expect("chunks of 4"))
+                .collect();
+            Image::from_pixels(width, height, pixels).map(Pixels::Eight)
+        }
+    }
+
+    /// Stores the decoded pixels of `path`, unless they're too large even compressed.
+    pub fn store(&self, path: &Path, pixels: &Pixels) -> io::Result<()> {
+        let stamp = Stamp::of(path)?;
+        let (magic, bytes) = match pixels {
+            Pixels::Eight(image) => (MAGIC_EIGHT, Cow::Borrowed(image.pixels().as_flattened())),
+            Pixels::Sixteen(image) => (
+                MAGIC_SIXTEEN,
+                Cow::Owned(
+                    image
+                        .pixels()
+                        .as_flattened()
+                        .iter()
+                        .flat_map(|channel| channel.to_le_bytes())
+                        .collect(),
+                ),
+            ),
+        };
+        let compressed = zstd::encode_all(&*bytes, ZSTD_LEVEL)?;
+        if compressed.len() as u64 > MAX_ENTRY {
+            tracing::debug!("Not caching {}: too large", path.display());
+            return Ok(());
+        }
+
+        let mut contents = magic.to_vec();
+        stamp.write(&mut contents);
+        contents.extend_from_slice(&(pixels.width() as u32).to_le_bytes());
+        contents.extend_from_slice(&(pixels.height() as u32).to_le_bytes());
+        contents.extend_from_slice(&compressed);
+
+        fs::create_dir_all(&self.dir)?;
+        let entry = self.entry(&stamp.path);
+        // a reader never sees a half-written entry
+        let partial = entry.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(partial, entry)?;
+        self.evict()
+    }
+
+    fn entry(&self, canonical: &Path) -> PathBuf {
+        let mut hasher = std::hash::DefaultHasher::new();
+        canonical.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.rgba.zst", hasher.finish()))
+    }
+
+    /// Removes the least recently written entries until the total fits.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = fs::read_dir(&self.dir)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect::<Vec<_>>();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= MAX_TOTAL {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use png_viewer::parse::Render;
+
+    #[test]
+    fn invalidated_by_modification() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("decoded-cache-{}", std::process::id()));
+        let file = dir.join("image.png");
+        fs::create_dir_all(&dir)?;
+        fs::write(&file, b"not really a png")?;
+        let cache = Cache::new(dir.join("cache"));
+
+        let mut image = Image16::new(3, 2);
+        image.draw_pixel(1, 1, iced::Color::from_rgb(0.1, 0.2, 0.3));
+        let image = Pixels::Sixteen(image);
+        cache.store(&file, &image)?;
+        let hit = cache.load(&file);
+
+        fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1))?;
+        let miss = cache.load(&file);
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(hit, Some(image));
+        assert_eq!(miss, None);
+        Ok(())
+    }
+}
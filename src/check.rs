@@ -0,0 +1,289 @@
+use std::io::{self, Read};
+
+use crate::parse::{
+    chunks::{self, BitDepth, ColorType, RawChunk},
+    header, Pass,
+};
+
+/// A spec violation found by [`check`], located by the byte offset of the offending chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset {:#x}: {}", self.offset, self.message)
+    }
+}
+
+struct Ihdr {
+    width: u32,
+    height: u32,
+    bit_depth: BitDepth,
+    color_type: ColorType,
+    interlaced: bool,
+}
+
+/// Validates the file against the PNG spec without rendering it, collecting every
+/// problem found rather than stopping at the first.
+pub fn check(data: &[u8]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut problem = |offset, message: String| problems.push(Problem { offset, message });
+
+    let Ok((mut input, _)) = header(data) else {
+        problem(0, "invalid PNG signature".into());
+        return problems;
+    };
+
+    let mut ihdr = None;
+    let mut palette_len = None;
+    let mut transparency = false;
+    let mut idat = Vec::new();
+    let mut idat_ended = false;
+    let mut iend = false;
+
+    while !input.is_empty() {
+        let offset = data.len() - input.len();
+        let Ok((rest, raw)) = chunks::raw_chunk(input) else {
+            problem(offset, "truncated or malformed chunk".into());
+            break;
+        };
+        input = rest;
+        let RawChunk { ty, data, crc } = raw;
+        let data: &[u8] = data.into();
+
+        if crc != raw.computed_crc() {
+            problem(offset, format!("CRC mismatch in {ty:?} chunk"));
+        }
+        if iend {
+            problem(offset, format!("{ty:?} chunk after IEND"));
+        }
+        if ihdr.is_none() && &ty.0 != b"IHDR" {
+            problem(offset, format!("{ty:?} chunk before IHDR"));
+        }
+        if !idat.is_empty() && &ty.0 != b"IDAT" {
+            idat_ended = true;
+        }
+
+        match &ty.0 {
+            b"IHDR" => {
+                if offset != 8 {
+                    problem(offset, "IHDR is not the first chunk".into());
+                }
+                if ihdr.is_some() {
+                    problem(offset, "duplicate IHDR chunk".into());
+                    continue;
+                }
+                match check_ihdr(data) {
+                    Ok(header) => ihdr = Some(header),
+                    Err(message) => {
+                        problem(offset, message);
+                        break;
+                    }
+                }
+            }
+
+            b"PLTE" => {
+                if palette_len.is_some() {
+                    problem(offset, "duplicate PLTE chunk".into());
+                }
+                if !idat.is_empty() {
+                    problem(offset, "PLTE after IDAT".into());
+                }
+                if transparency {
+                    problem(offset, "PLTE after tRNS".into());
+                }
+                if !data.len().is_multiple_of(3) || data.is_empty() || data.len() > 256 * 3 {
+                    problem(
+                        offset,
+                        format!("invalid palette size: {} bytes", data.len()),
+                    );
+                }
+                if let Some(header) = &ihdr {
+                    match header.color_type {
+                        ColorType::GrayScale | ColorType::GrayScaleAlpha => {
+                            problem(offset, "PLTE not allowed for grayscale images".into());
+                        }
+                        ColorType::Palette if data.len() / 3 > 1 << header.bit_depth as u8 => {
+                            problem(
+                                offset,
+                                format!(
+                                    "{} palette entries exceed bit depth {}",
+                                    data.len() / 3,
+                                    header.bit_depth as u8
+                                ),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                palette_len = Some(data.len() / 3);
+            }
+
+            b"IDAT" => {
+                if idat_ended {
+                    problem(offset, "IDAT chunks are not contiguous".into());
+                }
+                if matches!(&ihdr, Some(header) if header.color_type == ColorType::Palette)
+                    && palette_len.is_none()
+                {
+                    problem(offset, "palette image has no PLTE before IDAT".into());
+                }
+                idat.extend_from_slice(data);
+            }
+
+            // color space chunks apply to the palette, so they must come before it
+            b"gAMA" | b"cHRM" | b"sRGB" | b"iCCP" => {
+                if palette_len.is_some() {
+                    problem(offset, format!("{ty:?} after PLTE"));
+                }
+                if !idat.is_empty() {
+                    problem(offset, format!("{ty:?} after IDAT"));
+                }
+            }
+
+            b"tRNS" => {
+                if !idat.is_empty() {
+                    problem(offset, "tRNS after IDAT".into());
+                }
+                transparency = true;
+            }
+
+            b"IEND" => {
+                if !data.is_empty() {
+                    problem(offset, "IEND chunk is not empty".into());
+                }
+                iend = true;
+            }
+
+            _ => {
+                if ty.0[0].is_ascii_uppercase() {
+                    problem(offset, format!("unknown critical chunk {ty:?}"));
+                }
+            }
+        }
+    }
+
+    let end = data.len();
+    if !iend {
+        problem(end, "missing IEND chunk".into());
+    }
+    match (&ihdr, idat.is_empty()) {
+        (None, _) => problem(8, "missing IHDR chunk".into()),
+        (Some(_), true) => problem(end, "missing IDAT chunk".into()),
+        (Some(header), false) => {
+            // inflating one byte past the expected length is enough to tell it's too long
+            let expected = expected_len(header);
+            let mut inflated =
+                flate2::read::ZlibDecoder::new(idat.as_slice()).take(expected as u64 + 1);
+            match io::copy(&mut inflated, &mut io::sink()) {
+                Ok(len) if len > expected as u64 => problem(
+                    end,
+                    format!("image data inflates to more than the expected {expected} bytes"),
+                ),
+                Ok(len) if len < expected as u64 => problem(
+                    end,
+                    format!("image data inflates to {len} bytes, expected {expected}"),
+                ),
+                Ok(_) => {}
+                Err(error) => problem(end, format!("zlib stream is corrupt: {error}")),
+            }
+        }
+    }
+
+    problems
+}
+
+fn check_ihdr(data: &[u8]) -> Result<Ihdr, String> {
+    let &[w0, w1, w2, w3, h0, h1, h2, h3, bit_depth, color_type, compression, filter, interlace] =
+        data
+    else {
+        return Err(format!("IHDR must be 13 bytes, found {}", data.len()));
+    };
+    let width = u32::from_be_bytes([w0, w1, w2, w3]);
+    let height = u32::from_be_bytes([h0, h1, h2, h3]);
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(format!("invalid dimensions: {width}x{height}"));
+    }
+    let bit_depth = BitDepth::try_from(bit_depth).map_err(|error| error.to_string())?;
+    let color_type = ColorType::try_from(color_type).map_err(|error| error.to_string())?;
+    chunks::bits_per_pixel(bit_depth, color_type).map_err(|error| error.to_string())?;
+    if compression != 0 {
+        return Err(format!("invalid compression method: {compression}"));
+    }
+    if filter != 0 {
+        return Err(format!("invalid filter method: {filter}"));
+    }
+    if interlace > 1 {
+        return Err(format!("invalid interlace method: {interlace}"));
+    }
+
+    Ok(Ihdr {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        interlaced: interlace == 1,
+    })
+}
+
+/// Size of the filtered scanline stream the IDAT data should inflate to.
+fn expected_len(header: &Ihdr) -> usize {
+    let bits_per_pixel =
+        chunks::bits_per_pixel(header.bit_depth, header.color_type).expect("checked in check_ihdr");
+    let (width, height) = (header.width as usize, header.height as usize);
+    let pass_len = |width: usize, height: usize| {
+        if width == 0 {
+            0
+        } else {
+            height.saturating_mul(width.saturating_mul(bits_per_pixel).div_ceil(8) + 1)
+        }
+    };
+
+    let passes: &[Pass] = if header.interlaced {
+        &Pass::ADAM7
+    } else {
+        &[Pass::FULL]
+    };
+    passes
+        .iter()
+        .map(|pass| pass_len(pass.width(width), pass.height(height)))
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PNG: &[u8] = include_bytes!("../assets/xkcd.png");
+
+    #[test]
+    fn valid_file_passes() {
+        assert_eq!(check(PNG), []);
+    }
+
+    #[test]
+    fn corruption_is_reported() {
+        let mut data = PNG.to_vec();
+        data[40] ^= 1;
+        data.truncate(data.len() - 12);
+        let problems: Vec<_> = check(&data).into_iter().map(|p| p.message).collect();
+        assert_eq!(
+            problems,
+            ["CRC mismatch in pHYr chunk", "missing IEND chunk"]
+        );
+    }
+
+    #[test]
+    fn misplaced_chunks_are_reported() {
+        let iend = PNG.len() - 12;
+        let mut data = PNG[..iend].to_vec();
+        crate::encode::write_chunk(&mut data, b"gAMA", &100_000u32.to_be_bytes());
+        crate::encode::write_chunk(&mut data, b"tRNS", &[0, 0]);
+        data.extend_from_slice(&PNG[iend..]);
+        let problems: Vec<_> = check(&data).into_iter().map(|p| p.message).collect();
+        assert_eq!(problems, ["gAMA after IDAT", "tRNS after IDAT"]);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    io::{self, Write},
+    process::{self, Stdio},
+};
+
+/// Puts a PNG on the clipboard, through whatever the platform offers for scripting it.
+pub async fn copy_png(data: Vec<u8>) -> io::Result<()> {
+    tokio::task::spawn_blocking(move || copy(&data)).await?
+}
+
+#[cfg(target_os = "macos")]
+fn copy(data: &[u8]) -> io::Result<()> {
+    let path = std::env::temp_dir().join(format!("png-viewer-copy-{}.png", process::id()));
+    std::fs::write(&path, data)?;
+    let script = format!(
+        "set the clipboard to (read (POSIX file {:?}) as «class PNGf»)",
+        path.display().to_string()
+    );
+    run(
+        process::Command::new("osascript").args(["-e", &script]),
+        None,
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn copy(data: &[u8]) -> io::Result<()> {
+    let path = std::env::temp_dir().join(format!("png-viewer-copy-{}.png", process::id()));
+    std::fs::write(&path, data)?;
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         [Windows.Forms.Clipboard]::SetImage([Drawing.Image]::FromFile('{}'))",
+        path.display()
+    );
+    run(
+        process::Command::new("powershell").args(["-NoProfile", "-STA", "-Command", &script]),
+        None,
+    )
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn copy(data: &[u8]) -> io::Result<()> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        run(
+            process::Command::new("wl-copy").args(["--type", "image/png"]),
+            Some(data),
+        )
+    } else {
+        run(
+            process::Command::new("xclip").args(["-selection", "clipboard", "-t", "image/png"]),
+            Some(data),
+        )
+    }
+}
+
+/// Runs `command` to completion, feeding it `input`.
+fn run(command: &mut process::Command, input: Option<&[u8]>) -> io::Result<()> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input)?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "clipboard command failed: {status}"
+        )))
+    }
+}
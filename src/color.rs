@@ -0,0 +1,621 @@
+//! Color management: works out the color space a file declares with its iCCP, sRGB, gAMA and
+//! cHRM chunks, and converts decoded samples from it to the display's, rather than assuming
+//! both are sRGB.
+
+use std::{
+    hash::{Hash, Hasher},
+    io::Read,
+    sync::OnceLock,
+};
+
+use flate2::read::ZlibDecoder;
+
+use crate::parse::error::Error;
+
+/// White point of the ICC profile connection space.
+const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+/// Cone response matrix for chromatic adaptation.
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+pub const SRGB_CHROMATICITIES: Chromaticities = Chromaticities {
+    white: [0.3127, 0.3290],
+    red: [0.64, 0.33],
+    green: [0.30, 0.60],
+    blue: [0.15, 0.06],
+};
+
+type Matrix = [[f32; 3]; 3];
+
+static DISPLAY: OnceLock<Profile> = OnceLock::new();
+
+/// Sets the display's profile, for the rest of the process. Has no effect once anything has
+/// asked for it.
+pub fn set_display_profile(profile: Profile) {
+    if DISPLAY.set(profile).is_err() {
+        tracing::warn!("display profile already in use");
+    }
+}
+
+/// The display's profile, sRGB unless [`set_display_profile`] said otherwise.
+pub fn display_profile() -> &'static Profile {
+    DISPLAY.get_or_init(Profile::srgb)
+}
+
+/// CIE xy chromaticities of a white point and three primaries, as stored in cHRM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chromaticities {
+    pub white: [f32; 2],
+    pub red: [f32; 2],
+    pub green: [f32; 2],
+    pub blue: [f32; 2],
+}
+
+/// A tone curve between encoded samples and linear light.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve {
+    /// The ICC parametric curve: `(a·x + b)^g + e` from `d` up, and `c·x + f` below.
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+    /// Evenly spaced samples, interpolated linearly.
+    Table(Vec<f32>),
+}
+
+impl Curve {
+    pub const SRGB: Self = Self::Parametric {
+        g: 2.4,
+        a: 1.0 / 1.055,
+        b: 0.055 / 1.055,
+        c: 1.0 / 12.92,
+        d: 0.04045,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    /// A plain power law, `x^exponent`.
+    pub fn gamma(exponent: f32) -> Self {
+        Self::Parametric {
+            g: exponent,
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 0.0,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    /// From an encoded sample to linear light.
+    fn decode(&self, x: f32) -> f32 {
+        match *self {
+            Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= d {
+                    (a * x + b).max(0.0).powf(g) + e
+                } else {
+                    c * x + f
+                }
+            }
+            Self::Table(ref table) => interpolate(table, x),
+        }
+    }
+
+    /// From linear light to an encoded sample.
+    fn encode(&self, y: f32) -> f32 {
+        let x = match *self {
+            Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if y >= (a * d + b).max(0.0).powf(g) + e {
+                    ((y - e).max(0.0).powf(1.0 / g) - b) / a
+                } else if c != 0.0 {
+                    (y - f) / c
+                } else {
+                    d
+                }
+            }
+            Self::Table(ref table) => {
+                // tone curves only ever rise
+                let above = table.partition_point(|&value| value < y);
+                match above {
+                    0 => 0.0,
+                    _ if above == table.len() => 1.0,
+                    _ => {
+                        let (low, high) = (table[above - 1], table[above]);
+                        let fraction = if high > low {
+                            (y - low) / (high - low)
+                        } else {
+                            0.0
+                        };
+                        (above - 1) as f32 / (table.len() - 1) as f32
+                            + fraction / (table.len() - 1) as f32
+                    }
+                }
+            }
+        };
+        x.clamp(0.0, 1.0)
+    }
+}
+
+fn interpolate(table: &[f32], x: f32) -> f32 {
+    let Some(last) = table.len().checked_sub(1) else {
+        return x;
+    };
+    let position = x.clamp(0.0, 1.0) * last as f32;
+    let index = (position as usize).min(last.saturating_sub(1));
+    let next = (index + 1).min(last);
+    let fraction = position - index as f32;
+    table[index] + (table[next] - table[index]) * fraction
+}
+
+/// A color space: a tone curve per channel, and the primaries as a matrix from linear RGB to
+/// XYZ relative to D50, the way ICC profiles store them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    curves: [Curve; 3],
+    to_xyz: Matrix,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::srgb()
+    }
+}
+
+impl Profile {
+    pub fn srgb() -> Self {
+        Self::new(SRGB_CHROMATICITIES, Curve::SRGB).expect("sRGB primaries are valid")
+    }
+
+    /// A hash of the curves and primaries, to tell apart colors converted for different
+    /// displays.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = std::hash::DefaultHasher::new();
+        // the debug form spells out every float exactly
+        format!("{self:?}").hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns `None` if the chromaticities don't describe three independent primaries.
+    pub fn new(chromaticities: Chromaticities, curve: Curve) -> Option<Self> {
+        let xyz = |[x, y]: [f32; 2]| [x / y, 1.0, (1.0 - x - y) / y];
+        let primaries = [
+            chromaticities.red,
+            chromaticities.green,
+            chromaticities.blue,
+        ]
+        .map(xyz);
+        let columns = transpose(&primaries);
+        let white = xyz(chromaticities.white);
+        let scale = apply(&invert(&columns)?, white);
+        let to_xyz = columns.map(|row| [0, 1, 2].map(|c| row[c] * scale[c]));
+
+        // adapt from the file's white point to D50, like an ICC profile would
+        let cone_white = apply(&BRADFORD, white);
+        let cone_d50 = apply(&BRADFORD, D50);
+        let mut adapt = [[0.0; 3]; 3];
+        for (i, row) in adapt.iter_mut().enumerate() {
+            row[i] = cone_d50[i] / cone_white[i];
+        }
+        let adapt = multiply(&invert(&BRADFORD)?, &multiply(&adapt, &BRADFORD));
+
+        let to_xyz = multiply(&adapt, &to_xyz);
+        to_xyz
+            .as_flattened()
+            .iter()
+            .all(|value| value.is_finite())
+            .then(|| Self {
+                curves: [curve.clone(), curve.clone(), curve],
+                to_xyz,
+            })
+    }
+
+    /// Reads an RGB or gray ICC profile built from a matrix and tone curves, which is what
+    /// nearly every profile embedded in a PNG is. Lookup-table profiles aren't supported.
+    pub fn from_icc(data: &[u8]) -> Result<Self, Error> {
+        if data.get(36..40) != Some(b"acsp") {
+            return Err(Error::Icc("missing acsp signature"));
+        }
+        let count = be_u32(data, 128).ok_or(Error::Icc("missing tag table"))?;
+        let tag = |signature: &[u8; 4]| {
+            (0..count as usize)
+                .map(|index| 132 + 12 * index)
+                .take_while(|&entry| entry + 12 <= data.len())
+                .find(|&entry| data.get(entry..entry + 4) == Some(signature))
+                .and_then(|entry| {
+                    let offset = be_u32(data, entry + 4)? as usize;
+                    let size = be_u32(data, entry + 8)? as usize;
+                    data.get(offset..offset.checked_add(size)?)
+                })
+        };
+
+        match data.get(16..20) {
+            Some(b"RGB ") => {
+                let mut columns = [[0.0; 3]; 3];
+                for (column, signature) in columns.iter_mut().zip([b"rXYZ", b"gXYZ", b"bXYZ"]) {
+                    *column = tag(signature)
+                        .and_then(xyz)
+                        .ok_or(Error::Icc("no matrix colorants"))?;
+                }
+                let [red, green, blue] = [b"rTRC", b"gTRC", b"bTRC"].map(|signature| {
+                    tag(signature)
+                        .ok_or(Error::Icc("no tone curves"))
+                        .and_then(curve)
+                });
+                Ok(Self {
+                    curves: [red?, green?, blue?],
+                    to_xyz: transpose(&columns),
+                })
+            }
+            // a gray sample is the same in every channel, so only the curve matters
+            Some(b"GRAY") => {
+                let curve = tag(b"kTRC")
+                    .ok_or(Error::Icc("no gray curve"))
+                    .and_then(curve)?;
+                Ok(Self {
+                    curves: [curve.clone(), curve.clone(), curve],
+                    ..Self::srgb()
+                })
+            }
+            _ => Err(Error::Icc("not an RGB or gray profile")),
+        }
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn s15_fixed16(data: &[u8], offset: usize) -> Option<f32> {
+    Some(be_u32(data, offset)? as i32 as f32 / 65536.0)
+}
+
+fn xyz(tag: &[u8]) -> Option<[f32; 3]> {
+    if tag.get(..4) != Some(b"XYZ ") {
+        return None;
+    }
+    Some([
+        s15_fixed16(tag, 8)?,
+        s15_fixed16(tag, 12)?,
+        s15_fixed16(tag, 16)?,
+    ])
+}
+
+const MALFORMED_CURVE: Error = Error::Icc("malformed tone curve");
+
+fn curve(tag: &[u8]) -> Result<Curve, Error> {
+    match tag.get(..4) {
+        Some(b"curv") => {
+            let count = be_u32(tag, 8).ok_or(MALFORMED_CURVE)? as usize;
+            let entries = tag.get(12..12 + 2 * count).ok_or(MALFORMED_CURVE)?;
+            let entries: Vec<_> = entries
+                .chunks_exact(2)
+                .map(|entry| u16::from_be_bytes([entry[0], entry[1]]))
+                .collect();
+            Ok(match entries[..] {
+                [] => Curve::gamma(1.0),
+                // an 8.8 fixed point exponent
+                [exponent] => Curve::gamma(exponent as f32 / 256.0),
+                _ => Curve::Table(
+                    entries
+                        .into_iter()
+                        .map(|entry| entry as f32 / u16::MAX as f32)
+                        .collect(),
+                ),
+            })
+        }
+        Some(b"para") => {
+            let function = tag.get(8..10).ok_or(MALFORMED_CURVE)?;
+            let parameters: Vec<_> = (12..tag.len())
+                .step_by(4)
+                .map_while(|offset| s15_fixed16(tag, offset))
+                .collect();
+            let curve = match (
+                u16::from_be_bytes([function[0], function[1]]),
+                &parameters[..],
+            ) {
+                (0, &[g, ..]) => Curve::gamma(g),
+                (1, &[g, a, b, ..]) => Curve::Parametric {
+                    g,
+                    a,
+                    b,
+                    c: 0.0,
+                    d: -b / a,
+                    e: 0.0,
+                    f: 0.0,
+                },
+                (2, &[g, a, b, c, ..]) => Curve::Parametric {
+                    g,
+                    a,
+                    b,
+                    c: 0.0,
+                    d: -b / a,
+                    e: c,
+                    f: c,
+                },
+                (3, &[g, a, b, c, d, ..]) => Curve::Parametric {
+                    g,
+                    a,
+                    b,
+                    c,
+                    d,
+                    e: 0.0,
+                    f: 0.0,
+                },
+                (4, &[g, a, b, c, d, e, f, ..]) => Curve::Parametric {
+                    g,
+                    a,
+                    b,
+                    c,
+                    d,
+                    e,
+                    f,
+                },
+                _ => return Err(MALFORMED_CURVE),
+            };
+            Ok(curve)
+        }
+        _ => Err(Error::Icc("unsupported tone curve type")),
+    }
+}
+
+/// What a file says about its color space.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    /// The zlib-compressed profile from iCCP.
+    pub icc: Option<Vec<u8>>,
+    pub srgb: bool,
+    /// The encoding exponent from gAMA.
+    pub gamma: Option<f32>,
+    pub chromaticities: Option<Chromaticities>,
+}
+
+impl Source {
+    /// The profile the samples are in. iCCP wins over sRGB, which wins over gAMA and cHRM, as
+    /// the PNG spec asks; a file that says nothing is taken to be sRGB. An embedded profile
+    /// that inflates past `max_icc_bytes` is ignored.
+    pub fn profile(&self, max_icc_bytes: u64) -> Profile {
+        if let Some(compressed) = &self.icc {
+            match inflate(compressed, max_icc_bytes).and_then(|profile| Profile::from_icc(&profile))
+            {
+                Ok(profile) => return profile,
+                Err(error) => tracing::warn!("ignoring the iCCP profile: {error}"),
+            }
+        }
+        if self.srgb {
+            return Profile::srgb();
+        }
+        let curve = match self.gamma {
+            // 1/2.2 is how most encoders spell sRGB
+            Some(gamma) if gamma > 0.0 && !(0.45..=0.46).contains(&gamma) => {
+                Curve::gamma(1.0 / gamma)
+            }
+            _ => Curve::SRGB,
+        };
+        let chromaticities = self.chromaticities.unwrap_or(SRGB_CHROMATICITIES);
+        Profile::new(chromaticities, curve.clone()).unwrap_or_else(|| {
+            tracing::warn!("ignoring invalid cHRM chromaticities: {chromaticities:?}");
+            Profile {
+                curves: [curve.clone(), curve.clone(), curve],
+                ..Profile::srgb()
+            }
+        })
+    }
+}
+
+fn inflate(compressed: &[u8], max_bytes: u64) -> Result<Vec<u8>, Error> {
+    let mut profile = vec![];
+    ZlibDecoder::new(compressed)
+        .take(max_bytes + 1)
+        .read_to_end(&mut profile)?;
+    if profile.len() as u64 > max_bytes {
+        return Err(Error::LimitExceeded(
+            "inflated ICC profile",
+            profile.len() as u64,
+            max_bytes,
+        ));
+    }
+    Ok(profile)
+}
+
+/// Converts colors from one profile to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    from: [Curve; 3],
+    matrix: Matrix,
+    to: [Curve; 3],
+}
+
+impl Transform {
+    /// Returns `None` when there's nothing to convert, so most files cost nothing extra.
+    pub fn new(from: &Profile, to: &Profile) -> Option<Self> {
+        if from == to {
+            return None;
+        }
+        Some(Self {
+            from: from.curves.clone(),
+            matrix: multiply(&invert(&to.to_xyz)?, &from.to_xyz),
+            to: to.curves.clone(),
+        })
+    }
+
+    pub fn apply(&self, color: iced::Color) -> iced::Color {
+        let encoded = [color.r, color.g, color.b];
+        let linear = apply(
+            &self.matrix,
+            std::array::from_fn(|c| self.from[c].decode(encoded[c])),
+        );
+        let [r, g, b] = std::array::from_fn(|c| self.to[c].encode(linear[c].clamp(0.0, 1.0)));
+        iced::Color { r, g, b, ..color }
+    }
+}
+
+fn apply(matrix: &Matrix, vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| (0..3).map(|c| row[c] * vector[c]).sum())
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| matrix[j][i]))
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant: f32 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+    Some(std::array::from_fn(|i| {
+        std::array::from_fn(|j| cofactor(j, i) / determinant)
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::chunks::Limits;
+
+    fn max_icc_bytes() -> u64 {
+        Limits::default().max_metadata_bytes
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.002
+    }
+
+    /// A matrix/TRC profile with sRGB colorants and the given `curv` entries for every
+    /// channel.
+    fn icc(curve: &[u16]) -> Vec<u8> {
+        let colorants = transpose(&Profile::srgb().to_xyz);
+        let mut tags: Vec<(&[u8; 4], Vec<u8>)> = [b"rXYZ", b"gXYZ", b"bXYZ"]
+            .into_iter()
+            .zip(colorants)
+            .map(|(signature, column)| {
+                let mut tag = b"XYZ \0\0\0\0".to_vec();
+                for value in column {
+                    tag.extend(((value * 65536.0).round() as i32).to_be_bytes());
+                }
+                (signature, tag)
+            })
+            .collect();
+        let mut curv = b"curv\0\0\0\0".to_vec();
+        curv.extend((curve.len() as u32).to_be_bytes());
+        curv.extend(curve.iter().flat_map(|entry| entry.to_be_bytes()));
+        tags.extend([b"rTRC", b"gTRC", b"bTRC"].map(|signature| (signature, curv.clone())));
+
+        let mut header = vec![0; 128];
+        header[16..20].copy_from_slice(b"RGB ");
+        header[36..40].copy_from_slice(b"acsp");
+        header.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = header.len() + 12 * tags.len();
+        let mut body = vec![];
+        for (signature, tag) in &tags {
+            header.extend(*signature);
+            header.extend((offset as u32).to_be_bytes());
+            header.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+            body.extend(tag);
+        }
+        [header, body].concat()
+    }
+
+    #[test]
+    fn srgb_files_are_left_alone() {
+        let display = Profile::srgb();
+        assert!(Transform::new(&Source::default().profile(max_icc_bytes()), &display).is_none());
+        let srgb = Source {
+            srgb: true,
+            gamma: Some(0.45455),
+            ..Default::default()
+        };
+        assert!(Transform::new(&srgb.profile(max_icc_bytes()), &display).is_none());
+    }
+
+    #[test]
+    fn linear_samples_are_encoded_for_the_display() -> Result<(), Error> {
+        let linear = Source {
+            gamma: Some(1.0),
+            ..Default::default()
+        };
+        let transform = Transform::new(&linear.profile(max_icc_bytes()), &Profile::srgb()).unwrap();
+        let color = transform.apply(iced::Color::from_rgba(0.5, 0.0, 1.0, 0.25));
+        assert!(close(color.r, 0.7354) && close(color.g, 0.0) && close(color.b, 1.0));
+        assert_eq!(color.a, 0.25);
+
+        // the same, described by an embedded profile instead
+        let profile = Profile::from_icc(&icc(&[256]))?;
+        let transform = Transform::new(&profile, &Profile::srgb()).unwrap();
+        assert!(close(
+            transform.apply(iced::Color::from_rgb(0.5, 0.5, 0.5)).g,
+            0.7354
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_profile_is_ignored() -> Result<(), Error> {
+        let embedded = Source {
+            icc: Some(crate::encode::compress(&icc(&[256]), Default::default())?),
+            ..Default::default()
+        };
+        assert_eq!(
+            embedded.profile(max_icc_bytes()),
+            Profile::from_icc(&icc(&[256]))?
+        );
+        assert_eq!(embedded.profile(64), Profile::srgb());
+        Ok(())
+    }
+
+    #[test]
+    fn wide_gamut_red_is_clipped() {
+        // Adobe RGB primaries
+        let wide = Source {
+            chromaticities: Some(Chromaticities {
+                red: [0.64, 0.33],
+                green: [0.21, 0.71],
+                ..SRGB_CHROMATICITIES
+            }),
+            ..Default::default()
+        };
+        let transform = Transform::new(&wide.profile(max_icc_bytes()), &Profile::srgb()).unwrap();
+        let green = transform.apply(iced::Color::from_rgb(0.0, 1.0, 0.0));
+        // a purer green than sRGB can show pulls red below zero
+        assert_eq!(green.r, 0.0);
+        assert!(close(green.g, 1.0));
+        let gray = transform.apply(iced::Color::from_rgb(0.5, 0.5, 0.5));
+        assert!(close(gray.r, 0.5) && close(gray.g, 0.5) && close(gray.b, 0.5));
+    }
+}
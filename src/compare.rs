@@ -0,0 +1,338 @@
+use std::{cell::RefCell, fmt, path::PathBuf};
+
+use iced::{
+    alignment, mouse,
+    widget::canvas::{self, Cache, Frame, Geometry, Program},
+    Color, Point, Rectangle, Renderer, Size, Theme, Vector,
+};
+use png_viewer::{
+    color,
+    parse::{
+        self,
+        chunks::Handlers,
+        image::{Image, Pixels},
+    },
+};
+
+use crate::Message;
+
+/// How the second image is shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    SideBySide,
+    /// Laid over the viewed image, faded and cut off at a divider.
+    Overlay,
+    /// Side by side, with a heatmap of where they differ as a third view.
+    Difference,
+}
+
+impl Mode {
+    pub const ALL: [Self; 3] = [Self::SideBySide, Self::Overlay, Self::Difference];
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SideBySide => "Side by side",
+            Self::Overlay => "Overlay",
+            Self::Difference => "Difference",
+        })
+    }
+}
+
+/// A second image shown next to the one being viewed, following its zoom and pan.
+#[derive(Debug)]
+pub struct Compare {
+    path: PathBuf,
+    image: Image,
+    mode: Mode,
+    opacity: f32,
+    /// `image` with its alpha scaled by `opacity`.
+    faded: Image,
+    /// Where the divider sits across the overlay, from 0 (all shown) to 1 (none shown).
+    swipe: f32,
+    /// Channel deltas up to this don't count as differences.
+    tolerance: u8,
+    /// `None` until compared, or if the sizes don't match.
+    difference: Option<Difference>,
+    layer: Layer,
+    heatmap_layer: Layer,
+}
+
+/// Where the viewed image and the second one differ.
+#[derive(Debug)]
+struct Difference {
+    /// Pixels whose largest channel delta exceeds the tolerance.
+    count: usize,
+    heatmap: Image,
+}
+
+/// A cache and the view last drawn into it.
+#[derive(Debug, Default)]
+struct Layer {
+    cache: Cache,
+    drawn: RefCell<Option<(parse::State, f32, f32)>>,
+}
+
+impl Layer {
+    /// Clears the cache unless it was drawn with `key`.
+    fn redraw_unless(&self, key: (parse::State, f32, f32)) {
+        let mut drawn = self.drawn.borrow_mut();
+        if drawn.as_ref() != Some(&key) {
+            self.cache.clear();
+            *drawn = Some(key);
+        }
+    }
+}
+
+impl Compare {
+    /// Decodes the image at `path`, blocking until it's done.
+    pub fn open(path: PathBuf) -> Option<Self> {
+        let image = std::fs::read(&path)
+            .map_err(parse::error::Error::from)
+            .and_then(|data| {
+                parse::decode_with(&data, Handlers::default().display(color::display_profile()))
+            })
+            .map_err(|error| tracing::error!("from parse::decode: {}: {error}", path.display()))
+            .ok()?;
+        let mut compare = Self {
+            path,
+            faded: image.clone(),
+            image,
+            mode: Mode::default(),
+            opacity: 1.0,
+            swipe: 0.0,
+            tolerance: 0,
+            difference: None,
+            layer: Layer::default(),
+            heatmap_layer: Layer::default(),
+        };
+        compare.set_opacity(0.5);
+        Some(compare)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        let pixels = self
+            .image
+            .pixels()
+            .iter()
+            .map(|&[r, g, b, a]| [r, g, b, (a as f32 * self.opacity).round() as u8])
+            .collect();
+        if let Some(faded) = Image::from_pixels(self.image.width(), self.image.height(), pixels) {
+            self.faded = faded;
+        }
+    }
+
+    pub fn set_swipe(&mut self, swipe: f32) {
+        self.swipe = swipe.clamp(0.0, 1.0);
+    }
+
+    pub fn tolerance(&self) -> u8 {
+        self.tolerance
+    }
+
+    pub fn set_tolerance(&mut self, tolerance: u8) {
+        self.tolerance = tolerance;
+    }
+
+    /// Redraws against `base`, the image now being viewed, and works out where the two
+    /// differ if that's being shown.
+    pub fn set_base(&mut self, base: &Pixels) {
+        self.layer.cache.clear();
+        self.heatmap_layer.cache.clear();
+        if self.mode != Mode::Difference {
+            return;
+        }
+        self.difference = base
+            .to_eight()
+            .diff(&self.image, self.tolerance)
+            .map(|diff| Difference {
+                count: diff.count,
+                heatmap: diff.heatmap(self.image.width(), self.tolerance),
+            });
+    }
+
+    /// The pane for this image, seen the way `state` shows the main one. In overlay mode
+    /// it's drawn over `under`.
+    pub fn pane<'a>(&'a self, state: &'a parse::State, under: &'a Pixels) -> Pane<'a> {
+        Pane {
+            compare: self,
+            under: (self.mode == Mode::Overlay).then_some((state, under)),
+            heatmap: false,
+            state: state.with_image_size(self.size()),
+        }
+    }
+
+    /// The heatmap of differences, seen the way `state` shows the main image.
+    pub fn heatmap_pane(&self, state: &parse::State) -> Pane<'_> {
+        Pane {
+            compare: self,
+            under: None,
+            heatmap: true,
+            state: state.with_image_size(self.size()),
+        }
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.image.width() as f32, self.image.height() as f32)
+    }
+
+    fn name(&self) -> String {
+        self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+pub struct Pane<'a> {
+    compare: &'a Compare,
+    /// The viewed image and its view, when overlaying.
+    under: Option<(&'a parse::State, &'a Pixels)>,
+    /// Shows the difference heatmap instead of the image.
+    heatmap: bool,
+    state: parse::State,
+}
+
+impl Pane<'_> {
+    fn label(&self) -> String {
+        let compare = self.compare;
+        match &compare.difference {
+            _ if !self.heatmap => compare.name(),
+            Some(difference) if difference.count == 1 => "1 pixel differs".into(),
+            Some(difference) => format!("{} pixels differ", difference.count),
+            None => "The images aren't the same size".into(),
+        }
+    }
+}
+
+impl Program<Message> for Pane<'_> {
+    /// Whether the swipe divider is being dragged.
+    type State = bool;
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer<Theme>,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let compare = self.compare;
+        let layer = if self.heatmap {
+            &compare.heatmap_layer
+        } else {
+            &compare.layer
+        };
+        layer.redraw_unless((self.state.clone(), compare.opacity, compare.swipe));
+        let divider = bounds.width * compare.swipe;
+        let image = layer.cache.draw(renderer, bounds.size(), |frame| {
+            if self.heatmap {
+                if let Some(difference) = &compare.difference {
+                    parse::render(frame, &difference.heatmap, &self.state);
+                }
+                return;
+            }
+            let Some((state, under)) = self.under else {
+                parse::render(frame, &compare.image, &self.state);
+                return;
+            };
+            frame.with_save(|frame| match under {
+                Pixels::Eight(image) => parse::render(frame, image, state),
+                Pixels::Sixteen(image) => parse::render(frame, image, state),
+            });
+            let shown = Rectangle::new(
+                Point::new(divider, 0.0),
+                Size::new(bounds.width - divider, bounds.height),
+            );
+            frame.with_clip(shown, |frame| {
+                // the clip moves the origin to its corner
+                frame.translate(Vector::new(-divider, 0.0));
+                parse::render(frame, &compare.faded, &self.state)
+            });
+        });
+
+        let mut label = Frame::new(renderer, bounds.size());
+        if self.under.is_some() {
+            label.fill_rectangle(
+                Point::new(divider - 1.0, 0.0),
+                Size::new(2.0, bounds.height),
+                Color::WHITE,
+            );
+        }
+        label.fill_text(canvas::Text {
+            content: self.label(),
+            position: Point::new(bounds.width / 2.0, 10.0),
+            color: Color::from_rgb(0.8, 0.8, 0.8),
+            horizontal_alignment: alignment::Horizontal::Center,
+            ..Default::default()
+        });
+        vec![image, label.into_geometry()]
+    }
+
+    fn update(
+        &self,
+        swiping: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        let swipe_to = |x: f32| Message::CompareSwipe((x - bounds.x) / bounds.width);
+        let message = match event {
+            // the viewer's own canvas is hidden, so the view has to follow this one's size
+            _ if let Some((state, _)) = self.under
+                && state.viewport() != bounds.size() =>
+            {
+                Some(Message::Resized(bounds.size()))
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if self.under.is_some() && cursor.is_over(bounds) =>
+            {
+                *swiping = true;
+                cursor.position().map(|position| swipe_to(position.x))
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { position }) if *swiping => {
+                Some(swipe_to(position.x))
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if *swiping => {
+                *swiping = false;
+                None
+            }
+            canvas::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let (mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. }) =
+                    delta;
+                if y > 0.0 {
+                    Some(Message::ZoomIn(cursor.position_in(bounds)))
+                } else if y < 0.0 {
+                    Some(Message::ZoomOut(cursor.position_in(bounds)))
+                } else {
+                    None
+                }
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if cursor.is_over(bounds) =>
+            {
+                Some(Message::ZoomToggle(cursor.position_in(bounds)))
+            }
+            _ => None,
+        };
+        match message {
+            Some(message) => (canvas::event::Status::Captured, Some(message)),
+            None => (canvas::event::Status::Ignored, None),
+        }
+    }
+}
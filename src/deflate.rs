@@ -0,0 +1,365 @@
+//! Walks a zlib stream block by block without keeping its output, to see how an encoder
+//! compressed the image data.
+
+use crate::parse::error::Error;
+
+/// Extra bits and base values of the length and distance codes, from RFC 1951.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const END_OF_BLOCK: u16 = 256;
+
+/// The zlib header and every deflate block after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    /// How far back matches may reach, from the header.
+    pub window_size: usize,
+    /// The header's hint of how hard the encoder tried, 0 (fastest) to 3 (smallest).
+    pub level: u8,
+    /// Matches may reach into a dictionary agreed on outside the stream. PNG forbids it.
+    pub preset_dictionary: bool,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockType {
+    /// Copied as is.
+    Stored,
+    /// Huffman coded with the codes the format predefines.
+    Fixed,
+    /// Huffman coded with codes stored at the start of the block.
+    Dynamic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub ty: BlockType,
+    /// Offset of the block's first bit into the stream, zlib header included.
+    pub start_bit: usize,
+    /// Compressed size, code tables included.
+    pub bits: usize,
+    /// Bytes the block inflates to.
+    pub output: usize,
+    pub literals: usize,
+    /// Back-references to earlier output, the sliding dictionary.
+    pub matches: usize,
+    /// Output bytes that came from matches.
+    pub matched: usize,
+    pub max_distance: usize,
+    total_distance: u64,
+}
+
+impl Block {
+    fn new(ty: BlockType, start_bit: usize) -> Self {
+        Self {
+            ty,
+            start_bit,
+            bits: 0,
+            output: 0,
+            literals: 0,
+            matches: 0,
+            matched: 0,
+            max_distance: 0,
+            total_distance: 0,
+        }
+    }
+
+    pub fn mean_distance(&self) -> Option<f64> {
+        (self.matches > 0).then(|| self.total_distance as f64 / self.matches as f64)
+    }
+}
+
+/// Reads the stream through to the end of its last block.
+pub fn inspect(zlib: &[u8]) -> Result<Stream, Error> {
+    let [cmf, flg, ..] = *zlib else {
+        return Err(Error::InvalidDeflate(
+            0,
+            "stream too short for a zlib header",
+        ));
+    };
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(Error::InvalidDeflate(0, "not a zlib header for deflate"));
+    }
+    let preset_dictionary = flg & 0x20 != 0;
+    let mut bits = Bits {
+        data: zlib,
+        position: if preset_dictionary { 6 * 8 } else { 2 * 8 },
+    };
+
+    let mut blocks = vec![];
+    let mut total = 0;
+    loop {
+        let start_bit = bits.position;
+        let last = bits.take(1)? == 1;
+        let ty = match bits.take(2)? {
+            0 => BlockType::Stored,
+            1 => BlockType::Fixed,
+            2 => BlockType::Dynamic,
+            _ => return Err(bits.error("reserved block type")),
+        };
+        let mut block = Block::new(ty, start_bit);
+        match ty {
+            BlockType::Stored => {
+                bits.align();
+                let length = bits.take(16)?;
+                if length != !bits.take(16)? & 0xffff {
+                    return Err(bits.error("stored block length doesn't match its complement"));
+                }
+                bits.skip_bytes(length as usize)?;
+                block.output = length as usize;
+            }
+            BlockType::Fixed => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                codes(&mut bits, &literals, &distances, total, &mut block)?;
+            }
+            BlockType::Dynamic => {
+                let (literals, distances) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &literals, &distances, total, &mut block)?;
+            }
+        }
+        block.bits = bits.position - start_bit;
+        total += block.output;
+        blocks.push(block);
+        if last {
+            break;
+        }
+    }
+
+    Ok(Stream {
+        window_size: 1 << ((cmf >> 4) + 8),
+        level: flg >> 6,
+        preset_dictionary,
+        blocks,
+    })
+}
+
+/// Reads bits least significant first, the way deflate packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, count: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        for shift in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or_else(|| self.error("stream ends inside a block"))?;
+            value |= u32::from(byte >> (self.position % 8) & 1) << shift;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.next_multiple_of(8);
+    }
+
+    fn skip_bytes(&mut self, count: usize) -> Result<(), Error> {
+        if self.position / 8 + count > self.data.len() {
+            return Err(self.error("stream ends inside a stored block"));
+        }
+        self.position += count * 8;
+        Ok(())
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        Error::InvalidDeflate(self.position / 8, reason)
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, Error> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(bits.error("invalid Huffman code"))
+    }
+}
+
+/// Reads the code length tables a dynamic block starts with.
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman), Error> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(bits.error("too many codes in a dynamic block"));
+    }
+
+    let mut code_lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| bits.error("length repeated before any was given"))?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(bits.error("code lengths run past the tables"));
+    }
+    if lengths[END_OF_BLOCK as usize] == 0 {
+        return Err(bits.error("dynamic block has no end-of-block code"));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+/// Reads literals and matches up to the end of the block, counting them into `block`.
+/// `before` is how much output earlier blocks made, which matches can reach back into.
+fn codes(
+    bits: &mut Bits,
+    literals: &Huffman,
+    distances: &Huffman,
+    before: usize,
+    block: &mut Block,
+) -> Result<(), Error> {
+    loop {
+        let symbol = literals.decode(bits)?;
+        if symbol < END_OF_BLOCK {
+            block.literals += 1;
+            block.output += 1;
+            continue;
+        }
+        if symbol == END_OF_BLOCK {
+            return Ok(());
+        }
+
+        let code = (symbol - 257) as usize;
+        if code >= LENGTH_BASE.len() {
+            return Err(bits.error("invalid length code"));
+        }
+        let length = LENGTH_BASE[code] as usize + bits.take(LENGTH_EXTRA[code].into())? as usize;
+        let code = distances.decode(bits)? as usize;
+        if code >= DISTANCE_BASE.len() {
+            return Err(bits.error("invalid distance code"));
+        }
+        let distance =
+            DISTANCE_BASE[code] as usize + bits.take(DISTANCE_EXTRA[code].into())? as usize;
+        if distance > before + block.output {
+            return Err(bits.error("match reaches back before the start of the stream"));
+        }
+
+        block.matches += 1;
+        block.matched += length;
+        block.output += length;
+        block.max_distance = block.max_distance.max(distance);
+        block.total_distance += distance as u64;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    fn zlib(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::new(level));
+        encoder.write_all(data).expect("writing to a Vec");
+        encoder.finish().expect("writing to a Vec")
+    }
+
+    #[test]
+    fn counts_what_each_block_holds() -> Result<(), Error> {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251 * 7) as u8).collect();
+
+        let stored = inspect(&zlib(&data, 0))?;
+        assert!(stored
+            .blocks
+            .iter()
+            .all(|block| block.ty == BlockType::Stored));
+        assert_eq!(
+            stored
+                .blocks
+                .iter()
+                .map(|block| block.output)
+                .sum::<usize>(),
+            data.len()
+        );
+
+        let compressed = inspect(&zlib(&data, 9))?;
+        assert_eq!(compressed.window_size, 32768);
+        assert!(!compressed.preset_dictionary);
+        let output: usize = compressed.blocks.iter().map(|block| block.output).sum();
+        let matched: usize = compressed.blocks.iter().map(|block| block.matched).sum();
+        assert_eq!(output, data.len());
+        // the pattern repeats every 251 bytes, so nearly everything after that is matched
+        assert!(matched > data.len() - 1000);
+        assert!(compressed.blocks[0].mean_distance().is_some());
+
+        assert!(inspect(&zlib(&data, 9)[..40]).is_err());
+        Ok(())
+    }
+}
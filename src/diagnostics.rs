@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io::{self, Cursor, Write},
+    sync::Mutex,
+};
+
+use png_viewer::parse::{self, chunks};
+use zip::{write::FileOptions, ZipWriter};
+
+/// How many of the most recent log lines are kept for a report.
+const LOG_LINES: usize = 500;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Keeps the most recent log output in memory, for use as a `tracing` writer.
+pub struct LogBuffer;
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for line in String::from_utf8_lossy(buf).lines() {
+            if log.len() == LOG_LINES {
+                log.pop_front();
+            }
+            log.push_back(line.into());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What goes into a diagnostics bundle besides the log and system details.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub settings: Vec<(&'static str, String)>,
+    pub decode: Vec<(&'static str, String)>,
+    /// Layout of the problem file, only included if the user agreed to share it.
+    pub chunks: Option<String>,
+}
+
+impl Report {
+    /// Zips the report for attaching to a bug report.
+    pub fn bundle(&self) -> io::Result<Vec<u8>> {
+        let log = LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let log: String = log.iter().map(|line| format!("{line}\n")).collect();
+
+        let mut files = vec![
+            ("log.txt", log),
+            ("settings.txt", table(&self.settings)),
+            ("decode.txt", table(&self.decode)),
+            ("system.txt", system()),
+        ];
+        if let Some(chunks) = &self.chunks {
+            files.push(("chunks.txt", chunks.clone()));
+        }
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(name, FileOptions::default())?;
+            zip.write_all(contents.as_bytes())?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+/// Type, offset, length and CRC status of every chunk, without any of their contents.
+pub fn chunk_table(data: &[u8]) -> String {
+    let mut out = String::from("offset     type  length  crc\n");
+    let mut input = match parse::header(data) {
+        Ok((input, _)) => input,
+        Err(error) => return format!("{out}invalid header: {error}\n"),
+    };
+    while !input.is_empty() {
+        let offset = data.len() - input.len();
+        let (rest, raw) = match chunks::raw_chunk(input) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                writeln!(out, "{offset:#010x} stopped: {error}").unwrap();
+                break;
+            }
+        };
+        writeln!(
+            out,
+            "{offset:#010x} {:?}  {:>6}  {}",
+            raw.ty,
+            <&[u8]>::from(raw.data).len(),
+            if raw.crc_matches() { "ok" } else { "mismatch" }
+        )
+        .unwrap();
+        input = rest;
+    }
+    out
+}
+
+fn table(rows: &[(&str, String)]) -> String {
+    rows.iter()
+        .map(|(name, value)| format!("{name}: {value}\n"))
+        .collect()
+}
+
+fn system() -> String {
+    let mut out = table(&[
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("os", std::env::consts::OS.into()),
+        ("arch", std::env::consts::ARCH.into()),
+        (
+            "threads",
+            std::thread::available_parallelism().map_or_else(|_| "?".into(), |n| n.to_string()),
+        ),
+    ]);
+    // the renderer doesn't expose the adapter it picked, so record what steers its choice
+    for var in [
+        "WGPU_BACKEND",
+        "WGPU_ADAPTER_NAME",
+        "WGPU_POWER_PREF",
+        "ICED_BACKEND",
+    ] {
+        if let Some(value) = std::env::var_os(var) {
+            writeln!(out, "{var}: {}", value.to_string_lossy()).unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundle_lists_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let data = include_bytes!("../assets/xkcd.png");
+        let report = Report {
+            chunks: Some(chunk_table(data)),
+            ..Default::default()
+        };
+        assert!(report
+            .chunks
+            .as_deref()
+            .unwrap()
+            .contains("0x00000008 IHDR      13  ok"));
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(report.bundle()?))?;
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "chunks.txt",
+                "decode.txt",
+                "log.txt",
+                "settings.txt",
+                "system.txt"
+            ]
+        );
+        let mut chunks = String::new();
+        io::Read::read_to_string(&mut zip.by_name("chunks.txt")?, &mut chunks)?;
+        assert!(chunks.lines().last().unwrap().contains("IEND"));
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+use std::{collections::HashMap, io::Write};
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::parse::{
+    chunks::{bits_per_pixel, BitDepth, ColorType, Interlace},
+    error::Error,
+    image::{Image, Raster, Sample},
+    paeth, Pass,
+};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\x0A";
+
+/// How [`encode_with`] lays out the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// `None` picks the smallest that keeps every pixel: grayscale, then a palette, then
+    /// RGB, with alpha only if something is transparent. Types without alpha drop it.
+    pub color_type: Option<ColorType>,
+    /// `None` keeps the image's own depth, or less if no precision is lost.
+    pub bit_depth: Option<BitDepth>,
+    pub interlace: Interlace,
+    /// zlib level, from 0 (stored) to 9 (smallest).
+    pub compression: u32,
+}
+
+impl Default for Options {
+    /// What [`encode`] writes.
+    fn default() -> Self {
+        Self {
+            color_type: Some(ColorType::RgbAlpha),
+            bit_depth: None,
+            interlace: Interlace::None,
+            compression: Compression::default().level(),
+        }
+    }
+}
+
+/// Writes RGBA at the image's own bit depth, 8 or 16.
+pub fn encode<S: Sample>(image: &Raster<S>) -> Result<Vec<u8>, Error> {
+    encode_with(image, &Options::default())
+}
+
+pub fn encode_with<S: Sample>(image: &Raster<S>, options: &Options) -> Result<Vec<u8>, Error> {
+    let palette = palette(image);
+    let color_type = options.color_type.unwrap_or_else(|| {
+        let gray = image.pixels().iter().all(|&[r, g, b, _]| r == g && g == b);
+        let opaque = image.pixels().iter().all(|pixel| pixel[3].into() == S::MAX);
+        match (gray, opaque) {
+            (true, true) => ColorType::GrayScale,
+            (true, false) => ColorType::GrayScaleAlpha,
+            _ if palette.is_ok() => ColorType::Palette,
+            (false, true) => ColorType::Rgb,
+            (false, false) => ColorType::RgbAlpha,
+        }
+    });
+    let palette = match color_type {
+        ColorType::Palette => Some(palette.map_err(Error::InvalidPaletteSize)?),
+        _ => None,
+    };
+
+    let samples: Vec<Vec<u32>> = image
+        .pixels()
+        .iter()
+        .map(|&pixel| match &palette {
+            Some((_, indices)) => vec![indices[&to_eight_bits(pixel)].into()],
+            None => {
+                let [r, g, b, a] = pixel.map(Into::into);
+                let gray = if r == g && g == b {
+                    r
+                } else {
+                    (r * 299 + g * 587 + b * 114 + 500) / 1000
+                };
+                match color_type {
+                    ColorType::GrayScale => vec![gray],
+                    ColorType::GrayScaleAlpha => vec![gray, a],
+                    ColorType::Rgb => vec![r, g, b],
+                    _ => vec![r, g, b, a],
+                }
+            }
+        })
+        .collect();
+
+    let bit_depth = options.bit_depth.unwrap_or_else(|| match &palette {
+        Some((colors, _)) => match colors.len() {
+            0..=2 => BitDepth::One,
+            3..=4 => BitDepth::Two,
+            5..=16 => BitDepth::Four,
+            _ => BitDepth::Eight,
+        },
+        None => {
+            let depths: &[BitDepth] = match color_type {
+                ColorType::GrayScale => &[BitDepth::One, BitDepth::Two, BitDepth::Four],
+                _ => &[],
+            };
+            depths
+                .iter()
+                .chain(&[BitDepth::Eight])
+                .copied()
+                .filter(|&depth| depth as u8 <= S::BITS)
+                .find(|&depth| {
+                    samples
+                        .iter()
+                        .flatten()
+                        .all(|&sample| exact_at(sample, S::BITS, depth as u8))
+                })
+                .unwrap_or(BitDepth::Sixteen)
+        }
+    });
+    let bits = bits_per_pixel(bit_depth, color_type)?;
+    // indices past 2^depth would lose their high bits when packed
+    if let Some((colors, _)) = palette
+        .as_ref()
+        .filter(|(colors, _)| colors.len() > 1 << bits)
+    {
+        return Err(Error::PaletteDepth(colors.len(), bit_depth as u8));
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(image.width() as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(image.height() as u32).to_be_bytes());
+    // deflate, adaptive filtering
+    ihdr.extend_from_slice(&[
+        bit_depth as u8,
+        color_type as u8,
+        0,
+        0,
+        options.interlace as u8,
+    ]);
+
+    let pack = |pixels: &mut dyn Iterator<Item = &Vec<u32>>| {
+        let samples = pixels.flatten().map(|&sample| match palette {
+            Some(_) => sample,
+            None => rescale(sample, S::BITS, bit_depth as u8),
+        });
+        pack_row(samples, bit_depth as u8)
+    };
+    let width = image.width();
+    let passes = match options.interlace {
+        Interlace::None => &Pass::ADAM7[..0],
+        Interlace::Adam7 => &Pass::ADAM7[..],
+    };
+    let mut filtered = vec![];
+    if passes.is_empty() {
+        let rows: Vec<_> = samples
+            .chunks(width.max(1))
+            .map(|row| pack(&mut row.iter()))
+            .collect();
+        filtered = filter_scanlines(rows.iter().map(Vec::as_slice), bits.div_ceil(8));
+    }
+    for pass in passes {
+        let rows: Vec<_> = (pass.y..image.height())
+            .step_by(pass.dy)
+            .filter(|_| pass.x < width)
+            .map(|y| {
+                pack(
+                    &mut samples[y * width..][..width]
+                        .iter()
+                        .skip(pass.x)
+                        .step_by(pass.dx),
+                )
+            })
+            .collect();
+        // each pass is filtered as an image of its own
+        filtered.extend(filter_scanlines(
+            rows.iter().map(Vec::as_slice),
+            bits.div_ceil(8),
+        ));
+    }
+    let idat = compress(&filtered, Compression::new(options.compression.min(9)))?;
+
+    let (plte, trns) = match &palette {
+        Some((colors, _)) => {
+            let plte: Vec<_> = colors.iter().flat_map(|&[r, g, b, _]| [r, g, b]).collect();
+            let mut trns: Vec<_> = colors.iter().map(|&[.., a]| a).collect();
+            while trns.last() == Some(&u8::MAX) {
+                trns.pop();
+            }
+            (plte, trns)
+        }
+        None => (vec![], vec![]),
+    };
+    Ok(write_png(
+        [(b"IHDR", ihdr.as_slice())]
+            .into_iter()
+            .chain((!plte.is_empty()).then_some((b"PLTE", plte.as_slice())))
+            .chain((!trns.is_empty()).then_some((b"tRNS", trns.as_slice())))
+            .chain([(b"IDAT", idat.as_slice()), (b"IEND", &[])]),
+    ))
+}
+
+/// How many entries a palette of the image's colors needs, or `None` if they don't fit one.
+pub fn palette_len<S: Sample>(image: &Raster<S>) -> Option<usize> {
+    palette(image).ok().map(|(colors, _)| colors.len())
+}
+
+/// The image's distinct colors and each one's index, or how many there were if they
+/// don't fit a palette: more than 256, or any that 8 bits can't hold exactly.
+#[allow(clippy::type_complexity)]
+fn palette<S: Sample>(image: &Raster<S>) -> Result<(Vec<[u8; 4]>, HashMap<[u8; 4], u8>), usize> {
+    let mut colors = vec![];
+    let mut indices = HashMap::new();
+    for &pixel in image.pixels() {
+        if !pixel
+            .iter()
+            .all(|&sample| exact_at(sample.into(), S::BITS, 8))
+        {
+            return Err(colors.len());
+        }
+        let color = to_eight_bits(pixel);
+        if let std::collections::hash_map::Entry::Vacant(entry) = indices.entry(color) {
+            if colors.len() == 256 {
+                return Err(colors.len() + 1);
+            }
+            entry.insert(colors.len() as u8);
+            colors.push(color);
+        }
+    }
+    Ok((colors, indices))
+}
+
+fn to_eight_bits<S: Sample>(pixel: [S; 4]) -> [u8; 4] {
+    pixel.map(|sample| rescale(sample.into(), S::BITS, 8) as u8)
+}
+
+/// Scales a sample from one bit depth to another, rounding to the nearest value.
+fn rescale(sample: u32, from: u8, to: u8) -> u32 {
+    let (from_max, to_max) = ((1u32 << from) - 1, (1u32 << to) - 1);
+    (sample * to_max + from_max / 2) / from_max
+}
+
+/// Whether `sample` survives the trip to `to` bits and back.
+fn exact_at(sample: u32, from: u8, to: u8) -> bool {
+    rescale(rescale(sample, from, to), to, from) == sample
+}
+
+/// Packs samples of `bits` each into bytes, most significant first.
+fn pack_row(samples: impl Iterator<Item = u32>, bits: u8) -> Vec<u8> {
+    match bits {
+        16 => samples
+            .flat_map(|sample| (sample as u16).to_be_bytes())
+            .collect(),
+        8 => samples.map(|sample| sample as u8).collect(),
+        _ => {
+            let per_byte = 8 / bits as usize;
+            let samples: Vec<_> = samples.collect();
+            samples
+                .chunks(per_byte)
+                .map(|chunk| {
+                    chunk.iter().enumerate().fold(0, |byte, (i, &sample)| {
+                        byte | (sample as u8) << (8 - bits as usize * (i + 1))
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Writes a binary PPM (P6), compositing any transparency over black.
+pub fn encode_ppm(image: &Image) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", image.width(), image.height()).into_bytes();
+    out.reserve(image.width() * image.height() * 3);
+    for &[r, g, b, a] in image.pixels() {
+        out.extend([r, g, b].map(|c| (c as u32 * a as u32 / 255) as u8));
+    }
+    out
+}
+
+/// Filters each row with whichever filter type gives the smallest sum of absolute
+/// differences, the usual heuristic for picking filters that deflate well.
+pub fn filter_scanlines<'a>(
+    rows: impl IntoIterator<Item = &'a [u8]>,
+    bytes_per_pixel: usize,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut prev: &[u8] = &[];
+    let mut candidate = Vec::new();
+    let mut best = Vec::new();
+    for row in rows {
+        let mut best_score = u64::MAX;
+        for filter in 0..5 {
+            candidate.clear();
+            candidate.push(filter);
+            for (i, &byte) in row.iter().enumerate() {
+                let left = if i >= bytes_per_pixel {
+                    row[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let up = prev.get(i).copied().unwrap_or(0);
+                let up_left = if i >= bytes_per_pixel {
+                    prev.get(i - bytes_per_pixel).copied().unwrap_or(0)
+                } else {
+                    0
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                candidate.push(byte.wrapping_sub(predicted));
+            }
+            let score = candidate[1..]
+                .iter()
+                .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        out.extend_from_slice(&best);
+        prev = row;
+    }
+    out
+}
+
+pub fn compress(data: &[u8], level: Compression) -> Result<Vec<u8>, Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Writes the signature followed by each chunk, computing lengths and CRCs.
+pub fn write_png<'a>(chunks: impl IntoIterator<Item = (&'a [u8; 4], &'a [u8])>) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+    for (ty, data) in chunks {
+        write_chunk(&mut out, ty, data);
+    }
+    out
+}
+
+pub fn write_chunk(out: &mut Vec<u8>, ty: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(ty);
+    crc.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(ty);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::decode;
+
+    const PNG: &[u8] = include_bytes!("../assets/xkcd.png");
+
+    #[test]
+    fn round_trip() -> Result<(), Error> {
+        let image = decode(PNG)?;
+        assert_eq!(decode(&encode(&image)?)?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn auto_picks_smallest_layout() -> Result<(), Error> {
+        let auto = Options {
+            color_type: None,
+            ..Options::default()
+        };
+        let ihdr = |png: &[u8]| (png[24], png[25]);
+
+        let black_and_white = [[0, 0, 0, 255], [255, 255, 255, 255]];
+        let gray = Image::from_pixels(3, 2, [black_and_white; 3].concat()).expect("six pixels");
+        let png = encode_with(&gray, &auto)?;
+        assert_eq!(ihdr(&png), (1, ColorType::GrayScale as u8));
+        assert_eq!(decode(&png)?, gray);
+
+        let colors = [[255, 0, 0, 255], [0, 0, 255, 128], [0, 255, 0, 255]];
+        let few = Image::from_pixels(5, 1, [&colors[..], &colors[..2]].concat()).expect("five");
+        let png = encode_with(&few, &auto)?;
+        assert_eq!(ihdr(&png), (2, ColorType::Palette as u8));
+        assert_eq!(decode(&png)?, few);
+
+        // three colors can't be told apart with one bit each
+        let one_bit = Options {
+            color_type: Some(ColorType::Palette),
+            bit_depth: Some(BitDepth::One),
+            ..Options::default()
+        };
+        assert!(matches!(
+            encode_with(&few, &one_bit),
+            Err(Error::PaletteDepth(3, 1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn sixteen_bit_round_trip() -> Result<(), Error> {
+        let pixels = vec![[0x1234, 0xfffe, 1, 0x8001], [0, 0x00ff, 0xff00, 0xffff]];
+        let image = Raster::<u16>::from_pixels(2, 1, pixels).expect("two pixels");
+        assert_eq!(crate::parse::decode16(&encode(&image)?)?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn interlaced_round_trip() -> Result<(), Error> {
+        let interlaced = |color_type| Options {
+            color_type,
+            interlace: Interlace::Adam7,
+            ..Options::default()
+        };
+        let image = decode(PNG)?;
+        assert_eq!(decode(&encode_with(&image, &interlaced(None))?)?, image);
+
+        // narrower than some passes, and packed below a byte per pixel
+        let black_and_white = [[0, 0, 0, 255], [255, 255, 255, 255], [0, 0, 0, 255]];
+        let small = Image::from_pixels(3, 5, [black_and_white; 5].concat()).expect("15 pixels");
+        let png = encode_with(&small, &interlaced(None))?;
+        assert_eq!(png[24], 1);
+        assert_eq!(decode(&png)?, small);
+        Ok(())
+    }
+}
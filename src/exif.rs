@@ -0,0 +1,136 @@
+//! The Orientation tag of an `eXIf` chunk, so photos taken with the camera turned show
+//! upright.
+
+use nom::combinator::iterator;
+
+use crate::parse::{self, chunks, Rotation};
+
+const ORIENTATION_TAG: u16 = 0x0112;
+/// TIFF field type of an unsigned 16-bit value.
+const SHORT: u16 = 3;
+
+/// How the stored pixels have to be turned to show the picture upright.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Orientation {
+    /// Flipped left to right, before rotating.
+    pub mirrored: bool,
+    pub rotation: Rotation,
+}
+
+impl Orientation {
+    /// From the values the Orientation tag takes, 1 to 8.
+    pub fn from_tag(value: u16) -> Option<Self> {
+        let (mirrored, rotation) = match value {
+            1 => (false, Rotation::R0),
+            2 => (true, Rotation::R0),
+            3 => (false, Rotation::R180),
+            4 => (true, Rotation::R180),
+            5 => (true, Rotation::R270),
+            6 => (false, Rotation::R90),
+            7 => (true, Rotation::R90),
+            8 => (false, Rotation::R270),
+            _ => return None,
+        };
+        Some(Self { mirrored, rotation })
+    }
+}
+
+/// The orientation given in the file's `eXIf` chunk, unless it's upright already.
+pub fn orientation(data: &[u8]) -> Option<Orientation> {
+    let (data, _) = parse::header(data).ok()?;
+    let exif = (&mut iterator(data, chunks::raw_chunk))
+        .take_while(|raw| raw.ty.0 != *b"IEND")
+        .find(|raw| raw.ty.0 == *b"eXIf")?;
+    tiff_orientation(exif.data.into()).filter(|&orientation| orientation != Orientation::default())
+}
+
+/// Looks the tag up in the first IFD of the TIFF structure an `eXIf` chunk holds.
+pub fn tiff_orientation(tiff: &[u8]) -> Option<Orientation> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize| {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entry = (0..u16_at(ifd)? as usize)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))?;
+    if u16_at(entry + 2)? != SHORT {
+        return None;
+    }
+    // a single short sits at the start of the value field
+    Orientation::from_tag(u16_at(entry + 8)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_either_byte_order() {
+        let little = [
+            b"II*\0".as_slice(),
+            &8u32.to_le_bytes(),
+            &1u16.to_le_bytes(),
+            &ORIENTATION_TAG.to_le_bytes(),
+            &SHORT.to_le_bytes(),
+            &1u32.to_le_bytes(),
+            &[6, 0, 0, 0],
+        ]
+        .concat();
+        let big = [
+            b"MM\0*".as_slice(),
+            &8u32.to_be_bytes(),
+            &1u16.to_be_bytes(),
+            &ORIENTATION_TAG.to_be_bytes(),
+            &SHORT.to_be_bytes(),
+            &1u32.to_be_bytes(),
+            &[0, 5, 0, 0],
+        ]
+        .concat();
+        assert_eq!(
+            tiff_orientation(&little),
+            Some(Orientation {
+                mirrored: false,
+                rotation: Rotation::R90
+            })
+        );
+        assert_eq!(
+            tiff_orientation(&big),
+            Some(Orientation {
+                mirrored: true,
+                rotation: Rotation::R270
+            })
+        );
+
+        let ihdr = [
+            &1u32.to_be_bytes()[..],
+            &1u32.to_be_bytes(),
+            &[8, 0, 0, 0, 0],
+        ]
+        .concat();
+        let png = crate::encode::write_png([
+            (b"IHDR", ihdr.as_slice()),
+            (b"eXIf", little.as_slice()),
+            (b"IEND", &[]),
+        ]);
+        assert_eq!(orientation(&png), tiff_orientation(&little));
+    }
+}
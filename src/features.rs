@@ -0,0 +1,115 @@
+//! What this build can decode and write, so callers can decide up front whether to hand a
+//! file to this crate or fall back to something else.
+
+use std::{fmt, path::Path};
+
+use crate::parse::chunks::{bits_per_pixel, BitDepth, ColorType, Interlace};
+
+const COLOR_TYPES: [ColorType; 5] = [
+    ColorType::GrayScale,
+    ColorType::Rgb,
+    ColorType::Palette,
+    ColorType::GrayScaleAlpha,
+    ColorType::RgbAlpha,
+];
+const BIT_DEPTHS: [BitDepth; 5] = [
+    BitDepth::One,
+    BitDepth::Two,
+    BitDepth::Four,
+    BitDepth::Eight,
+    BitDepth::Sixteen,
+];
+
+#[cfg(feature = "webp")]
+const INPUT_FORMATS: &[&str] = &["png", "webp"];
+#[cfg(not(feature = "webp"))]
+const INPUT_FORMATS: &[&str] = &["png"];
+
+/// Whether the file's extension is one of the formats this build opens.
+pub fn is_supported_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        INPUT_FORMATS
+            .iter()
+            .any(|format| extension.eq_ignore_ascii_case(format))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureMatrix {
+    /// Color type and bit depth combinations that decode.
+    pub formats: Vec<(ColorType, BitDepth)>,
+    pub interlace: Vec<Interlace>,
+    /// Ancillary chunks that are read rather than skipped.
+    pub ancillary_chunks: Vec<&'static str>,
+    /// File formats that can be opened.
+    pub input_formats: Vec<&'static str>,
+    /// File formats images can be written as.
+    pub export_formats: Vec<&'static str>,
+    /// Terminal graphics protocols images can be printed with.
+    pub terminal_formats: Vec<&'static str>,
+}
+
+pub fn supported_features() -> FeatureMatrix {
+    FeatureMatrix {
+        formats: COLOR_TYPES
+            .into_iter()
+            .flat_map(|color_type| BIT_DEPTHS.map(|bit_depth| (color_type, bit_depth)))
+            .filter(|&(color_type, bit_depth)| bits_per_pixel(bit_depth, color_type).is_ok())
+            .collect(),
+        // Adam7 passes are read as if they were plain scanlines
+        interlace: vec![Interlace::None],
+        ancillary_chunks: vec![
+            "gAMA", "sRGB", "cHRM", "iCCP", "tEXt", "iTXt", "acTL", "fcTL", "fdAT",
+        ],
+        input_formats: INPUT_FORMATS.to_vec(),
+        export_formats: vec!["png", "ppm", "ora"],
+        terminal_formats: vec!["sixel", "iterm2"],
+    }
+}
+
+impl FeatureMatrix {
+    /// Whether an image with this IHDR would decode correctly.
+    pub fn supports(
+        &self,
+        color_type: ColorType,
+        bit_depth: BitDepth,
+        interlace: Interlace,
+    ) -> bool {
+        self.formats.contains(&(color_type, bit_depth)) && self.interlace.contains(&interlace)
+    }
+}
+
+impl fmt::Display for FeatureMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PNG Viewer {}", env!("CARGO_PKG_VERSION"))?;
+        for color_type in COLOR_TYPES {
+            let depths: Vec<_> = self
+                .formats
+                .iter()
+                .filter(|(ty, _)| *ty == color_type)
+                .map(|(_, depth)| (*depth as u8).to_string())
+                .collect();
+            writeln!(f, "{color_type:?}: {}-bit", depths.join(", "))?;
+        }
+        writeln!(f, "Interlace: {:?}", self.interlace)?;
+        writeln!(f, "Ancillary chunks: {}", self.ancillary_chunks.join(", "))?;
+        writeln!(f, "Open: {}", self.input_formats.join(", "))?;
+        writeln!(f, "Export: {}", self.export_formats.join(", "))?;
+        write!(f, "Terminal output: {}", self.terminal_formats.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matrix_matches_spec() {
+        let features = supported_features();
+        // 5 gray, 1 each for the rest but palette (4) and two at 8/16 for RGB(A) and GA
+        assert_eq!(features.formats.len(), 15);
+        assert!(features.supports(ColorType::RgbAlpha, BitDepth::Eight, Interlace::None));
+        assert!(!features.supports(ColorType::Palette, BitDepth::Sixteen, Interlace::None));
+        assert!(!features.supports(ColorType::Rgb, BitDepth::Eight, Interlace::Adam7));
+    }
+}
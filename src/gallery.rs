@@ -0,0 +1,118 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use iced::widget::image::Handle;
+
+use crate::slideshow;
+
+/// Longer side of each thumbnail in the grid.
+pub const THUMBNAIL_SIDE: usize = 128;
+pub const COLUMNS: usize = 4;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Thumbnails of every PNG in a folder, filled in as they finish decoding.
+///
+/// Only a few decode at once: each finished thumbnail hands out the next [`Job`].
+/// Dropping the gallery cancels the jobs that haven't started.
+#[derive(Debug)]
+pub struct Gallery {
+    id: u64,
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    /// `None` once decoding has failed, so the cell stops waiting.
+    thumbnails: HashMap<PathBuf, Option<Handle>>,
+    queue: VecDeque<PathBuf>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A thumbnail to decode for the gallery `gallery`.
+#[derive(Debug)]
+pub struct Job {
+    pub gallery: u64,
+    pub path: PathBuf,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    /// Whether the gallery closed since the job was handed out.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// What a grid cell shows.
+pub enum Cell<'a> {
+    Decoding,
+    Failed,
+    Thumbnail(&'a Handle),
+}
+
+impl Gallery {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let paths = slideshow::scan(dir)?;
+        Ok(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            dir: dir.to_path_buf(),
+            queue: paths.iter().cloned().collect(),
+            paths,
+            thumbnails: HashMap::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// The first jobs to run, one per worker.
+    pub fn start(&mut self) -> Vec<Job> {
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        std::iter::from_fn(|| self.next_job())
+            .take(workers)
+            .collect()
+    }
+
+    pub fn next_job(&mut self) -> Option<Job> {
+        Some(Job {
+            gallery: self.id,
+            path: self.queue.pop_front()?,
+            cancelled: self.cancelled.clone(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn cell(&self, path: &Path) -> Cell<'_> {
+        match self.thumbnails.get(path) {
+            None => Cell::Decoding,
+            Some(None) => Cell::Failed,
+            Some(Some(handle)) => Cell::Thumbnail(handle),
+        }
+    }
+
+    /// Fills in the thumbnail a job of this gallery decoded. Returns false for a job
+    /// of a gallery that has since closed, whose worker shouldn't take another job.
+    pub fn set_thumbnail(&mut self, gallery: u64, path: PathBuf, handle: Option<Handle>) -> bool {
+        if gallery != self.id {
+            return false;
+        }
+        self.thumbnails.insert(path, handle);
+        true
+    }
+}
+
+impl Drop for Gallery {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+/// Every file viewed, in the order they were viewed, for going back and forth between them
+/// like in a web browser.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<PathBuf>,
+    /// The entry being viewed.
+    index: usize,
+}
+
+impl History {
+    /// Records `path` as viewed, dropping anything that was ahead of the current entry.
+    pub fn visit(&mut self, path: &Path) {
+        if self.current() == Some(path) {
+            return;
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.index + 1);
+        }
+        self.entries.push(path.to_path_buf());
+        self.index = self.entries.len() - 1;
+    }
+
+    pub fn current(&self) -> Option<&Path> {
+        self.entries.get(self.index).map(PathBuf::as_path)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.index + 1 < self.entries.len()
+    }
+
+    pub fn back(&mut self) -> Option<&Path> {
+        self.index = self.index.checked_sub(1)?;
+        self.current()
+    }
+
+    pub fn forward(&mut self) -> Option<&Path> {
+        if !self.can_go_forward() {
+            return None;
+        }
+        self.index += 1;
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn visiting_after_going_back_drops_forward_entries() {
+        let mut history = History::default();
+        for path in ["a.png", "b.png", "c.png"] {
+            history.visit(Path::new(path));
+        }
+        assert_eq!(history.back(), Some(Path::new("b.png")));
+        assert_eq!(history.back(), Some(Path::new("a.png")));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some(Path::new("b.png")));
+
+        // reloading the current file isn't a new entry
+        history.visit(Path::new("b.png"));
+        assert!(history.can_go_forward());
+        history.visit(Path::new("d.png"));
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back(), Some(Path::new("b.png")));
+    }
+}
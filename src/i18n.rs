@@ -0,0 +1,67 @@
+/// Languages the UI has translations for.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Locale {
+    /// Picks the locale from the usual POSIX environment variables, falling back to English.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Self::from_tag(&value))
+            .unwrap_or_default()
+    }
+
+    /// Parses tags like `de_DE.UTF-8` or `fr-CA`.
+    pub fn from_tag(tag: &str) -> Self {
+        match tag.get(..2).map(str::to_ascii_lowercase).as_deref() {
+            Some("de") => Self::German,
+            Some("fr") => Self::French,
+            Some("es") => Self::Spanish,
+            _ => Self::English,
+        }
+    }
+
+    /// Display label for one of the standard tEXt keywords, or `None` if the keyword
+    /// isn't one of them.
+    pub fn keyword_label(self, keyword: &str) -> Option<&'static str> {
+        let index = KEYWORDS.iter().position(|entry| entry[0] == keyword)?;
+        Some(KEYWORDS[index][self as usize])
+    }
+}
+
+/// Standard keywords from the PNG spec, in the order of the `Locale` variants.
+const KEYWORDS: &[[&str; 4]] = &[
+    ["Title", "Titel", "Titre", "Título"],
+    ["Author", "Autor", "Auteur", "Autor"],
+    ["Description", "Beschreibung", "Description", "Descripción"],
+    [
+        "Copyright",
+        "Urheberrecht",
+        "Droits d'auteur",
+        "Derechos de autor",
+    ],
+    [
+        "Creation Time",
+        "Erstellungszeit",
+        "Date de création",
+        "Fecha de creación",
+    ],
+    ["Software", "Software", "Logiciel", "Software"],
+    [
+        "Disclaimer",
+        "Haftungsausschluss",
+        "Avertissement légal",
+        "Descargo de responsabilidad",
+    ],
+    ["Warning", "Warnung", "Avertissement", "Advertencia"],
+    ["Source", "Quelle", "Source", "Fuente"],
+    ["Comment", "Kommentar", "Commentaire", "Comentario"],
+];
@@ -0,0 +1,15 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Wraps an image file in iTerm2's inline image escape sequence. `width` is in terminal
+/// columns; by default the terminal picks a size that fits.
+pub fn inline_image(file: &[u8], name: Option<&str>, width: Option<usize>) -> String {
+    let mut arguments = format!("inline=1;size={};preserveAspectRatio=1", file.len());
+    if let Some(name) = name {
+        arguments.push_str(";name=");
+        arguments.push_str(&STANDARD.encode(name));
+    }
+    if let Some(width) = width {
+        arguments.push_str(&format!(";width={width}"));
+    }
+    format!("\x1b]1337;File={arguments}:{}\x07", STANDARD.encode(file))
+}
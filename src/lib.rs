@@ -1 +1,16 @@
+pub mod apng;
+pub mod check;
+pub mod cli;
+pub mod color;
+pub mod deflate;
+pub mod encode;
+pub mod exif;
+pub mod features;
+pub mod i18n;
+pub mod iterm2;
+pub mod ora;
 pub mod parse;
+pub mod sixel;
+pub mod stages;
+#[cfg(feature = "webp")]
+pub mod webp;
@@ -1,185 +1,3113 @@
 // uncomment for release: #![windows_subsystem = "windows"]
 
-use png_viewer::parse;
+mod appearance;
+mod association;
+mod board;
+mod browser;
+mod cache;
+mod clipboard;
+mod compare;
+mod diagnostics;
+mod gallery;
+mod history;
+mod minimap;
+mod options;
+mod passes;
+mod save_as;
+mod session;
+mod share;
+mod slideshow;
+mod timeline;
+mod update;
 
+use png_viewer::{
+    encode,
+    exif::Orientation,
+    i18n::Locale,
+    parse::{
+        self,
+        chunks::{BitDepth, ColorType, Handlers, Interlace, Policy, Text},
+        image::{Filter, Image, Pixels, Raster, Sample, Statistics},
+        FilterType, Filters,
+    },
+};
+
+use appearance::{Appearance, ThemeChoice};
+use board::Board;
+use browser::Browser;
+use compare::Compare;
+use gallery::Gallery;
+use history::History;
 use iced::{
-    alignment, executor, mouse, theme,
+    alignment, executor, keyboard, mouse, theme,
     widget::{
         self,
         canvas::{self, Cache, Frame, Geometry, Program},
         column, row, Canvas,
     },
-    window, Application, Command, Element, Length, Rectangle, Renderer, Settings, Theme, Vector,
+    window, Alignment, Application, Color, Command, Element, Length, Point, Rectangle, Renderer,
+    Settings, Size, Subscription, Theme, Vector,
 };
-use tokio::sync::oneshot;
+use minimap::Minimap;
+use options::{Options, ViewOptions};
+use passes::Passes;
+use save_as::{Auto, SaveAs, Unsaved};
+use session::{Data, Session};
+use slideshow::Slideshow;
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use timeline::Timeline;
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use update::{Consent, Release};
+
+const SIZE: (u32, u32) = (700, 700);
+const MIN_SIZE: (u32, u32) = (200, 400);
+const PHOTO_ICON: &[u8] = include_bytes!("../assets/photo.ico");
+const NIGHT_KELVIN: f32 = 3400.0;
+const HIGHLIGHT_DURATION: Duration = Duration::from_secs(2);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the view must stay still before it's redrawn at full resolution.
+const SETTLE_DELAY: Duration = Duration::from_millis(150);
+/// How long the native file dialog gets to answer before the built-in browser is offered,
+/// since on some setups it never appears at all.
+const DIALOG_TIMEOUT: Duration = Duration::from_secs(5);
+const GOTO_INPUT: &str = "goto-input";
+const URL_INPUT: &str = "url-input";
+/// Largest width or height the resize bar accepts.
+const MAX_RESIZE_SIDE: usize = 16384;
+/// Runs of the same filter type listed in the info panel before the rest are cut off.
+const MAX_FILTER_RUNS: usize = 60;
+const EMOJIS: &[char] = &['🌄', '🌅', '🌇', '🌠', '🌉', '🏡', '🌺', '⛵', '🪐', '🌞'];
+
+fn main() -> iced::Result {
+    tracing_subscriber::registry()
+        .with(EnvFilter::new("png_viewer"))
+        .with(fmt::layer())
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| diagnostics::LogBuffer),
+        )
+        .init();
+
+    let options = Options::from_args(std::env::args_os().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        std::process::exit(2);
+    });
+
+    if options.features {
+        println!("{}", png_viewer::features::supported_features());
+        return Ok(());
+    }
+
+    if let Some(path) = &options.display_profile {
+        match std::fs::read(path)
+            .map_err(Into::into)
+            .and_then(|icc| png_viewer::color::Profile::from_icc(&icc))
+        {
+            Ok(profile) => png_viewer::color::set_display_profile(profile),
+            Err(error) => {
+                eprintln!(
+                    "Couldn't use {} as the display profile: {error}",
+                    path.display()
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+    cache::init(!options.no_cache);
+
+    App::run(Settings {
+        flags: options,
+        window: window::Settings {
+            size: SIZE,
+            position: window::Position::Centered,
+            min_size: Some(MIN_SIZE),
+            icon: Some(window::icon::from_file_data(PHOTO_ICON, None).unwrap()),
+            ..window::Settings::default()
+        },
+        ..Settings::default()
+    })
+}
+
+#[derive(Default)]
+struct App {
+    viewer: Viewer,
+    locale: Locale,
+    show_metadata: bool,
+    pending_view: Option<ViewOptions>,
+    goto: Option<String>,
+    /// Address typed into the open-URL box, until it's submitted.
+    url_input: Option<String>,
+    /// Text typed into the zoom box, until it's submitted.
+    zoom_input: Option<String>,
+    /// Action button picked with Tab, which Enter presses.
+    focus: Option<usize>,
+    resize: Option<ResizeDialog>,
+    /// Encoder options being picked for an image about to be saved.
+    save_as: Option<SaveAs>,
+    appearance: Appearance,
+    /// Where the cursor is on the canvas, for the status bar.
+    cursor: Option<Point>,
+    /// Colors typed into the theme bar, until they're applied.
+    theme_input: Option<ThemeInput>,
+    slideshow: Option<Slideshow>,
+    /// Options to reapply whenever a new slide loads.
+    slide_view: ViewOptions,
+    /// Settings for slideshows started from the viewer.
+    slide_interval: Duration,
+    slide_shuffle: bool,
+    background: Option<Color>,
+    policy: Policy,
+    kiosk: bool,
+    night: bool,
+    night_kelvin: f32,
+    /// Interpolate when zoomed, for photos, rather than keep pixel art crisp.
+    smooth: bool,
+    show_badge: bool,
+    show_filters: bool,
+    /// Show interlaced images pass by pass as they refine.
+    show_passes: bool,
+    board: Option<Board>,
+    /// Second image shown beside the one being viewed.
+    compare: Option<Compare>,
+    update_banner: Option<UpdateBanner>,
+    /// Bumped on every zoom or scroll step, so only the last one's settle timer counts.
+    interaction: u64,
+    /// File browser pane, opened on request or when the native dialog seems stuck.
+    browser: Option<Browser>,
+    /// Thumbnail grid shown in place of the viewer.
+    gallery: Option<Gallery>,
+    history: History,
+    /// The native dialog waiting for an answer, if any.
+    dialog: Option<u64>,
+    dialogs: u64,
+}
+
+#[derive(Debug, Default)]
+struct ThemeInput {
+    accent: String,
+    background: String,
+}
+
+/// Size and filter picked in the resize bar, until they're applied.
+#[derive(Debug, Default)]
+struct ResizeDialog {
+    width: String,
+    height: String,
+    filter: Filter,
+}
+
+/// Shown above the image until the user answers or dismisses it.
+enum UpdateBanner {
+    AskConsent,
+    Available(Release),
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Load,
+    Decoded,
+    DecodeProgress,
+    /// Zooms around a point in the viewport, or its center.
+    ZoomIn(Option<Point>),
+    ZoomOut(Option<Point>),
+    ZoomToggle(Option<Point>),
+    Rotate,
+    Jump(parse::Jump),
+    Resized(Size),
+    /// The cursor moved over the canvas, or left it.
+    Hover(Option<Point>),
+    Tick,
+    AutoCrop,
+    /// Selects the pixels between two points on screen.
+    Select(Point, Point),
+    ExportSelection,
+    /// Trims the image to the selection, then offers to save it.
+    Crop,
+    Resize,
+    ResizeWidth(String),
+    ResizeHeight(String),
+    ResizeFilter(Filter),
+    /// Resamples the image to the size in the resize bar, then offers to save it.
+    ResizeSubmit,
+    SaveColorType(Auto<ColorType>),
+    SaveBitDepth(Auto<BitDepth>),
+    SaveInterlaced(bool),
+    SaveCompression(u32),
+    /// Encodes with the options in the Save As bar and asks where to write the file.
+    SaveSubmit,
+    CopySelection,
+    ClearSelection,
+    ExportView,
+    FitContent,
+    ToggleMetadata,
+    ToggleLenient,
+    Share,
+    GoTo,
+    GoToInput(String),
+    GoToSubmit,
+    OpenUrl,
+    UrlInput(String),
+    UrlSubmit,
+    ZoomInput(String),
+    ZoomSubmit,
+    BoardAdd,
+    BoardMove(usize, Point),
+    BoardPan(Vector),
+    BoardScale(usize, bool),
+    BoardSave,
+    CloseBoard,
+    /// Picks an image to show beside this one, zoomed and panned along with it.
+    Compare,
+    CompareMode(compare::Mode),
+    CompareOpacity(f32),
+    /// Largest channel delta the difference view ignores.
+    CompareTolerance(u8),
+    /// Moves the overlay's divider, as a fraction of the width.
+    CompareSwipe(f32),
+    CloseCompare,
+    HighlightExpired(Rectangle),
+    Settled(u64),
+    Picked(Option<PathBuf>),
+    BoardPicked(Vec<PathBuf>),
+    ComparePicked(Option<PathBuf>),
+    /// A native dialog was answered, with what to do about the answer.
+    DialogAnswered(u64, Box<Message>),
+    DialogStalled(u64),
+    ToggleBrowser,
+    BrowserParent,
+    /// Opens the entry: enters a folder, or views a file.
+    BrowserActivate(usize),
+    BrowserOpenSelected,
+    BrowserMove(isize),
+    BrowserHover(Option<usize>),
+    PreviewDecoded(PathBuf, Option<widget::image::Handle>),
+    CloseBrowser,
+    OpenGallery,
+    ThumbnailDecoded(u64, PathBuf, Option<widget::image::Handle>),
+    GalleryPick(PathBuf),
+    CloseGallery,
+    HistoryBack,
+    HistoryForward,
+    SeekFrame(usize),
+    /// Shows an interlaced image as it looks after the pass at this index, pausing.
+    SeekPass(usize),
+    /// Replays the passes from the first.
+    PlayPasses,
+    /// Time to show the next pass.
+    PassTick,
+    /// Centers the view on a point of the image picked in the minimap.
+    MinimapSeek(Point),
+    Escape,
+    /// A key pressed while no text box has the keyboard, for the viewer to act on.
+    Key(keyboard::KeyCode, keyboard::Modifiers),
+    /// Moves keyboard focus forwards or backwards through the action buttons, wrapping.
+    FocusAction(isize),
+    /// Presses the focused action button, or opens the browser's selection.
+    Confirm,
+    NextSlide,
+    StartSlideshow,
+    ToggleSlidePause,
+    ToggleShuffle,
+    StopSlideshow,
+    Retry,
+    ToggleNight,
+    ToggleSmooth,
+    /// Switches between the EXIF orientation and the pixels as stored.
+    ToggleOrientation,
+    ToggleOriginal,
+    ToggleBadge,
+    /// Tints each scanline by the filter type it was stored with.
+    ToggleFilterOverlay,
+    ToggleInterlacePasses,
+    UpdateConsent(bool),
+    UpdateChecked(Option<Release>),
+    OpenRelease,
+    DismissUpdate,
+    CollectDiagnostics,
+    About,
+    /// Makes the viewer the program PNGs open in.
+    RegisterAssociation,
+    ThemeSettings,
+    SetTheme(ThemeChoice),
+    ThemeAccent(String),
+    ThemeBackground(String),
+    /// Applies the colors in the theme bar and closes it.
+    ThemeSubmit,
+    Saved,
+}
+
+impl Application for App {
+    type Executor = executor::Default;
+
+    type Message = Message;
+
+    type Theme = Theme;
+
+    type Flags = Options;
+
+    fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let slide_interval = flags.interval.unwrap_or(slideshow::INTERVAL);
+        let slideshow = flags.slideshow.as_deref().and_then(|dir| {
+            let mut slideshow = match Slideshow::from_dir(dir) {
+                Ok(slideshow) => slideshow,
+                Err(error) => {
+                    tracing::error!("from Slideshow::from_dir: {error}");
+                    // a kiosk has no other way to show anything, so it waits for images
+                    if !flags.kiosk {
+                        return None;
+                    }
+                    Slideshow::empty(dir)
+                }
+            };
+            slideshow.interval = slide_interval;
+            slideshow.set_shuffle(flags.shuffle);
+            Some(slideshow)
+        });
+        let board = flags.board.as_deref().and_then(|session| {
+            Board::open(session)
+                .map_err(|error| tracing::error!("from Board::open: {error}"))
+                .ok()
+        });
+        let path = match &slideshow {
+            Some(slideshow) => slideshow.current().map(Path::to_path_buf),
+            None => flags.path,
+        };
+
+        let mut app = Self {
+            locale: Locale::from_env(),
+            pending_view: path.is_some().then_some(flags.view),
+            slideshow,
+            slide_view: flags.view,
+            slide_interval,
+            slide_shuffle: flags.shuffle,
+            background: flags.background,
+            appearance: Appearance::load(),
+            kiosk: flags.kiosk,
+            night: flags.night.is_some(),
+            night_kelvin: flags.night.unwrap_or(NIGHT_KELVIN),
+            show_badge: !flags.kiosk,
+            board,
+            policy: if flags.lenient {
+                Policy::Lenient
+            } else {
+                Policy::Strict
+            },
+            ..Self::default()
+        };
+        let mut commands = vec![];
+        if let Some(path) = path {
+            commands.push(app.viewer.open(path, app.policy));
+        }
+        if flags.fullscreen {
+            commands.push(window::change_mode(window::Mode::Fullscreen));
+        }
+        if !app.kiosk {
+            match update::consent() {
+                Consent::Unasked => app.update_banner = Some(UpdateBanner::AskConsent),
+                Consent::Granted => commands.push(check_for_update()),
+                Consent::Declined => {}
+            }
+        }
+        (app, Command::batch(commands))
+    }
+
+    fn title(&self) -> String {
+        // names that aren't valid Unicode are still shown, with replacement characters
+        match self.viewer.path().and_then(Path::file_name) {
+            Some(name) => format!("{} - PNG Viewer", name.to_string_lossy()),
+            None => "PNG Viewer".into(),
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
+        // kiosk mode only runs the slideshow
+        if self.kiosk
+            && !matches!(
+                message,
+                Message::Decoded
+                    | Message::DecodeProgress
+                    | Message::NextSlide
+                    | Message::Resized(_)
+                    | Message::Tick
+                    | Message::Settled(_)
+            )
+        {
+            return Command::none();
+        }
+
+        match message {
+            Message::Load => self.file_dialog(pick_png, Message::Picked),
+            Message::Picked(path) => match path {
+                Some(path) => self.viewer.open(path, self.policy),
+                None => {
+                    tracing::debug!("No file selected");
+                    Command::none()
+                }
+            },
+            Message::DialogAnswered(dialog, answer) => {
+                if self.dialog == Some(dialog) {
+                    self.dialog = None;
+                }
+                self.update(*answer)
+            }
+            Message::DialogStalled(dialog) => {
+                if self.dialog == Some(dialog) && self.browser.is_none() {
+                    tracing::warn!("No answer from the file dialog after {DIALOG_TIMEOUT:?}");
+                    self.browser = Some(Browser::open_default());
+                }
+                Command::none()
+            }
+            Message::ToggleBrowser => {
+                self.browser = match self.browser {
+                    Some(_) => None,
+                    None => Some(match self.viewer.path().and_then(Path::parent) {
+                        Some(dir) if !dir.as_os_str().is_empty() => {
+                            Browser::open(dir.to_path_buf())
+                        }
+                        _ => Browser::open_default(),
+                    }),
+                };
+                Command::none()
+            }
+            Message::BrowserParent => {
+                if let Some(parent) = self.browser.as_ref().and_then(Browser::parent) {
+                    self.browser = Some(Browser::open(parent.to_path_buf()));
+                }
+                Command::none()
+            }
+            Message::BrowserActivate(index) => self.activate_entry(index),
+            Message::BrowserOpenSelected => {
+                match self.browser.as_ref().and_then(Browser::selected) {
+                    Some(index) => self.activate_entry(index),
+                    None => Command::none(),
+                }
+            }
+            Message::BrowserMove(delta) => {
+                if let Some(browser) = &mut self.browser {
+                    browser.select(delta);
+                }
+                self.request_preview()
+            }
+            Message::BrowserHover(index) => {
+                if let Some(browser) = &mut self.browser {
+                    browser.hover(index);
+                }
+                self.request_preview()
+            }
+            Message::PreviewDecoded(path, handle) => {
+                if let (Some(browser), Some(handle)) = (&mut self.browser, handle) {
+                    browser.set_preview(path, handle);
+                }
+                Command::none()
+            }
+            Message::CloseBrowser => {
+                self.browser = None;
+                Command::none()
+            }
+            Message::HistoryBack => match self.history.back() {
+                Some(path) => self.viewer.open(path.to_path_buf(), self.policy),
+                None => Command::none(),
+            },
+            Message::HistoryForward => match self.history.forward() {
+                Some(path) => self.viewer.open(path.to_path_buf(), self.policy),
+                None => Command::none(),
+            },
+            Message::OpenGallery => self.open_gallery(),
+            Message::ThumbnailDecoded(id, path, handle) => {
+                let Some(gallery) = &mut self.gallery else {
+                    return Command::none();
+                };
+                if !gallery.set_thumbnail(id, path, handle) {
+                    return Command::none();
+                }
+                gallery
+                    .next_job()
+                    .map_or_else(Command::none, decode_thumbnail)
+            }
+            Message::GalleryPick(path) => {
+                self.gallery = None;
+                self.viewer.open(path, self.policy)
+            }
+            Message::CloseGallery => {
+                self.gallery = None;
+                Command::none()
+            }
+            Message::SeekFrame(index) => self.viewer.seek_frame(index),
+            Message::SeekPass(index) => self.viewer.seek_pass(index),
+            Message::PlayPasses => self.viewer.play_passes(),
+            Message::PassTick => self.viewer.pass_tick(),
+            Message::MinimapSeek(point) => self.viewer.update_state(|state| {
+                state.center_on(point);
+                true
+            }),
+            Message::Decoded => {
+                let command = self.viewer.decoded();
+                self.refresh_compare();
+                if !matches!(self.viewer, Viewer::Decoding(_)) {
+                    if let Some(path) = self.viewer.path() {
+                        self.history.visit(path);
+                    }
+                }
+                self.viewer.set_show_badge(self.show_badge);
+                self.viewer.set_show_filters(self.show_filters);
+                if self.show_metadata {
+                    self.viewer.load_filters();
+                }
+                let passes = self.viewer.set_show_passes(self.show_passes);
+                let view = self.pending_view.take();
+                let tint = self.night_tint();
+                let smooth = self.smooth;
+                Command::batch([
+                    command,
+                    passes,
+                    self.viewer.update_state(|state| {
+                        if let Some(view) = view {
+                            view.apply(state);
+                        }
+                        state.set_color_matrix(tint);
+                        state.set_smooth(smooth);
+                        true
+                    }),
+                ])
+            }
+            Message::ZoomIn(anchor) => Command::batch([
+                self.interact(),
+                self.viewer.update_state(|state| state.zoom_in(anchor)),
+            ]),
+            Message::ZoomOut(anchor) => Command::batch([
+                self.interact(),
+                self.viewer.update_state(|state| state.zoom_out(anchor)),
+            ]),
+            Message::ZoomToggle(anchor) => Command::batch([
+                self.interact(),
+                self.viewer.update_state(|state| {
+                    state.zoom_toggle(anchor);
+                    true
+                }),
+            ]),
+            Message::Rotate => self.viewer.update_state(|state| {
+                state.rotate_clockwise();
+                true
+            }),
+            Message::Jump(jump) => Command::batch([
+                self.interact(),
+                self.viewer.update_state(|state| {
+                    state.jump(jump);
+                    true
+                }),
+            ]),
+            Message::Hover(cursor) => {
+                self.cursor = cursor;
+                Command::none()
+            }
+            Message::Resized(viewport) => self
+                .viewer
+                .update_state(|state| state.set_viewport(viewport)),
+            Message::Tick => Command::batch([
+                self.interact(),
+                self.viewer.update_state(parse::State::tick),
+            ]),
+            Message::Settled(interaction) if interaction == self.interaction => {
+                self.viewer.update_state(|state| state.set_draft(false))
+            }
+            Message::Settled(_) => Command::none(),
+            // redraws the progress bar, and the rows decoded so far
+            Message::DecodeProgress => {
+                if let Viewer::Decoding(session) = &mut self.viewer {
+                    session.receive_rows();
+                }
+                Command::none()
+            }
+            Message::AutoCrop => self.viewer.auto_crop(),
+            Message::Select(from, to) => self.viewer.select(from, to),
+            Message::ExportSelection => {
+                let image = self.viewer.export_selection();
+                self.save_as(image)
+            }
+            Message::Crop => {
+                let image = self.viewer.crop();
+                self.refresh_compare();
+                self.save_as(image)
+            }
+            Message::Resize => {
+                if let Some(size) = self.viewer.image_size() {
+                    self.resize = Some(ResizeDialog {
+                        width: size.width.to_string(),
+                        height: size.height.to_string(),
+                        ..Default::default()
+                    });
+                }
+                Command::none()
+            }
+            Message::ResizeWidth(width) => {
+                if let Some(dialog) = &mut self.resize {
+                    dialog.width = width;
+                }
+                Command::none()
+            }
+            Message::ResizeHeight(height) => {
+                if let Some(dialog) = &mut self.resize {
+                    dialog.height = height;
+                }
+                Command::none()
+            }
+            Message::ResizeFilter(filter) => {
+                if let Some(dialog) = &mut self.resize {
+                    dialog.filter = filter;
+                }
+                Command::none()
+            }
+            Message::ResizeSubmit => {
+                let Some(dialog) = self.resize.take() else {
+                    return Command::none();
+                };
+                let side = |input: &str| {
+                    input
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|side| (1..=MAX_RESIZE_SIDE).contains(side))
+                };
+                match (side(&dialog.width), side(&dialog.height)) {
+                    (Some(width), Some(height)) => {
+                        let image = self.viewer.edit(Edit::Resize(width, height, dialog.filter));
+                        self.refresh_compare();
+                        self.save_as(image)
+                    }
+                    _ => {
+                        tracing::error!("invalid resize: {:?} x {:?}", dialog.width, dialog.height);
+                        Command::none()
+                    }
+                }
+            }
+            Message::CopySelection => self.viewer.copy_selection(),
+            Message::ClearSelection => self.viewer.clear_selection(),
+            Message::ExportView => {
+                let image = self.viewer.export_view();
+                self.save_as(image)
+            }
+            Message::SaveColorType(Auto(color_type)) => {
+                if let Some(save_as) = &mut self.save_as {
+                    save_as.set_color_type(color_type);
+                }
+                Command::none()
+            }
+            Message::SaveBitDepth(Auto(bit_depth)) => {
+                if let Some(save_as) = &mut self.save_as {
+                    save_as.set_bit_depth(bit_depth);
+                }
+                Command::none()
+            }
+            Message::SaveInterlaced(interlaced) => {
+                if let Some(save_as) = &mut self.save_as {
+                    save_as.set_interlaced(interlaced);
+                }
+                Command::none()
+            }
+            Message::SaveCompression(level) => {
+                if let Some(save_as) = &mut self.save_as {
+                    save_as.set_compression(level);
+                }
+                Command::none()
+            }
+            Message::SaveSubmit => {
+                let Some(save_as) = self.save_as.take() else {
+                    return Command::none();
+                };
+                match save_as.encode() {
+                    Ok(data) => self.file_dialog(
+                        move || {
+                            save_with(
+                                native_dialog::FileDialog::new()
+                                    .set_title("Save PNG")
+                                    .add_filter("PNG image", &["png"]),
+                                &data,
+                            )
+                        },
+                        |()| Message::Saved,
+                    ),
+                    Err(error) => {
+                        tracing::error!("from SaveAs::encode: {error}");
+                        Command::none()
+                    }
+                }
+            }
+            Message::FitContent => self.viewer.fit_content(),
+            Message::Share => self.viewer.share(),
+            Message::Retry => self.viewer.retry(self.policy),
+            Message::ToggleSmooth => {
+                self.smooth = !self.smooth;
+                let smooth = self.smooth;
+                self.viewer.update_state(|state| {
+                    state.set_smooth(smooth);
+                    true
+                })
+            }
+            Message::ToggleNight => {
+                self.night = !self.night;
+                let tint = self.night_tint();
+                self.viewer.update_state(|state| {
+                    state.set_color_matrix(tint);
+                    true
+                })
+            }
+            Message::ToggleOrientation => self.viewer.toggle_orientation(),
+            Message::ToggleOriginal => self.viewer.update_state(|state| {
+                state.toggle_original();
+                true
+            }),
+            Message::ToggleBadge => {
+                self.show_badge = !self.show_badge;
+                self.viewer.set_show_badge(self.show_badge);
+                Command::none()
+            }
+            Message::ToggleFilterOverlay => {
+                self.show_filters = !self.show_filters;
+                self.viewer.set_show_filters(self.show_filters);
+                Command::none()
+            }
+            Message::ToggleInterlacePasses => {
+                self.show_passes = !self.show_passes;
+                self.viewer.set_show_passes(self.show_passes)
+            }
+            Message::ToggleLenient => {
+                self.policy = match self.policy {
+                    Policy::Strict => Policy::Lenient,
+                    Policy::Lenient => Policy::Strict,
+                };
+                self.viewer.set_policy(self.policy)
+            }
+            Message::GoTo => {
+                if !matches!(self.viewer, Viewer::Viewing { .. }) {
+                    return Command::none();
+                }
+                self.goto = Some(String::new());
+                widget::text_input::focus(widget::text_input::Id::new(GOTO_INPUT))
+            }
+            Message::GoToInput(input) => {
+                self.goto = Some(input);
+                Command::none()
+            }
+            Message::GoToSubmit => {
+                let input = self.goto.take().unwrap_or_default();
+                if let Some(point) = options::parse_point(&input) {
+                    let pixel = Rectangle::new(point, Size::new(1.0, 1.0));
+                    Command::batch([
+                        self.viewer.update_state(|state| {
+                            state.center_on(Point::new(point.x + 0.5, point.y + 0.5));
+                            true
+                        }),
+                        self.viewer.highlight(pixel),
+                    ])
+                } else if let Some(region) = options::parse_region(&input) {
+                    Command::batch([
+                        self.viewer.update_state(|state| {
+                            state.fit(region);
+                            true
+                        }),
+                        self.viewer.highlight(region),
+                    ])
+                } else {
+                    tracing::error!("invalid go-to input: {input:?}");
+                    Command::none()
+                }
+            }
+            Message::OpenUrl => {
+                self.url_input = Some(String::new());
+                widget::text_input::focus(widget::text_input::Id::new(URL_INPUT))
+            }
+            Message::UrlInput(input) => {
+                self.url_input = Some(input);
+                Command::none()
+            }
+            Message::UrlSubmit => {
+                let url = self.url_input.take().unwrap_or_default();
+                self.viewer.open(url.trim().into(), self.policy)
+            }
+            Message::ZoomInput(input) => {
+                self.zoom_input = Some(input);
+                Command::none()
+            }
+            Message::ZoomSubmit => {
+                let input = self.zoom_input.take().unwrap_or_default();
+                match options::parse_percent(&input) {
+                    Some(percent) => self.viewer.update_state(|state| {
+                        state.set_zoom(parse::Zoom::from_percent_exact(percent));
+                        true
+                    }),
+                    None => {
+                        tracing::error!("invalid zoom percentage: {input:?}");
+                        Command::none()
+                    }
+                }
+            }
+            Message::HighlightExpired(region) => self.viewer.clear_highlight(region),
+            Message::Key(key_code, modifiers) => match self.viewer.key(key_code, modifiers) {
+                Some(message) => self.update(message),
+                None => Command::none(),
+            },
+            Message::FocusAction(step) => {
+                let count = self.toolbar().len() as isize;
+                self.focus = match self.focus {
+                    _ if count == 0 => None,
+                    Some(index) => Some((index as isize + step).rem_euclid(count) as usize),
+                    None if step < 0 => Some(count as usize - 1),
+                    None => Some(0),
+                };
+                Command::none()
+            }
+            Message::Confirm => {
+                match self
+                    .focus
+                    .and_then(|index| self.toolbar().into_iter().nth(index))
+                {
+                    Some((_, message)) => self.update(message),
+                    None => self.update(Message::BrowserOpenSelected),
+                }
+            }
+            Message::Escape => {
+                self.focus = None;
+                self.goto = None;
+                self.url_input = None;
+                self.zoom_input = None;
+                self.resize = None;
+                self.save_as = None;
+                self.theme_input = None;
+                self.viewer.clear_selection()
+            }
+            Message::NextSlide => {
+                let Some(slideshow) = &mut self.slideshow else {
+                    return Command::none();
+                };
+                let Some(path) = slideshow.advance().map(Path::to_path_buf) else {
+                    return Command::none();
+                };
+                self.pending_view = Some(self.slide_view);
+                self.viewer.open(path, self.policy)
+            }
+            Message::StartSlideshow => {
+                let Some(path) = self.viewer.path() else {
+                    return Command::none();
+                };
+                let dir = match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                match Slideshow::from_dir(dir) {
+                    Ok(mut slideshow) => {
+                        slideshow.seek(path);
+                        slideshow.interval = self.slide_interval;
+                        slideshow.set_shuffle(self.slide_shuffle);
+                        self.slideshow = Some(slideshow);
+                    }
+                    Err(error) => tracing::error!("from Slideshow::from_dir: {error}"),
+                }
+                Command::none()
+            }
+            Message::ToggleSlidePause => {
+                if let Some(slideshow) = &mut self.slideshow {
+                    slideshow.paused = !slideshow.paused;
+                }
+                Command::none()
+            }
+            Message::ToggleShuffle => {
+                if let Some(slideshow) = &mut self.slideshow {
+                    self.slide_shuffle = !slideshow.shuffle();
+                    slideshow.set_shuffle(self.slide_shuffle);
+                }
+                Command::none()
+            }
+            Message::StopSlideshow => {
+                self.slideshow = None;
+                Command::none()
+            }
+            Message::ToggleMetadata => {
+                self.show_metadata = !self.show_metadata;
+                if self.show_metadata {
+                    self.viewer.load_filters();
+                }
+                Command::none()
+            }
+            Message::BoardAdd if self.board.is_some() => self.file_dialog(
+                || {
+                    native_dialog::FileDialog::new()
+                        .set_title("Add to board")
+                        .add_filter("PNG image", &["png"])
+                        .show_open_multiple_file()
+                        .unwrap_or_else(|error| {
+                            tracing::error!("from native_dialog::FileDialog: {error}");
+                            Vec::new()
+                        })
+                },
+                Message::BoardPicked,
+            ),
+            Message::BoardAdd => Command::none(),
+            Message::BoardPicked(paths) => {
+                let Some(board) = &mut self.board else {
+                    return Command::none();
+                };
+                if paths.is_empty() {
+                    return Command::none();
+                }
+                for path in paths {
+                    board.add(path);
+                }
+                self.update(Message::BoardSave)
+            }
+            Message::Compare => self.file_dialog(
+                || {
+                    native_dialog::FileDialog::new()
+                        .set_title("Compare with")
+                        .add_filter("PNG image", &["png"])
+                        .show_open_single_file()
+                        .unwrap_or_else(|error| {
+                            tracing::error!("from native_dialog::FileDialog: {error}");
+                            None
+                        })
+                },
+                Message::ComparePicked,
+            ),
+            Message::ComparePicked(path) => {
+                if let Some(compare) = path.and_then(Compare::open) {
+                    self.compare = Some(compare);
+                }
+                Command::none()
+            }
+            Message::CompareMode(mode) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.set_mode(mode);
+                }
+                self.refresh_compare();
+                Command::none()
+            }
+            Message::CompareOpacity(opacity) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.set_opacity(opacity);
+                }
+                Command::none()
+            }
+            Message::CompareTolerance(tolerance) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.set_tolerance(tolerance);
+                }
+                self.refresh_compare();
+                Command::none()
+            }
+            Message::CompareSwipe(swipe) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.set_swipe(swipe);
+                }
+                Command::none()
+            }
+            Message::CloseCompare => {
+                self.compare = None;
+                Command::none()
+            }
+            Message::BoardMove(index, position) => {
+                if let Some(board) = &mut self.board {
+                    board.move_item(index, position);
+                }
+                Command::none()
+            }
+            Message::BoardPan(delta) => {
+                if let Some(board) = &mut self.board {
+                    board.pan(delta);
+                }
+                Command::none()
+            }
+            Message::BoardScale(index, zoom_in) => {
+                if let Some(board) = &mut self.board {
+                    board.scale_item(index, zoom_in);
+                }
+                self.update(Message::BoardSave)
+            }
+            Message::BoardSave => {
+                if let Some(Err(error)) = self.board.as_ref().map(Board::save) {
+                    tracing::error!("from Board::save: {error}");
+                }
+                Command::none()
+            }
+            Message::CloseBoard => {
+                self.board = None;
+                Command::none()
+            }
+            Message::UpdateConsent(granted) => {
+                self.update_banner = None;
+                if let Err(error) = update::set_consent(granted) {
+                    tracing::error!("from update::set_consent: {error}");
+                }
+                if granted {
+                    check_for_update()
+                } else {
+                    Command::none()
+                }
+            }
+            Message::UpdateChecked(release) => {
+                self.update_banner = release.map(UpdateBanner::Available);
+                Command::none()
+            }
+            Message::OpenRelease => {
+                if let Some(UpdateBanner::Available(release)) = self.update_banner.take() {
+                    if let Err(error) = update::open(&release.url) {
+                        tracing::error!("from update::open: {error}");
+                    }
+                }
+                Command::none()
+            }
+            Message::DismissUpdate => {
+                self.update_banner = None;
+                Command::none()
+            }
+            Message::CollectDiagnostics => self.collect_diagnostics(),
+            Message::About => {
+                if let Err(error) = native_dialog::MessageDialog::new()
+                    .set_title("About PNG Viewer")
+                    .set_text(&png_viewer::features::supported_features().to_string())
+                    .show_alert()
+                {
+                    tracing::error!("from native_dialog::MessageDialog: {error}");
+                }
+                Command::none()
+            }
+            Message::RegisterAssociation => {
+                let (title, text) = match association::register() {
+                    Ok(()) => (
+                        "Default viewer",
+                        "PNG files will now open in PNG Viewer.".to_string(),
+                    ),
+                    Err(error) => {
+                        tracing::error!("from association::register: {error}");
+                        (
+                            "Couldn't register",
+                            format!("PNG files weren't associated: {error}"),
+                        )
+                    }
+                };
+                if let Err(error) = native_dialog::MessageDialog::new()
+                    .set_title(title)
+                    .set_text(&text)
+                    .show_alert()
+                {
+                    tracing::error!("from native_dialog::MessageDialog: {error}");
+                }
+                Command::none()
+            }
+            Message::ThemeSettings => {
+                self.theme_input = Some(ThemeInput {
+                    accent: appearance::to_hex(self.appearance.accent),
+                    background: self
+                        .appearance
+                        .background
+                        .map(appearance::to_hex)
+                        .unwrap_or_default(),
+                });
+                Command::none()
+            }
+            Message::SetTheme(theme) => {
+                self.appearance.theme = theme;
+                self.save_appearance()
+            }
+            Message::ThemeAccent(accent) => {
+                if let Some(input) = &mut self.theme_input {
+                    input.accent = accent;
+                }
+                Command::none()
+            }
+            Message::ThemeBackground(background) => {
+                if let Some(input) = &mut self.theme_input {
+                    input.background = background;
+                }
+                Command::none()
+            }
+            Message::ThemeSubmit => {
+                let Some(input) = self.theme_input.take() else {
+                    return Command::none();
+                };
+                match options::parse_color(&input.accent) {
+                    Some(accent) => self.appearance.accent = accent,
+                    None => tracing::error!("invalid accent color: {:?}", input.accent),
+                }
+                // an empty field goes back to the theme's own background
+                self.appearance.background = options::parse_color(&input.background);
+                if self.appearance.background.is_none() && !input.background.trim().is_empty() {
+                    tracing::error!("invalid background color: {:?}", input.background);
+                }
+                self.save_appearance()
+            }
+            Message::Saved => Command::none(),
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let frames = if self.viewer.is_scrolling() {
+            window::frames().map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        };
+        let slides = if let Some(slideshow) = self.slideshow.as_ref().filter(|s| !s.paused) {
+            iced::time::every(slideshow.interval).map(|_| Message::NextSlide)
+        } else {
+            Subscription::none()
+        };
+        let progress = if self.viewer.progress().is_some() {
+            iced::time::every(PROGRESS_INTERVAL).map(|_| Message::DecodeProgress)
+        } else {
+            Subscription::none()
+        };
+        let input = iced::subscription::events_with(global_input);
+        Subscription::batch([frames, slides, progress, input])
+    }
+
+    fn view(&self) -> Element<'_, Self::Message, Renderer<Self::Theme>> {
+        struct ButtonTheme {
+            /// Ringed, since Tab moved keyboard focus here.
+            focused: bool,
+        }
+
+        impl widget::button::StyleSheet for ButtonTheme {
+            type Style = Theme;
+
+            fn active(&self, style: &Self::Style) -> widget::button::Appearance {
+                let appearance = style.active(&theme::Button::Primary);
+                widget::button::Appearance {
+                    border_radius: 15.0.into(),
+                    border_width: if self.focused { 3.0 } else { 0.0 },
+                    border_color: style.palette().text,
+                    ..appearance
+                }
+            }
+        }
+
+        let button = |label, message, focused| {
+            widget::button(label)
+                .style(theme::Button::custom(ButtonTheme { focused }))
+                .padding(10)
+                .on_press(message)
+        };
+        // the name and shortcut, for actions whose label alone doesn't say how to reach them
+        let labelled = |label: &'static str, message: Message, focused| {
+            let tip = match shortcut(&message) {
+                Some(keys) => format!("{label} ({keys})"),
+                None => label.to_string(),
+            };
+            widget::tooltip(
+                button(label, message, focused),
+                tip,
+                widget::tooltip::Position::Bottom,
+            )
+            .style(theme::Container::Box)
+            .into()
+        };
+
+        let mut buttons: Vec<_> = self
+            .toolbar()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (label, message))| labelled(label, message, self.focus == Some(index)))
+            .collect();
+        if let Some(zoom) = self.viewer.zoom().filter(|_| self.board.is_none()) {
+            let value = match &self.zoom_input {
+                Some(input) => input.clone(),
+                None => format!("{:.0}%", zoom.percent()),
+            };
+            buttons.push(
+                row![
+                    widget::text("Zoom:"),
+                    widget::text_input("Zoom", &value)
+                        .on_input(Message::ZoomInput)
+                        .on_submit(Message::ZoomSubmit)
+                        .padding(10)
+                        .width(70),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into(),
+            );
+        }
+        if let Some(compare) = &self.compare {
+            buttons.push(
+                row![
+                    widget::text("Compare:"),
+                    widget::pick_list(
+                        &compare::Mode::ALL[..],
+                        Some(compare.mode()),
+                        Message::CompareMode,
+                    )
+                    .padding(10),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into(),
+            );
+            let setting = match compare.mode() {
+                compare::Mode::SideBySide => None,
+                compare::Mode::Overlay => Some((
+                    "Opacity",
+                    widget::slider(0.0..=1.0, compare.opacity(), Message::CompareOpacity)
+                        .step(0.05)
+                        .width(120)
+                        .into(),
+                )),
+                compare::Mode::Difference => Some((
+                    "Tolerance",
+                    widget::slider(0..=64, compare.tolerance(), Message::CompareTolerance)
+                        .width(120)
+                        .into(),
+                )),
+            };
+            if let Some((label, slider)) = setting {
+                buttons.push(
+                    widget::Row::with_children(vec![widget::text(label).into(), slider])
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .into(),
+                );
+            }
+        }
+
+        let controls: Element<'_, Self::Message, Renderer<Self::Theme>> = match &self.goto {
+            None if let Some((rows, height)) = self.viewer.progress() => {
+                let percent = 100 * rows / height.max(1);
+                row![
+                    widget::text(format!("Decoding… {percent}%")),
+                    widget::progress_bar(0.0..=height.max(1) as f32, rows as f32)
+                        .width(300)
+                        .height(10),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into()
+            }
+            Some(input) => row![
+                widget::text("Go to pixel:"),
+                widget::text_input("x,y or x,y,width,height", input)
+                    .id(widget::text_input::Id::new(GOTO_INPUT))
+                    .on_input(Message::GoToInput)
+                    .on_submit(Message::GoToSubmit)
+                    .width(250),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into(),
+            None if let Some(input) = &self.url_input => row![
+                widget::text("Open URL:"),
+                widget::text_input("https://…", input)
+                    .id(widget::text_input::Id::new(URL_INPUT))
+                    .on_input(Message::UrlInput)
+                    .on_submit(Message::UrlSubmit)
+                    .width(400),
+                button("Open", Message::UrlSubmit, false),
+                button("Cancel", Message::Escape, false),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into(),
+            None if let Some(dialog) = &self.resize => row![
+                widget::text("Resize to:"),
+                widget::text_input("Width", &dialog.width)
+                    .on_input(Message::ResizeWidth)
+                    .on_submit(Message::ResizeSubmit)
+                    .width(80),
+                widget::text("×"),
+                widget::text_input("Height", &dialog.height)
+                    .on_input(Message::ResizeHeight)
+                    .on_submit(Message::ResizeSubmit)
+                    .width(80),
+                widget::pick_list(&Filter::ALL[..], Some(dialog.filter), Message::ResizeFilter),
+                button("Apply", Message::ResizeSubmit, false),
+                button("Cancel", Message::Escape, false),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into(),
+            None if let Some(input) = &self.theme_input => row![
+                widget::text("Theme:"),
+                widget::pick_list(
+                    &ThemeChoice::ALL[..],
+                    Some(self.appearance.theme),
+                    Message::SetTheme
+                ),
+                widget::text("Accent:"),
+                widget::text_input("#RRGGBB", &input.accent)
+                    .on_input(Message::ThemeAccent)
+                    .on_submit(Message::ThemeSubmit)
+                    .width(90),
+                widget::text("Background:"),
+                widget::text_input("Theme's own", &input.background)
+                    .on_input(Message::ThemeBackground)
+                    .on_submit(Message::ThemeSubmit)
+                    .width(110),
+                button("Apply", Message::ThemeSubmit, false),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into(),
+            None if let Some(save_as) = &self.save_as => {
+                let options = save_as.options();
+                row![
+                    widget::text("Save as:"),
+                    widget::pick_list(
+                        SaveAs::color_types(),
+                        Some(Auto(options.color_type)),
+                        Message::SaveColorType
+                    ),
+                    widget::pick_list(
+                        save_as.bit_depths(),
+                        Some(Auto(options.bit_depth)),
+                        Message::SaveBitDepth
+                    ),
+                    widget::checkbox(
+                        "Interlaced",
+                        options.interlace == Interlace::Adam7,
+                        Message::SaveInterlaced
+                    ),
+                    widget::text(format!("Compression {}", options.compression)),
+                    widget::slider(0..=9, options.compression, Message::SaveCompression).width(90),
+                    button("Save", Message::SaveSubmit, false),
+                    button("Cancel", Message::Escape, false),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .into()
+            }
+            None => widget::Row::with_children(buttons).spacing(10).into(),
+        };
+
+        let bottom_bar = row![
+            widget::horizontal_space(Length::Fill),
+            controls,
+            widget::horizontal_space(Length::Fill)
+        ]
+        .padding(20);
+
+        let canvas = Canvas::new(&self.viewer)
+            .height(Length::Fill)
+            .width(Length::Fill);
+        let background = self.background.or(self.appearance.background);
+        let canvas =
+            widget::container(canvas).style(move |_: &Theme| widget::container::Appearance {
+                background: background.map(Into::into),
+                ..Default::default()
+            });
+        if self.kiosk {
+            return canvas.into();
+        }
+        let canvas: Element<'_, Self::Message, Renderer<Self::Theme>> =
+            match (&self.compare, self.viewer.state(), self.viewer.pixels()) {
+                (Some(compare), Some(state), Some(pixels))
+                    if compare.mode() == compare::Mode::Overlay =>
+                {
+                    Canvas::new(compare.pane(state, pixels))
+                        .width(Length::Fill)
+                        .height(Length::Fill)
+                        .into()
+                }
+                (Some(compare), Some(state), Some(pixels))
+                    if compare.mode() == compare::Mode::Difference =>
+                {
+                    row![
+                        canvas,
+                        widget::vertical_rule(1),
+                        Canvas::new(compare.pane(state, pixels))
+                            .width(Length::Fill)
+                            .height(Length::Fill),
+                        widget::vertical_rule(1),
+                        Canvas::new(compare.heatmap_pane(state))
+                            .width(Length::Fill)
+                            .height(Length::Fill),
+                    ]
+                    .into()
+                }
+                (Some(compare), Some(state), Some(pixels)) => row![
+                    canvas,
+                    widget::vertical_rule(1),
+                    Canvas::new(compare.pane(state, pixels))
+                        .width(Length::Fill)
+                        .height(Length::Fill),
+                ]
+                .into(),
+                _ => canvas.into(),
+            };
+        let canvas: Element<'_, Self::Message, Renderer<Self::Theme>> = match self.viewer.timeline()
+        {
+            Some(timeline) => column![canvas, timeline_bar(timeline)].into(),
+            None if let Some(passes) = self.viewer.passes() => {
+                column![canvas, passes_bar(passes)].into()
+            }
+            None => canvas,
+        };
+
+        let main: Element<'_, Self::Message, Renderer<Self::Theme>> = match self.viewer.metadata() {
+            _ if let Some(gallery) = &self.gallery => gallery_view(gallery),
+            _ if let Some(board) = &self.board => Canvas::new(board)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .into(),
+            Some((metadata, statistics, filters)) if self.show_metadata => row![
+                canvas,
+                metadata_panel(metadata, statistics, filters, self.locale)
+            ]
+            .into(),
+            _ => canvas,
+        };
+        let main = match &self.browser {
+            Some(browser) => row![browser_panel(browser), main].into(),
+            None => main,
+        };
+
+        let status_bar = self.viewer.status(self.cursor).map(|status| {
+            widget::container(widget::text(status).size(14).font(iced::Font::MONOSPACE))
+                .padding([4, 10])
+                .width(Length::Fill)
+                .style(|theme: &Theme| widget::container::Appearance {
+                    background: Some(theme.extended_palette().background.weak.color.into()),
+                    ..Default::default()
+                })
+        });
+        let status_bar =
+            widget::Column::with_children(status_bar.into_iter().map(Into::into).collect());
+
+        let partial = self.viewer.partial().map(|partial| {
+            widget::container(widget::text(format!(
+                "⚠ The file ends early, so only part of the image is shown ({partial})"
+            )))
+            .padding(8)
+            .width(Length::Fill)
+            .style(|theme: &Theme| widget::container::Appearance {
+                background: Some(theme.extended_palette().danger.weak.color.into()),
+                text_color: Some(theme.extended_palette().danger.weak.text),
+                ..Default::default()
+            })
+        });
+
+        let banner = self.update_banner.as_ref().map(|banner| {
+            let (text, actions) = match banner {
+                UpdateBanner::AskConsent => (
+                    "Check online for new versions when PNG Viewer starts?".to_string(),
+                    [
+                        ("Yes", Message::UpdateConsent(true)),
+                        ("No", Message::UpdateConsent(false)),
+                    ],
+                ),
+                UpdateBanner::Available(release) => (
+                    match &release.summary {
+                        Some(summary) => {
+                            format!("PNG Viewer {} is available: {summary}", release.version)
+                        }
+                        None => format!("PNG Viewer {} is available", release.version),
+                    },
+                    [
+                        ("Release notes", Message::OpenRelease),
+                        ("Dismiss", Message::DismissUpdate),
+                    ],
+                ),
+            };
+            let buttons = actions.map(|(label, message)| {
+                widget::button(label)
+                    .style(theme::Button::Secondary)
+                    .on_press(message)
+                    .into()
+            });
+            widget::container(
+                widget::Row::with_children(
+                    std::iter::once(widget::text(text).width(Length::Fill).into())
+                        .chain(buttons)
+                        .collect(),
+                )
+                .spacing(10)
+                .align_items(Alignment::Center),
+            )
+            .padding(8)
+            .width(Length::Fill)
+            .style(|theme: &Theme| widget::container::Appearance {
+                background: Some(theme.extended_palette().background.weak.color.into()),
+                ..Default::default()
+            })
+        });
+
+        column![
+            widget::Column::with_children(
+                partial
+                    .into_iter()
+                    .map(Into::into)
+                    .chain(banner.map(Into::into))
+                    .collect()
+            ),
+            main,
+            widget::container("")
+                .style(|theme: &Theme| widget::container::Appearance {
+                    border_width: 2.0,
+                    border_color: theme.palette().primary,
+                    ..Default::default()
+                })
+                .width(Length::Fill)
+                .max_height(2),
+            bottom_bar,
+            status_bar,
+        ]
+        .into()
+    }
+
+    fn theme(&self) -> Self::Theme {
+        self.appearance.theme()
+    }
+}
+
+impl App {
+    fn night_tint(&self) -> Option<parse::ColorMatrix> {
+        self.night
+            .then(|| parse::color_temperature(self.night_kelvin))
+    }
+
+    /// Drops to draft quality while the view is moving, returning to full resolution
+    /// once it has been still for a moment.
+    fn interact(&mut self) -> Command<Message> {
+        self.interaction += 1;
+        let interaction = self.interaction;
+        Command::batch([
+            self.viewer.update_state(|state| state.set_draft(true)),
+            Command::perform(tokio::time::sleep(SETTLE_DELAY), move |_| {
+                Message::Settled(interaction)
+            }),
+        ])
+    }
+
+    /// Shows a native dialog off the UI thread, turning its answer into a message, and
+    /// offers the built-in browser if it isn't answered within [`DIALOG_TIMEOUT`]. Only
+    /// one dialog is shown at a time.
+    fn file_dialog<T: Default + Send + 'static>(
+        &mut self,
+        show: impl FnOnce() -> T + Send + 'static,
+        answer: impl FnOnce(T) -> Message + Send + 'static,
+    ) -> Command<Message> {
+        if self.dialog.is_some() {
+            return Command::none();
+        }
+        // AppKit only shows panels from the main thread, so it blocks there as before
+        if cfg!(target_os = "macos") {
+            return self.update(answer(show()));
+        }
+        self.dialogs += 1;
+        let dialog = self.dialogs;
+        self.dialog = Some(dialog);
+        Command::batch([
+            Command::perform(tokio::task::spawn_blocking(show), move |shown| {
+                let shown = shown.unwrap_or_else(|error| {
+                    tracing::error!("from tokio::task::spawn_blocking: {error}");
+                    T::default()
+                });
+                Message::DialogAnswered(dialog, Box::new(answer(shown)))
+            }),
+            Command::perform(tokio::time::sleep(DIALOG_TIMEOUT), move |()| {
+                Message::DialogStalled(dialog)
+            }),
+        ])
+    }
+
+    /// Shows thumbnails of the folder being viewed or browsed, decoding them in the
+    /// background.
+    fn open_gallery(&mut self) -> Command<Message> {
+        let dir = match (self.viewer.path().and_then(Path::parent), &self.browser) {
+            (Some(dir), _) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            (_, Some(browser)) => browser.dir().to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut gallery = match Gallery::open(&dir) {
+            Ok(gallery) => gallery,
+            Err(error) => {
+                tracing::error!("from Gallery::open: {}: {error}", dir.display());
+                return Command::none();
+            }
+        };
+        let command = Command::batch(gallery.start().into_iter().map(decode_thumbnail));
+        self.gallery = Some(gallery);
+        command
+    }
+
+    /// Enters the folder at `index` in the browser, or views the file.
+    fn activate_entry(&mut self, index: usize) -> Command<Message> {
+        let Some(browser) = &mut self.browser else {
+            return Command::none();
+        };
+        let Some(path) = browser.path(index) else {
+            return Command::none();
+        };
+        if browser.is_dir(index) {
+            *browser = Browser::open(path);
+            return Command::none();
+        }
+        browser.set_selected(index);
+        self.viewer.open(path, self.policy)
+    }
+
+    /// Decodes a preview of the file under the cursor or selected, unless it's showing.
+    fn request_preview(&self) -> Command<Message> {
+        let Some(browser) = &self.browser else {
+            return Command::none();
+        };
+        let Some(path) = browser
+            .preview_target()
+            .filter(|path| !browser.has_preview(path))
+        else {
+            return Command::none();
+        };
+        let decoding = path.clone();
+        Command::perform(
+            tokio::task::spawn_blocking(move || {
+                browser::decode_thumbnail(&decoding, browser::PREVIEW_SIDE)
+            }),
+            move |handle| Message::PreviewDecoded(path, handle.ok().flatten()),
+        )
+    }
+
+    fn save_appearance(&self) -> Command<Message> {
+        if let Err(error) = self.appearance.save() {
+            tracing::error!("from Appearance::save: {error}");
+        }
+        Command::none()
+    }
+
+    /// Shows the Save As bar for `image`, if there is one to save.
+    fn save_as(&mut self, image: Option<Unsaved>) -> Command<Message> {
+        self.save_as = image.map(SaveAs::new);
+        Command::none()
+    }
+
+    /// The buttons along the top, in the order Tab visits them.
+    fn toolbar(&self) -> Vec<(&'static str, Message)> {
+        if self.gallery.is_some() {
+            vec![("Close gallery", Message::CloseGallery)]
+        } else if self.board.is_some() {
+            vec![
+                ("Add image", Message::BoardAdd),
+                ("Close board", Message::CloseBoard),
+            ]
+        } else {
+            [
+                ("Open PNG", Message::Load),
+                ("Open URL", Message::OpenUrl),
+                ("Files", Message::ToggleBrowser),
+                ("Gallery", Message::OpenGallery),
+            ]
+            .into_iter()
+            .chain(self.history_actions())
+            .chain(self.compare_actions())
+            .chain(self.viewer.actions())
+            .chain(self.slideshow_actions())
+            .collect()
+        }
+    }
+
+    fn history_actions(&self) -> Vec<(&'static str, Message)> {
+        let mut actions = vec![];
+        if self.history.can_go_back() {
+            actions.push(("Back", Message::HistoryBack));
+        }
+        if self.history.can_go_forward() {
+            actions.push(("Forward", Message::HistoryForward));
+        }
+        actions
+    }
+
+    fn compare_actions(&self) -> Vec<(&'static str, Message)> {
+        match (&self.compare, &self.viewer) {
+            (Some(_), _) => vec![("Close comparison", Message::CloseCompare)],
+            (None, Viewer::Viewing { .. }) => vec![("Compare", Message::Compare)],
+            (None, _) => vec![],
+        }
+    }
+
+    /// Has the comparison catch up with the image being viewed, or how it's compared.
+    fn refresh_compare(&mut self) {
+        if let (Some(compare), Some(pixels)) = (&mut self.compare, self.viewer.pixels()) {
+            compare.set_base(pixels);
+        }
+    }
+
+    fn slideshow_actions(&self) -> Vec<(&'static str, Message)> {
+        match &self.slideshow {
+            Some(slideshow) => vec![
+                (
+                    if slideshow.paused { "Resume" } else { "Pause" },
+                    Message::ToggleSlidePause,
+                ),
+                (
+                    if slideshow.shuffle() {
+                        "Shuffle: on"
+                    } else {
+                        "Shuffle: off"
+                    },
+                    Message::ToggleShuffle,
+                ),
+                ("Stop slideshow", Message::StopSlideshow),
+            ],
+            None if matches!(self.viewer, Viewer::Viewing { .. }) => {
+                vec![("Slideshow", Message::StartSlideshow)]
+            }
+            None => vec![],
+        }
+    }
+
+    fn collect_diagnostics(&mut self) -> Command<Message> {
+        let (decode, path) = self.viewer.diagnostics();
+        // the chunk table reveals the file's layout, so only include it if asked
+        let chunks = path
+            .filter(|path| {
+                native_dialog::MessageDialog::new()
+                    .set_title("Collect diagnostics")
+                    .set_text(&format!(
+                        "Include the chunk layout of {}? No pixel data or text is included.",
+                        path.display()
+                    ))
+                    .show_confirm()
+                    .unwrap_or_else(|error| {
+                        tracing::error!("from native_dialog::MessageDialog: {error}");
+                        false
+                    })
+            })
+            .and_then(|path| {
+                std::fs::read(path)
+                    .map_err(|error| tracing::error!("from std::fs::read: {error}"))
+                    .ok()
+            })
+            .map(|data| diagnostics::chunk_table(&data));
+
+        let report = diagnostics::Report {
+            settings: vec![
+                ("policy", format!("{:?}", self.policy)),
+                ("locale", format!("{:?}", self.locale)),
+                ("night", format!("{} ({} K)", self.night, self.night_kelvin)),
+                ("background", format!("{:?}", self.background)),
+                ("badge", self.show_badge.to_string()),
+                ("metadata panel", self.show_metadata.to_string()),
+                ("slideshow", self.slideshow.is_some().to_string()),
+                ("board", self.board.is_some().to_string()),
+            ],
+            decode,
+            chunks,
+        };
+        let data = match report.bundle() {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::error!("from diagnostics::Report::bundle: {error}");
+                return Command::none();
+            }
+        };
+
+        self.file_dialog(
+            move || {
+                save_with(
+                    native_dialog::FileDialog::new()
+                        .set_title("Save diagnostics")
+                        .set_filename("png-viewer-diagnostics.zip")
+                        .add_filter("Zip archive", &["zip"]),
+                    &data,
+                )
+            },
+            |()| Message::Saved,
+        )
+    }
+}
+
+enum Viewer {
+    Viewing {
+        path: PathBuf,
+        data: Data,
+        cache: Cache,
+        state: Box<parse::State>,
+        /// Region picked by auto-crop or by dragging, in image coordinates.
+        selection: Option<Rectangle<usize>>,
+        highlight: Option<Box<Rectangle>>,
+        metadata: Vec<Text>,
+        statistics: Option<Box<Statistics>>,
+        filters: Option<Box<Filters>>,
+        policy: Policy,
+        format: Box<str>,
+        show_badge: bool,
+        /// Tint scanlines by their filter type.
+        show_filters: bool,
+        timeline: Option<Timeline>,
+        /// An interlaced image shown pass by pass, in place of the whole.
+        passes: Option<Box<Passes>>,
+        minimap: Box<Minimap>,
+        /// What `data` decodes to, so redraws never parse the file again.
+        pixels: Box<Pixels>,
+        /// Set when the file is cut short and only its first rows are shown.
+        partial: Option<Box<str>>,
+        orientation: Option<Orientation>,
+    },
+    Decoding(Session),
+    Error {
+        message: String,
+        path: Option<PathBuf>,
+    },
+    Empty {
+        emoji: char,
+    },
+}
+
+impl Viewer {
+    fn open(&mut self, path: PathBuf, policy: Policy) -> Command<Message> {
+        tracing::debug!("Loading: {}", path.display());
+        // replacing a session that's still decoding cancels it
+        let (session, done) = Session::start(path, policy);
+        *self = Self::Decoding(session);
+        Command::perform(done, |()| Message::Decoded)
+    }
+
+    fn decoded(&mut self) -> Command<Message> {
+        let Self::Decoding(session) = self else {
+            // a cancelled session finishing after the viewer moved on
+            return Command::none();
+        };
+        let Some(result) = session.try_result() else {
+            return Command::none();
+        };
+        let path = session.path().to_path_buf();
+
+        match result {
+            Ok(decoded) => {
+                let mut state = parse::State::new(decoded.image_size);
+                if let Some(orientation) = decoded.orientation {
+                    state.set_rotation(orientation.rotation);
+                    state.set_mirrored(orientation.mirrored);
+                }
+                *self = Self::Viewing {
+                    path,
+                    data: decoded.data,
+                    cache: Cache::new(),
+                    state: Box::new(state),
+                    selection: None,
+                    highlight: None,
+                    metadata: decoded.metadata,
+                    statistics: decoded.statistics,
+                    filters: None,
+                    policy: decoded.policy,
+                    format: decoded.format.into(),
+                    show_badge: false,
+                    show_filters: false,
+                    timeline: decoded.timeline,
+                    passes: None,
+                    minimap: Box::new(decoded.minimap),
+                    pixels: Box::new(decoded.pixels),
+                    partial: decoded.partial.map(Into::into),
+                    orientation: decoded.orientation,
+                };
+            }
+            Err(error) => {
+                tracing::error!("from Session::try_result: {error}");
+                let verb = match error {
+                    parse::error::Error::IoError(_) => "read",
+                    _ => "decode",
+                };
+                *self = Self::Error {
+                    message: format!("Couldn't {verb} {}: {error}", path.display()),
+                    path: Some(path),
+                };
+            }
+        }
+        Command::none()
+    }
+
+    /// Scanlines decoded so far and the image height, while decoding.
+    fn progress(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Decoding(session) => Some(session.progress()),
+            _ => None,
+        }
+    }
+
+    fn retry(&mut self, policy: Policy) -> Command<Message> {
+        match self {
+            Self::Error {
+                path: Some(path), ..
+            } => {
+                let path = path.clone();
+                self.open(path, policy)
+            }
+            _ => Command::none(),
+        }
+    }
+
+    fn update_state(&mut self, update: impl FnOnce(&mut parse::State) -> bool) -> Command<Message> {
+        if let Self::Viewing { cache, state, .. } = self {
+            if update(state) {
+                cache.clear();
+            }
+        }
+        Command::none()
+    }
+
+    fn highlight(&mut self, region: Rectangle) -> Command<Message> {
+        let Self::Viewing { highlight, .. } = self else {
+            return Command::none();
+        };
+        *highlight = Some(Box::new(region));
+        Command::perform(tokio::time::sleep(HIGHLIGHT_DURATION), move |_| {
+            Message::HighlightExpired(region)
+        })
+    }
+
+    fn clear_highlight(&mut self, region: Rectangle) -> Command<Message> {
+        if let Self::Viewing { highlight, .. } = self {
+            // a newer highlight may have replaced this one
+            if highlight.as_deref() == Some(&region) {
+                *highlight = None;
+            }
+        }
+        Command::none()
+    }
+
+    fn metadata(&self) -> Option<(&[Text], Option<&Statistics>, Option<&Filters>)> {
+        match self {
+            Self::Viewing {
+                metadata,
+                statistics,
+                filters,
+                ..
+            } => Some((metadata, statistics.as_deref(), filters.as_deref())),
+            _ => None,
+        }
+    }
+
+    fn set_show_badge(&mut self, show: bool) {
+        if let Self::Viewing { show_badge, .. } = self {
+            *show_badge = show;
+        }
+    }
+
+    fn set_show_filters(&mut self, show: bool) {
+        if let Self::Viewing { show_filters, .. } = self {
+            *show_filters = show;
+        }
+        if show {
+            self.load_filters();
+        }
+    }
+
+    /// Records each scanline's filter type the first time the overlay or the metadata panel
+    /// shows them, since that takes another pass over the image data.
+    fn load_filters(&mut self) {
+        if let Self::Viewing {
+            data,
+            policy,
+            filters: filters @ None,
+            ..
+        } = self
+        {
+            *filters = read_data(data).and_then(|data| record_filters(&data, *policy));
+        }
+    }
+
+    fn timeline(&self) -> Option<&Timeline> {
+        match self {
+            Self::Viewing { timeline, .. } => timeline.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn seek_frame(&mut self, index: usize) -> Command<Message> {
+        if let Self::Viewing {
+            timeline: Some(timeline),
+            cache,
+            ..
+        } = self
+        {
+            if timeline.seek(index) {
+                cache.clear();
+            }
+        }
+        Command::none()
+    }
+
+    /// Shows an interlaced image pass by pass, playing from the first, or goes back to
+    /// showing it whole.
+    fn set_show_passes(&mut self, show: bool) -> Command<Message> {
+        let Self::Viewing {
+            data,
+            cache,
+            policy,
+            timeline,
+            passes,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+        if show == passes.is_some() {
+            return Command::none();
+        }
+        cache.clear();
+        // an animation's frames aren't interlaced on their own
+        *passes = if show && timeline.is_none() {
+            read_data(data)
+                .and_then(|data| Passes::new(&data, *policy))
+                .map(Box::new)
+        } else {
+            None
+        };
+        if passes.is_some() {
+            next_pass()
+        } else {
+            Command::none()
+        }
+    }
+
+    fn passes(&self) -> Option<&Passes> {
+        match self {
+            Self::Viewing { passes, .. } => passes.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn seek_pass(&mut self, index: usize) -> Command<Message> {
+        if let Self::Viewing {
+            passes: Some(passes),
+            cache,
+            ..
+        } = self
+        {
+            passes.pause();
+            if passes.seek(index) {
+                cache.clear();
+            }
+        }
+        Command::none()
+    }
+
+    fn play_passes(&mut self) -> Command<Message> {
+        let Self::Viewing {
+            passes: Some(passes),
+            cache,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+        // already ticking if it was playing
+        let ticking = passes.playing();
+        passes.play();
+        cache.clear();
+        if ticking {
+            Command::none()
+        } else {
+            next_pass()
+        }
+    }
+
+    fn pass_tick(&mut self) -> Command<Message> {
+        let Self::Viewing {
+            passes: Some(passes),
+            cache,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+        if passes.advance() {
+            cache.clear();
+            next_pass()
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Size of the image being viewed.
+    fn image_size(&self) -> Option<Size> {
+        match self {
+            Self::Viewing { state, .. } => Some(state.image_size()),
+            _ => None,
+        }
+    }
+
+    fn state(&self) -> Option<&parse::State> {
+        match self {
+            Self::Viewing { state, .. } => Some(state),
+            _ => None,
+        }
+    }
+
+    fn pixels(&self) -> Option<&Pixels> {
+        match self {
+            Self::Viewing { pixels, .. } => Some(pixels),
+            _ => None,
+        }
+    }
+
+    fn zoom(&self) -> Option<parse::Zoom> {
+        match self {
+            Self::Viewing { state, .. } => Some(state.zoom()),
+            _ => None,
+        }
+    }
+
+    /// Size, format and zoom of the image, then the position and value of the pixel under
+    /// `cursor`.
+    fn status(&self, cursor: Option<Point>) -> Option<String> {
+        let Self::Viewing {
+            state,
+            format,
+            pixels,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        let size = state.image_size();
+        let mut status = format!(
+            "{} × {}   {format}   {:.0}%",
+            size.width,
+            size.height,
+            state.zoom().percent()
+        );
+        let pixel = cursor.map(|cursor| state.to_image(cursor)).filter(|point| {
+            (0.0..pixels.width() as f32).contains(&point.x)
+                && (0.0..pixels.height() as f32).contains(&point.y)
+        });
+        if let Some(point) = pixel {
+            let (x, y) = (point.x as usize, point.y as usize);
+            let [r, g, b, a] = pixels.get(x, y);
+            status += &format!("   ({x}, {y})   rgba({r}, {g}, {b}, {a})");
+        }
+        Some(status)
+    }
+
+    fn toggle_orientation(&mut self) -> Command<Message> {
+        let Self::Viewing {
+            orientation: Some(orientation),
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+        let orientation = *orientation;
+        self.update_state(|state| {
+            if state.rotation() == orientation.rotation && state.mirrored() == orientation.mirrored
+            {
+                state.set_rotation(parse::Rotation::R0);
+                state.set_mirrored(false);
+            } else {
+                state.set_rotation(orientation.rotation);
+                state.set_mirrored(orientation.mirrored);
+            }
+            true
+        })
+    }
+
+    fn partial(&self) -> Option<&str> {
+        match self {
+            Self::Viewing {
+                partial: Some(partial),
+                ..
+            } => Some(partial),
+            _ => None,
+        }
+    }
+
+    fn is_scrolling(&self) -> bool {
+        matches!(self, Self::Viewing { state, .. } if state.is_scrolling())
+    }
+
+    fn actions(&self) -> Vec<(&'static str, Message)> {
+        match self {
+            Self::Viewing {
+                selection: None,
+                policy,
+                state,
+                orientation,
+                ..
+            } => [
+                ("Auto-crop", Message::AutoCrop),
+                ("Resize", Message::Resize),
+                ("Export view", Message::ExportView),
+                ("Fit content", Message::FitContent),
+                ("Share", Message::Share),
+                ("Metadata", Message::ToggleMetadata),
+                ("Filters", Message::ToggleFilterOverlay),
+                ("Passes", Message::ToggleInterlacePasses),
+                ("Night mode", Message::ToggleNight),
+                (
+                    if state.smooth() {
+                        "Scaling: smooth"
+                    } else {
+                        "Scaling: nearest"
+                    },
+                    Message::ToggleSmooth,
+                ),
+                (
+                    match policy {
+                        Policy::Strict => "Lenient: off",
+                        Policy::Lenient => "Lenient: on",
+                    },
+                    Message::ToggleLenient,
+                ),
+            ]
+            .into_iter()
+            .chain(orientation.map(|orientation| {
+                let upright = state.rotation() == orientation.rotation
+                    && state.mirrored() == orientation.mirrored;
+                (
+                    if upright {
+                        "Orientation: EXIF"
+                    } else {
+                        "Orientation: raw"
+                    },
+                    Message::ToggleOrientation,
+                )
+            }))
+            .collect(),
+            Self::Error { path: Some(_), .. } => vec![
+                ("Retry", Message::Retry),
+                ("Collect diagnostics", Message::CollectDiagnostics),
+            ],
+            Self::Error { path: None, .. } => {
+                vec![("Collect diagnostics", Message::CollectDiagnostics)]
+            }
+            Self::Viewing {
+                selection: Some(_), ..
+            } => vec![
+                ("Crop", Message::Crop),
+                ("Export selection", Message::ExportSelection),
+                ("Copy", Message::CopySelection),
+                ("Cancel", Message::ClearSelection),
+            ],
+            Self::Empty { .. } => vec![
+                ("About", Message::About),
+                ("Open PNGs here", Message::RegisterAssociation),
+                ("Theme", Message::ThemeSettings),
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// What a key pressed outside any text box does to the image being viewed.
+    fn key(&self, key_code: keyboard::KeyCode, modifiers: keyboard::Modifiers) -> Option<Message> {
+        use keyboard::KeyCode;
+
+        let Self::Viewing { passes, .. } = self else {
+            return None;
+        };
+        Some(match key_code {
+            KeyCode::R => Message::Rotate,
+            KeyCode::N => Message::ToggleNight,
+            KeyCode::S if modifiers.is_empty() => Message::ToggleSmooth,
+            KeyCode::B => Message::ToggleBadge,
+            KeyCode::F if modifiers.shift() => Message::ToggleFilterOverlay,
+            KeyCode::I if modifiers.shift() => Message::ToggleInterlacePasses,
+            KeyCode::Comma if let Some(passes) = passes => {
+                Message::SeekPass(passes.current().saturating_sub(1))
+            }
+            KeyCode::Period if let Some(passes) = passes => Message::SeekPass(passes.current() + 1),
+            KeyCode::P => Message::ToggleSlidePause,
+            KeyCode::Backspace => Message::ToggleOriginal,
+            KeyCode::G if modifiers.command() => Message::GoTo,
+            KeyCode::D if modifiers.command() && modifiers.shift() => Message::CollectDiagnostics,
+            KeyCode::Plus | KeyCode::Equals | KeyCode::NumpadAdd => Message::ZoomIn(None),
+            KeyCode::Minus | KeyCode::NumpadSubtract => Message::ZoomOut(None),
+            KeyCode::Key0 if modifiers.is_empty() => Message::ZoomToggle(None),
+            KeyCode::Space if modifiers.shift() => Message::Jump(parse::Jump::PageUp),
+            KeyCode::Space | KeyCode::PageDown => Message::Jump(parse::Jump::PageDown),
+            KeyCode::PageUp => Message::Jump(parse::Jump::PageUp),
+            KeyCode::Home => Message::Jump(parse::Jump::Top),
+            KeyCode::End => Message::Jump(parse::Jump::Bottom),
+            _ => return None,
+        })
+    }
+
+    /// The file being shown or decoded.
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Viewing { path, .. } => Some(path),
+            Self::Decoding(session) => Some(session.path()),
+            Self::Error { path, .. } => path.as_deref(),
+            Self::Empty { .. } => None,
+        }
+    }
+
+    /// Decode details for a diagnostics report, and the file they came from.
+    fn diagnostics(&self) -> (Vec<(&'static str, String)>, Option<&Path>) {
+        match self {
+            Self::Viewing {
+                path,
+                data,
+                state,
+                metadata,
+                statistics,
+                policy,
+                format,
+                ..
+            } => {
+                let size = state.image_size();
+                let mut decode = vec![
+                    ("state", "viewing".into()),
+                    (
+                        "bytes",
+                        data.size().map_or("?".into(), |size| size.to_string()),
+                    ),
+                    ("size", format!("{}x{}", size.width, size.height)),
+                    ("format", format.to_string()),
+                    ("policy", format!("{policy:?}")),
+                    ("text chunks", metadata.len().to_string()),
+                    ("zoom", format!("{:.0}%", state.zoom().percent())),
+                ];
+                if let Some(statistics) = statistics {
+                    decode.push(("statistics", statistics.to_json()));
+                }
+                (decode, Some(path))
+            }
+            Self::Decoding(session) => {
+                let (rows, height) = session.progress();
+                let decode = vec![
+                    ("state", "decoding".into()),
+                    ("rows", format!("{rows} of {height}")),
+                ];
+                (decode, Some(session.path()))
+            }
+            Self::Error { message, path } => (
+                vec![("state", "error".into()), ("error", message.clone())],
+                path.as_deref(),
+            ),
+            Self::Empty { .. } => (vec![("state", "empty".into())], None),
+        }
+    }
 
-const SIZE: (u32, u32) = (700, 700);
-const MIN_SIZE: (u32, u32) = (200, 400);
-const PHOTO_ICON: &[u8] = include_bytes!("../assets/photo.ico");
-const EMOJIS: &[char] = &['🌄', '🌅', '🌇', '🌠', '🌉', '🏡', '🌺', '⛵', '🪐', '🌞'];
+    fn auto_crop(&mut self) -> Command<Message> {
+        if let Self::Viewing {
+            data,
+            selection,
+            policy,
+            ..
+        } = self
+        {
+            let Some(data) = read_data(data) else {
+                return Command::none();
+            };
+            match decode(&data, *policy) {
+                Ok(image) => {
+                    *selection = image.auto_crop();
+                    if selection.is_none() {
+                        tracing::debug!("No uniform border found");
+                    }
+                }
+                Err(error) => {
+                    tracing::error!("from parse::decode: {error}");
+                }
+            }
+        }
+        Command::none()
+    }
 
-fn main() -> iced::Result {
-    tracing_subscriber::fmt::fmt()
-        .with_env_filter("png_viewer")
-        .init();
+    fn select(&mut self, from: Point, to: Point) -> Command<Message> {
+        if let Self::Viewing {
+            state, selection, ..
+        } = self
+        {
+            let region = state.screen_region(from, to);
+            *selection = (region.width > 0 && region.height > 0).then_some(region);
+        }
+        Command::none()
+    }
 
-    App::run(Settings {
-        window: window::Settings {
-            size: SIZE,
-            position: window::Position::Centered,
-            min_size: Some(MIN_SIZE),
-            icon: Some(window::icon::from_file_data(PHOTO_ICON, None).unwrap()),
-            ..window::Settings::default()
-        },
-        ..Settings::default()
-    })
-}
+    fn clear_selection(&mut self) -> Command<Message> {
+        if let Self::Viewing { selection, .. } = self {
+            *selection = None;
+        }
+        Command::none()
+    }
 
-#[derive(Default)]
-struct App {
-    viewer: Viewer,
-}
+    fn export_selection(&mut self) -> Option<Unsaved> {
+        let Self::Viewing {
+            data,
+            selection: Some(region),
+            policy,
+            timeline,
+            ..
+        } = self
+        else {
+            return None;
+        };
 
-#[derive(Debug, Clone)]
-enum Message {
-    Load,
-    Loaded,
-}
+        let region = *region;
+        let data = shown_data(data, timeline)?;
+        if is_sixteen_bit(&data) {
+            export::<u16>(&data, *policy, |image| image.crop(region))
+        } else {
+            export::<u8>(&data, *policy, |image| image.crop(region))
+        }
+    }
 
-impl Application for App {
-    type Executor = executor::Default;
+    fn crop(&mut self) -> Option<Unsaved> {
+        match self {
+            Self::Viewing {
+                selection: Some(region),
+                ..
+            } => {
+                let region = *region;
+                self.edit(Edit::Crop(region))
+            }
+            _ => None,
+        }
+    }
 
-    type Message = Message;
+    /// Replaces the image being viewed with an edited copy, which is returned for saving.
+    fn edit(&mut self, edit: Edit) -> Option<Unsaved> {
+        let Self::Viewing {
+            data,
+            cache,
+            state,
+            selection,
+            statistics,
+            filters,
+            policy,
+            timeline,
+            minimap,
+            pixels,
+            partial,
+            ..
+        } = self
+        else {
+            return None;
+        };
 
-    type Theme = Theme;
+        let source = shown_data(data, timeline)?;
+        let edited = if is_sixteen_bit(&source) {
+            export::<u16>(&source, *policy, |image| edit.apply(image))
+        } else {
+            export::<u8>(&source, *policy, |image| edit.apply(image))
+        }?;
+        drop(source);
+        let (png, image) = match edited
+            .encode(&encode::Options::default())
+            .and_then(|png| Ok((decode(&png, *policy)?, png)))
+        {
+            Ok((image, png)) => (png, image),
+            Err(error) => {
+                tracing::error!("from Unsaved::encode: {error}");
+                return None;
+            }
+        };
 
-    type Flags = ();
+        // from here on the edited image is the one being viewed
+        **state = parse::State::new(Size::new(image.width() as f32, image.height() as f32));
+        *statistics = image.statistics().map(Box::new);
+        if filters.is_some() {
+            *filters = record_filters(&png, *policy);
+        }
+        *data = Data::Memory(png);
+        **minimap = Minimap::new(&image);
+        **pixels = match &edited {
+            Unsaved::Eight(_) => Pixels::Eight(image),
+            Unsaved::Sixteen(image) => Pixels::Sixteen(image.clone()),
+        };
+        *partial = None;
+        *timeline = None;
+        *selection = None;
+        cache.clear();
+        Some(edited)
+    }
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        (Self::default(), Command::none())
+    fn copy_selection(&self) -> Command<Message> {
+        let Self::Viewing {
+            data,
+            selection: Some(region),
+            policy,
+            timeline,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+
+        let Some(data) = shown_data(data, timeline) else {
+            return Command::none();
+        };
+        let png =
+            match decode(&data, *policy).and_then(|image| encode::encode(&image.crop(*region))) {
+                Ok(png) => png,
+                Err(error) => {
+                    tracing::error!("from encode::encode: {error}");
+                    return Command::none();
+                }
+            };
+        Command::perform(clipboard::copy_png(png), |result| {
+            if let Err(error) = result {
+                tracing::error!("from clipboard::copy_png: {error}");
+            }
+            Message::Saved
+        })
     }
 
-    fn title(&self) -> String {
-        "PNG Viewer".into()
+    fn fit_content(&mut self) -> Command<Message> {
+        let Self::Viewing {
+            data,
+            state,
+            cache,
+            policy,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+
+        let Some(data) = read_data(data) else {
+            return Command::none();
+        };
+        match decode(&data, *policy) {
+            Ok(image) => {
+                if let Some(bounds) = image.opaque_bounds() {
+                    state.fit(to_f32(bounds));
+                    cache.clear();
+                }
+            }
+            Err(error) => {
+                tracing::error!("from parse::decode: {error}");
+            }
+        }
+        Command::none()
     }
 
-    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
-        match message {
-            Message::Load => self.viewer.load(),
-            Message::Loaded => self.viewer.loaded(),
+    fn set_policy(&mut self, new_policy: Policy) -> Command<Message> {
+        let Self::Viewing {
+            path,
+            data,
+            policy,
+            cache,
+            ..
+        } = self
+        else {
+            return Command::none();
+        };
+
+        *policy = new_policy;
+        cache.clear();
+        let decoded = data
+            .read()
+            .map_err(Into::into)
+            .and_then(|data| decode(&data, new_policy));
+        if let Err(error) = decoded {
+            tracing::error!("from parse::decode: {error}");
+            *self = Self::Error {
+                message: format!("Couldn't decode {}: {error}", path.display()),
+                path: Some(std::mem::take(path)),
+            };
         }
+        Command::none()
     }
 
-    fn view(&self) -> Element<'_, Self::Message, Renderer<Self::Theme>> {
-        struct ButtonTheme;
+    fn share(&self) -> Command<Message> {
+        let Self::Viewing { data, .. } = self else {
+            return Command::none();
+        };
 
-        impl widget::button::StyleSheet for ButtonTheme {
-            type Style = Theme;
+        let Some(data) = read_data(data) else {
+            return Command::none();
+        };
+        Command::perform(share::share(data.into_owned()), |result| {
+            if let Err(error) = result {
+                tracing::error!("from share::share: {error}");
+            }
+            Message::Saved
+        })
+    }
 
-            fn active(&self, style: &Self::Style) -> widget::button::Appearance {
-                widget::button::Appearance {
-                    border_radius: 15.0.into(),
-                    ..style.active(&theme::Button::Primary)
-                }
+    fn export_view(&mut self) -> Option<Unsaved> {
+        let Self::Viewing {
+            data,
+            state,
+            policy,
+            timeline,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        let data = shown_data(data, timeline)?;
+        if is_sixteen_bit(&data) {
+            export::<u16>(&data, *policy, |image| image.view(state))
+        } else {
+            export::<u8>(&data, *policy, |image| image.view(state))
+        }
+    }
+}
+
+/// Runs a gallery job in the background, unless the gallery has closed by the time it starts.
+fn decode_thumbnail(job: gallery::Job) -> Command<Message> {
+    let (id, path) = (job.gallery, job.path.clone());
+    Command::perform(
+        tokio::task::spawn_blocking(move || {
+            if job.is_cancelled() {
+                return None;
             }
+            browser::decode_thumbnail(&job.path, gallery::THUMBNAIL_SIDE)
+        }),
+        move |handle| Message::ThumbnailDecoded(id, path, handle.ok().flatten()),
+    )
+}
+
+/// The viewed file's bytes, read again from disk if that's where they are.
+fn read_data(data: &Data) -> Option<Cow<'_, [u8]>> {
+    data.read()
+        .map_err(|error| tracing::error!("from Data::read: {error}"))
+        .ok()
+}
+
+/// The bytes of the frame being shown: the file's own, unless it's an animation.
+fn shown_data<'a>(data: &'a Data, timeline: &'a Option<Timeline>) -> Option<Cow<'a, [u8]>> {
+    match timeline {
+        Some(timeline) => Some(Cow::Borrowed(timeline.data())),
+        None => read_data(data),
+    }
+}
+
+fn record_filters(data: &[u8], policy: Policy) -> Option<Box<Filters>> {
+    parse::filters(data, Handlers::default().policy(policy))
+        .map(Box::new)
+        .map_err(|error| tracing::error!("from parse::filters: {error}"))
+        .ok()
+}
+
+/// Decodes with samples as stored, for editing and saving rather than showing.
+fn decode(data: &[u8], policy: Policy) -> Result<Image, parse::error::Error> {
+    parse::decode_with(data, Handlers::default().policy(policy))
+}
+
+fn is_sixteen_bit(data: &[u8]) -> bool {
+    parse::color_info(data).is_ok_and(|info| info.bit_depth == BitDepth::Sixteen)
+}
+
+/// Decodes with `S`-sized channels and returns what `change` makes of the image.
+fn export<S: Sample>(
+    data: &[u8],
+    policy: Policy,
+    change: impl FnOnce(Raster<S>) -> Raster<S>,
+) -> Option<Unsaved>
+where
+    Raster<S>: Into<Unsaved>,
+{
+    match parse::draw(data, Raster::new, Handlers::default().policy(policy)) {
+        Ok(image) => Some(change(image).into()),
+        Err(error) => {
+            tracing::error!("from parse::draw: {error}");
+            None
         }
+    }
+}
 
-        let open_button = widget::button("Open PNG")
-            .style(theme::Button::custom(ButtonTheme))
-            .padding(10)
-            .on_press(Message::Load);
+/// A change that produces a new image to view and save.
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Crop(Rectangle<usize>),
+    Resize(usize, usize, Filter),
+}
 
-        let bottom_bar = row![
-            widget::horizontal_space(Length::Fill),
-            open_button,
-            widget::horizontal_space(Length::Fill)
-        ]
-        .padding(20);
+impl Edit {
+    fn apply<S: Sample>(self, image: Raster<S>) -> Raster<S> {
+        match self {
+            Self::Crop(region) => image.crop(region),
+            Self::Resize(width, height, filter) => image.resample(width, height, filter),
+        }
+    }
+}
 
-        column![
-            Canvas::new(&self.viewer)
-                .height(Length::Fill)
-                .width(Length::Fill),
-            widget::container("")
-                .style(|theme: &Theme| widget::container::Appearance {
-                    border_width: 2.0,
-                    border_color: theme.palette().primary,
-                    ..Default::default()
+fn to_f32(region: Rectangle<usize>) -> Rectangle {
+    Rectangle {
+        x: region.x as f32,
+        y: region.y as f32,
+        width: region.width as f32,
+        height: region.height as f32,
+    }
+}
+
+fn metadata_panel<'a>(
+    metadata: &'a [Text],
+    statistics: Option<&Statistics>,
+    filters: Option<&Filters>,
+    locale: Locale,
+) -> Element<'a, Message, Renderer<Theme>> {
+    const WARNING: Color = Color::from_rgb(1.0, 0.75, 0.3);
+
+    let statistics = statistics.map(|stats| {
+        let channels = ["R", "G", "B", "A"].iter().enumerate().map(|(c, name)| {
+            widget::text(format!(
+                "{name}: mean {:.1}, σ {:.1}, {}–{}",
+                stats.mean[c], stats.std_dev[c], stats.min[c], stats.max[c]
+            ))
+            .size(12)
+            .into()
+        });
+        widget::Column::with_children(
+            std::iter::once(widget::text("Statistics").size(14).into())
+                .chain(channels)
+                .chain(std::iter::once(
+                    widget::text(format!("Opaque: {:.1}%", stats.opaque_percent))
+                        .size(12)
+                        .into(),
+                ))
+                .collect(),
+        )
+        .spacing(4)
+    });
+
+    let filters = filters.map(|filters| {
+        let total = filters.sequence().len().max(1);
+        let counts = FilterType::ALL
+            .into_iter()
+            .zip(filters.counts())
+            .map(|(filter, count)| {
+                let percent = count as f64 / total as f64 * 100.0;
+                widget::text(format!("{filter:?}: {count} ({percent:.1}%)"))
+                    .size(12)
+                    .into()
+            });
+        let runs = filters.runs();
+        let mut sequence: Vec<_> = runs
+            .iter()
+            .take(MAX_FILTER_RUNS)
+            .map(|&(filter, count)| match count {
+                1 => format!("{filter:?}"),
+                _ => format!("{filter:?}×{count}"),
+            })
+            .collect();
+        if runs.len() > MAX_FILTER_RUNS {
+            sequence.push("…".into());
+        }
+        widget::Column::with_children(
+            std::iter::once(widget::text("Filters").size(14).into())
+                .chain(counts)
+                .chain(std::iter::once(
+                    widget::text(sequence.join(" ")).size(12).into(),
+                ))
+                .collect(),
+        )
+        .spacing(4)
+    });
+
+    let entries = metadata
+        .iter()
+        .map(|text| {
+            let label = locale.keyword_label(&text.keyword).unwrap_or(&text.keyword);
+            let entry = column![widget::text(label).size(14), widget::text(&text.text)];
+            text.warnings
+                .iter()
+                .fold(entry, |entry, warning| {
+                    entry.push(
+                        widget::text(format!("warning: {warning}"))
+                            .size(12)
+                            .style(WARNING),
+                    )
                 })
-                .width(Length::Fill)
-                .max_height(2),
-            bottom_bar,
-        ]
+                .spacing(4)
+                .into()
+        })
+        .collect::<Vec<_>>();
+
+    let texts: Element<'_, Message, Renderer<Theme>> = if entries.is_empty() {
+        widget::text("No text metadata").into()
+    } else {
+        widget::Column::with_children(entries).spacing(12).into()
+    };
+    let content = widget::Column::with_children(
+        statistics
+            .map(Into::into)
+            .into_iter()
+            .chain(filters.map(Into::into))
+            .chain(std::iter::once(texts))
+            .collect(),
+    )
+    .spacing(20);
+
+    widget::scrollable(widget::container(content).padding(10))
+        .width(250)
+        .height(Length::Fill)
         .into()
-    }
+}
 
-    fn theme(&self) -> Self::Theme {
-        Theme::Dark
+fn browser_panel(browser: &Browser) -> Element<'_, Message, Renderer<Theme>> {
+    let mut up = widget::button("Up").style(theme::Button::Secondary);
+    if browser.parent().is_some() {
+        up = up.on_press(Message::BrowserParent);
     }
+    let header = row![
+        widget::text(browser.dir().display().to_string())
+            .size(14)
+            .width(Length::Fill),
+        up,
+        widget::button("Close")
+            .style(theme::Button::Secondary)
+            .on_press(Message::CloseBrowser),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center);
+
+    let list: Element<'_, Message, Renderer<Theme>> = match browser.error() {
+        Some(error) => widget::text(format!("Couldn't list this folder: {error}")).into(),
+        None if browser.entries().is_empty() => widget::text("No folders or PNG files here").into(),
+        None => widget::scrollable(
+            Canvas::new(browser)
+                .width(Length::Fill)
+                .height(browser.entries().len() as f32 * browser::ROW_HEIGHT),
+        )
+        .height(Length::Fill)
+        .into(),
+    };
+    let preview = browser.preview().map(|handle| {
+        widget::container(widget::image(handle.clone()))
+            .center_x()
+            .width(Length::Fill)
+    });
+
+    widget::Column::with_children(
+        [header.into(), list]
+            .into_iter()
+            .chain(preview.map(Into::into))
+            .collect(),
+    )
+    .spacing(10)
+    .padding(10)
+    .width(260)
+    .height(Length::Fill)
+    .into()
 }
 
-enum Viewer {
-    Viewing {
-        data: Vec<u8>,
-        cache: Cache,
-    },
-    Loading {
-        load_recv: oneshot::Receiver<std::io::Result<Vec<u8>>>,
-    },
-    Empty {
-        emoji: char,
-    },
+fn next_pass() -> Command<Message> {
+    Command::perform(tokio::time::sleep(passes::PASS_DELAY), |_| {
+        Message::PassTick
+    })
 }
 
-impl Viewer {
-    fn load(&mut self) -> Command<Message> {
-        match native_dialog::FileDialog::new()
-            .set_title("Open PNG")
-            .show_open_single_file()
-        {
-            Ok(Some(path)) => {
-                tracing::debug!("Loading: {}", path.display());
-                let (load_send, load_recv) = oneshot::channel();
-                *self = Self::Loading { load_recv };
-                Command::perform(tokio::fs::read(path), |result| {
-                    let _ = load_send.send(result);
-                    Message::Loaded
-                })
-            }
+/// Steps through the passes of an interlaced image, or plays them again.
+fn passes_bar(passes: &Passes) -> Element<'_, Message, Renderer<Theme>> {
+    let current = passes.current();
+    let last = passes.len() - 1;
+    row![
+        widget::button("Play").on_press(Message::PlayPasses),
+        widget::button("Previous").on_press_maybe(current.checked_sub(1).map(Message::SeekPass)),
+        widget::slider(0..=last as u32, current as u32, |index| {
+            Message::SeekPass(index as usize)
+        })
+        .width(Length::Fill),
+        widget::button("Next")
+            .on_press_maybe((current < last).then_some(Message::SeekPass(current + 1))),
+        widget::text(format!(
+            "Pass {}/{} · {}",
+            current + 1,
+            passes.len(),
+            passes.describe()
+        )),
+    ]
+    .spacing(10)
+    .padding(10)
+    .align_items(Alignment::Center)
+    .into()
+}
 
-            Ok(None) => {
-                tracing::debug!("No file selected");
-                Command::none()
-            }
+/// Thumbnails of an animation's frames with a slider to scrub through them.
+fn timeline_bar(timeline: &Timeline) -> Element<'_, Message, Renderer<Theme>> {
+    let current = timeline.current();
+    let thumbnails = timeline.thumbnails().enumerate().map(|(index, handle)| {
+        widget::button(widget::image(handle.clone()))
+            .style(if index == current {
+                theme::Button::Primary
+            } else {
+                theme::Button::Text
+            })
+            .padding(2)
+            .on_press(Message::SeekFrame(index))
+            .into()
+    });
+    let last = timeline.len() - 1;
 
-            Err(error) => {
-                tracing::error!("from native_dialog::FileDialog: {error}");
-                Command::none()
-            }
+    column![
+        widget::scrollable(widget::Row::with_children(thumbnails.collect()).spacing(4))
+            .direction(widget::scrollable::Direction::Horizontal(
+                widget::scrollable::Properties::default()
+            ))
+            .width(Length::Fill),
+        row![
+            widget::slider(0..=last as u32, current as u32, |index| {
+                Message::SeekFrame(index as usize)
+            })
+            .width(Length::Fill),
+            widget::text(format!(
+                "Frame {}/{} · {} ms",
+                current + 1,
+                timeline.len(),
+                timeline.delay().as_millis()
+            )),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center),
+    ]
+    .spacing(6)
+    .padding(10)
+    .into()
+}
+
+fn gallery_view(gallery: &Gallery) -> Element<'_, Message, Renderer<Theme>> {
+    let cell_width = gallery::THUMBNAIL_SIDE as f32 + 20.0;
+    let rows = gallery.paths().chunks(gallery::COLUMNS).map(|paths| {
+        let cells = paths.iter().map(|path| {
+            let picture: Element<'_, Message, Renderer<Theme>> = match gallery.cell(path) {
+                gallery::Cell::Thumbnail(handle) => widget::image(handle.clone()).into(),
+                gallery::Cell::Decoding => widget::text("…").into(),
+                gallery::Cell::Failed => widget::text("Can't decode").size(12).into(),
+            };
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            widget::button(
+                column![
+                    widget::container(picture)
+                        .center_x()
+                        .center_y()
+                        .width(Length::Fill)
+                        .height(gallery::THUMBNAIL_SIDE as f32),
+                    widget::text(name).size(12),
+                ]
+                .spacing(4)
+                .align_items(Alignment::Center),
+            )
+            .style(theme::Button::Text)
+            .width(cell_width)
+            .on_press(Message::GalleryPick(path.clone()))
+            .into()
+        });
+        widget::Row::with_children(cells.collect())
+            .spacing(10)
+            .into()
+    });
+
+    let grid: Element<'_, Message, Renderer<Theme>> = if gallery.paths().is_empty() {
+        widget::text(format!("No PNG files in {}", gallery.dir().display())).into()
+    } else {
+        widget::Column::with_children(rows.collect())
+            .spacing(10)
+            .into()
+    };
+    widget::scrollable(
+        widget::container(grid)
+            .padding(20)
+            .center_x()
+            .width(Length::Fill),
+    )
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+}
+
+/// Input handled wherever the cursor or focus is.
+///
+/// Alt+Left/Right and the back/forward mouse buttons move through the history. Arrow keys
+/// move through the browser, Enter or Right opens and Left goes up a folder. F shows or
+/// hides it. Tab and Shift+Tab move focus through the action buttons, and then Enter presses
+/// the focused one instead. Other keys go to the viewer.
+///
+/// Keys a text box captured are left alone, since every widget sees every key. Escape still
+/// closes whichever box is open.
+fn global_input(event: iced::Event, status: iced::event::Status) -> Option<Message> {
+    use keyboard::KeyCode;
+
+    if let iced::Event::Keyboard(keyboard::Event::KeyPressed {
+        key_code: KeyCode::Escape,
+        ..
+    }) = event
+    {
+        return Some(Message::Escape);
+    }
+    if status == iced::event::Status::Captured {
+        return None;
+    }
+    let (key_code, modifiers) = match event {
+        // winit numbers the side buttons differently on each platform
+        iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Other(button))) => {
+            return match button {
+                1 | 3 | 8 => Some(Message::HistoryBack),
+                2 | 4 | 9 => Some(Message::HistoryForward),
+                _ => None,
+            };
         }
+        iced::Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }) => (key_code, modifiers),
+        _ => return None,
+    };
+    match key_code {
+        KeyCode::Left if modifiers.alt() => Some(Message::HistoryBack),
+        KeyCode::Right if modifiers.alt() => Some(Message::HistoryForward),
+        KeyCode::F if modifiers.is_empty() => Some(Message::ToggleBrowser),
+        KeyCode::L if modifiers.command() => Some(Message::OpenUrl),
+        KeyCode::Tab if modifiers.shift() => Some(Message::FocusAction(-1)),
+        KeyCode::Tab => Some(Message::FocusAction(1)),
+        KeyCode::Up => Some(Message::BrowserMove(-1)),
+        KeyCode::Down => Some(Message::BrowserMove(1)),
+        KeyCode::Enter => Some(Message::Confirm),
+        KeyCode::Right => Some(Message::BrowserOpenSelected),
+        KeyCode::Left => Some(Message::BrowserParent),
+        _ => Some(Message::Key(key_code, modifiers)),
     }
+}
 
-    fn loaded(&mut self) -> Command<Message> {
-        match self {
-            Self::Loading { load_recv } => match load_recv.try_recv() {
-                Ok(Ok(data)) => {
-                    *self = Self::Viewing {
-                        data,
-                        cache: Cache::new(),
-                    };
-                }
-                Ok(Err(error)) => {
-                    tracing::error!("from tokio::fs::read: {error}");
-                }
-                Err(error) => {
-                    tracing::error!("from load_recv.try_recv: {error}");
-                }
-            },
-            _ => {
-                tracing::error!("Viewer::loaded called on non-Loading variant");
+/// The keys that do the same as an action button, to show alongside its name.
+fn shortcut(message: &Message) -> Option<&'static str> {
+    Some(match message {
+        Message::OpenUrl => "Ctrl+L",
+        Message::ToggleBrowser => "F",
+        Message::HistoryBack => "Alt+Left",
+        Message::HistoryForward => "Alt+Right",
+        Message::ToggleFilterOverlay => "Shift+F",
+        Message::ToggleInterlacePasses => "Shift+I",
+        Message::ToggleNight => "N",
+        Message::ToggleSmooth => "S",
+        Message::ToggleSlidePause => "P",
+        Message::CollectDiagnostics => "Ctrl+Shift+D",
+        Message::ClearSelection => "Escape",
+        _ => return None,
+    })
+}
+
+/// Asks for a PNG with the native dialog, blocking until it's answered.
+fn pick_png() -> Option<PathBuf> {
+    native_dialog::FileDialog::new()
+        .set_title("Open PNG")
+        .show_open_single_file()
+        .unwrap_or_else(|error| {
+            tracing::error!("from native_dialog::FileDialog: {error}");
+            None
+        })
+}
+
+fn check_for_update() -> Command<Message> {
+    Command::perform(update::check(), |result| {
+        Message::UpdateChecked(result.unwrap_or_else(|error| {
+            tracing::error!("from update::check: {error}");
+            None
+        }))
+    })
+}
+
+/// Asks where to save `data` with `dialog` and writes it there, blocking until the
+/// dialog is answered.
+fn save_with(dialog: native_dialog::FileDialog, data: &[u8]) {
+    match dialog.show_save_single_file() {
+        Ok(Some(path)) => {
+            tracing::debug!("Saving: {}", path.display());
+            if let Err(error) = std::fs::write(path, data) {
+                tracing::error!("from std::fs::write: {error}");
             }
         }
-        Command::none()
+
+        Ok(None) => tracing::debug!("No file selected"),
+
+        Err(error) => tracing::error!("from native_dialog::FileDialog: {error}"),
     }
 }
 
@@ -193,27 +3121,263 @@ impl Default for Viewer {
     }
 }
 
+/// What the left mouse button is doing on the canvas.
+#[derive(Debug, Default)]
+enum Drag {
+    #[default]
+    None,
+    /// Pressed at this point, but not moved far enough yet to be more than a click.
+    Pressed(Point),
+    Selecting(Point),
+    Minimap,
+}
+
+/// How far the cursor has to move while pressed before a click becomes a selection.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Tints each scanline by its filter type, with a legend in the corner.
+fn draw_filters(frame: &mut Frame, filters: &Filters, state: &parse::State) {
+    fn color(filter: FilterType) -> Color {
+        match filter {
+            FilterType::None => Color::from_rgb(0.6, 0.6, 0.6),
+            FilterType::Sub => Color::from_rgb(1.0, 0.2, 0.2),
+            FilterType::Up => Color::from_rgb(0.2, 0.9, 0.2),
+            FilterType::Average => Color::from_rgb(0.2, 0.5, 1.0),
+            FilterType::Paeth => Color::from_rgb(1.0, 0.85, 0.1),
+        }
+    }
+
+    let width = state.image_size().width;
+    let mut y = 0;
+    for (filter, count) in filters.runs() {
+        let rows = Rectangle::new(Point::new(0.0, y as f32), Size::new(width, count as f32));
+        let rows = state.rect_to_screen(rows);
+        frame.fill_rectangle(
+            rows.position(),
+            rows.size(),
+            Color {
+                a: 0.35,
+                ..color(filter)
+            },
+        );
+        y += count;
+    }
+
+    const ROW: f32 = 18.0;
+    let corner = Point::new(
+        10.0,
+        frame.height() - 10.0 - ROW * FilterType::ALL.len() as f32,
+    );
+    frame.fill_rectangle(
+        corner,
+        Size::new(90.0, ROW * FilterType::ALL.len() as f32),
+        Color::from_rgba(0.0, 0.0, 0.0, 0.6),
+    );
+    for (index, filter) in FilterType::ALL.into_iter().enumerate() {
+        let top = corner.y + index as f32 * ROW;
+        frame.fill_rectangle(
+            Point::new(corner.x + 4.0, top + 4.0),
+            Size::new(10.0, 10.0),
+            color(filter),
+        );
+        frame.fill_text(canvas::Text {
+            content: format!("{filter:?}"),
+            position: Point::new(corner.x + 20.0, top + 2.0),
+            color: Color::WHITE,
+            size: 13.0,
+            ..Default::default()
+        });
+    }
+}
+
 impl Program<Message> for Viewer {
-    type State = parse::State;
+    type State = Drag;
 
     fn draw(
         &self,
-        state: &Self::State,
+        _state: &Self::State,
         renderer: &Renderer<Theme>,
-        _theme: &Theme,
+        theme: &Theme,
         bounds: Rectangle,
         _cursor: mouse::Cursor,
     ) -> Vec<Geometry> {
         match self {
-            Self::Viewing { data, cache } => {
-                vec![cache.draw(renderer, bounds.size(), |frame| {
-                    if let Err(error) = parse::render(frame, data, state) {
-                        tracing::error!("from render::render: {error}");
+            Self::Viewing {
+                cache,
+                state,
+                selection,
+                highlight,
+                format,
+                show_badge,
+                show_filters,
+                filters,
+                timeline,
+                passes,
+                minimap,
+                pixels,
+                ..
+            } => {
+                // the filters belong to the default image, not to animation frames
+                let filters = filters
+                    .as_deref()
+                    .filter(|_| *show_filters && timeline.is_none());
+                let image = cache.draw(renderer, bounds.size(), |frame| match timeline {
+                    Some(timeline) => parse::render(frame, timeline.image(), state),
+                    None if let Some(passes) = passes => {
+                        parse::render(frame, passes.image(), state)
                     }
-                })]
+                    None => match &**pixels {
+                        Pixels::Eight(image) => parse::render(frame, image, state),
+                        Pixels::Sixteen(image) => parse::render(frame, image, state),
+                    },
+                });
+                let minimap = if state.overflows() {
+                    minimap.draw(renderer, bounds.size(), state)
+                } else {
+                    vec![]
+                };
+
+                if selection.is_none()
+                    && highlight.is_none()
+                    && !state.showing_original()
+                    && !show_badge
+                    && filters.is_none()
+                {
+                    return std::iter::once(image).chain(minimap).collect();
+                }
+
+                let mut frame = Frame::new(renderer, bounds.size());
+                if let Some(filters) = filters {
+                    draw_filters(&mut frame, filters, state);
+                }
+                if *show_badge {
+                    const PADDING: f32 = 6.0;
+                    let size = Size::new(format.len() as f32 * 9.0 + 2.0 * PADDING, 26.0);
+                    let corner = Point::new(bounds.width - size.width - 10.0, 10.0);
+                    frame.fill_rectangle(corner, size, Color::from_rgba(0.0, 0.0, 0.0, 0.6));
+                    frame.fill_text(canvas::Text {
+                        content: format.to_string(),
+                        position: Point::new(corner.x + PADDING, corner.y + 5.0),
+                        color: Color::WHITE,
+                        size: 14.0,
+                        font: iced::Font::MONOSPACE,
+                        ..Default::default()
+                    });
+                }
+                if state.showing_original() {
+                    frame.fill_text(canvas::Text {
+                        content: "Original".into(),
+                        position: Point::new(10.0, 10.0),
+                        color: Color::from_rgb(1.0, 0.9, 0.2),
+                        size: 16.0,
+                        ..Default::default()
+                    });
+                }
+                if let Some(region) = selection {
+                    let region = state.rect_to_screen(to_f32(*region));
+                    frame.stroke(
+                        &canvas::Path::rectangle(region.position(), region.size()),
+                        canvas::Stroke::default()
+                            .with_width(2.0)
+                            .with_color(Color::from_rgb(1.0, 0.2, 0.2)),
+                    );
+                }
+                if let Some(region) = highlight {
+                    let region = state.rect_to_screen(**region);
+                    let stroke = canvas::Stroke::default()
+                        .with_width(2.0)
+                        .with_color(Color::from_rgb(1.0, 0.9, 0.2));
+                    frame.stroke(
+                        &canvas::Path::rectangle(region.position(), region.size()),
+                        stroke.clone(),
+                    );
+                    // keeps single pixels visible at low zoom
+                    let radius = region.width.max(region.height) / 2.0 + 10.0;
+                    frame.stroke(&canvas::Path::circle(region.center(), radius), stroke);
+                }
+                [image, frame.into_geometry()]
+                    .into_iter()
+                    .chain(minimap)
+                    .collect()
+            }
+
+            Self::Decoding(session) => {
+                const RADIUS: f32 = 24.0;
+                let mut frame = Frame::new(renderer, bounds.size());
+                if let Some(preview) = session.preview() {
+                    // shown the way the finished image first will be
+                    let mut state = parse::State::new(Size::new(
+                        preview.width() as f32,
+                        preview.height() as f32,
+                    ));
+                    state.set_viewport(bounds.size());
+                    frame.with_save(|frame| parse::render(frame, preview, &state));
+                }
+                let center = Point::new(bounds.width * 0.5, bounds.height * 0.45);
+                let color = theme.palette().primary;
+
+                // one turn a second, redrawn whenever the progress is polled
+                let turn = session.started().elapsed().as_secs_f32().fract();
+                let start = turn * std::f32::consts::TAU;
+                frame.stroke(
+                    &canvas::Path::circle(center, RADIUS),
+                    canvas::Stroke::default()
+                        .with_width(4.0)
+                        .with_color(Color { a: 0.2, ..color }),
+                );
+                frame.stroke(
+                    &canvas::Path::new(|path| {
+                        path.arc(canvas::path::Arc {
+                            center,
+                            radius: RADIUS,
+                            start_angle: start,
+                            end_angle: start + std::f32::consts::FRAC_PI_2,
+                        })
+                    }),
+                    canvas::Stroke::default().with_width(4.0).with_color(color),
+                );
+
+                let name = session
+                    .path()
+                    .file_name()
+                    .unwrap_or(session.path().as_os_str())
+                    .to_string_lossy();
+                let content = match session.progress() {
+                    (_, 0) => format!("Loading {name}…"),
+                    (rows, height) => format!("Loading {name}… {}%", 100 * rows / height),
+                };
+                frame.fill_text(canvas::Text {
+                    content,
+                    position: Point::new(center.x, center.y + RADIUS + 20.0),
+                    color: theme.palette().text,
+                    size: 18.0,
+                    horizontal_alignment: alignment::Horizontal::Center,
+                    ..Default::default()
+                });
+                vec![frame.into_geometry()]
             }
 
-            Self::Loading { .. } => vec![],
+            Self::Error { message, .. } => {
+                let mut frame = Frame::new(renderer, bounds.size());
+                frame.translate(Vector::new(bounds.width * 0.5, bounds.height * 0.4));
+                frame.fill_text(canvas::Text {
+                    content: "⚠".into(),
+                    shaping: widget::text::Shaping::Advanced,
+                    size: 80.0,
+                    horizontal_alignment: alignment::Horizontal::Center,
+                    vertical_alignment: alignment::Vertical::Bottom,
+                    ..Default::default()
+                });
+                frame.fill_text(canvas::Text {
+                    content: message.clone(),
+                    position: Point::new(0.0, 20.0),
+                    color: Color::from_rgb(0.9, 0.3, 0.3),
+                    size: 20.0,
+                    horizontal_alignment: alignment::Horizontal::Center,
+                    ..Default::default()
+                });
+                vec![frame.into_geometry()]
+            }
 
             Self::Empty { emoji } => {
                 let mut frame = Frame::new(renderer, bounds.size());
@@ -232,44 +3396,75 @@ impl Program<Message> for Viewer {
 
     fn update(
         &self,
-        state: &mut Self::State,
+        drag: &mut Self::State,
         event: canvas::Event,
         bounds: Rectangle,
         cursor: mouse::Cursor,
     ) -> (canvas::event::Status, Option<Message>) {
-        let mut updated = false;
+        let message = match self {
+            Viewer::Viewing { state, .. } if state.viewport() != bounds.size() => {
+                Some(Message::Resized(bounds.size()))
+            }
+
+            Viewer::Viewing { state, minimap, .. } => match event {
+                canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                    if state.overflows()
+                        && cursor.is_over(
+                            minimap.area(bounds.size()) + Vector::new(bounds.x, bounds.y),
+                        ) =>
+                {
+                    *drag = Drag::Minimap;
+                    cursor
+                        .position_in(bounds)
+                        .map(|point| Message::MinimapSeek(minimap.to_image(bounds.size(), point)))
+                }
+                canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                    if let Some(point) = cursor.position_in(bounds) {
+                        *drag = Drag::Pressed(point);
+                    }
+                    None
+                }
+                canvas::Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                    let point = position - Vector::new(bounds.x, bounds.y);
+                    match *drag {
+                        Drag::Minimap => {
+                            Some(Message::MinimapSeek(minimap.to_image(bounds.size(), point)))
+                        }
+                        Drag::Pressed(from) if from.distance(point) >= DRAG_THRESHOLD => {
+                            *drag = Drag::Selecting(from);
+                            Some(Message::Select(from, point))
+                        }
+                        Drag::Selecting(from) => Some(Message::Select(from, point)),
+                        _ => Some(Message::Hover(cursor.position_in(bounds))),
+                    }
+                }
+                canvas::Event::Mouse(mouse::Event::CursorLeft) => Some(Message::Hover(None)),
+                canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                    match std::mem::take(drag) {
+                        Drag::Pressed(point) => Some(Message::ZoomToggle(Some(point))),
+                        _ => None,
+                    }
+                }
 
-        match self {
-            Viewer::Viewing { cache, .. } => match event {
                 canvas::Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
                     let (mouse::ScrollDelta::Lines { y, .. }
                     | mouse::ScrollDelta::Pixels { y, .. }) = delta;
                     use std::cmp::Ordering::*;
-                    updated = match y.partial_cmp(&0.0) {
-                        Some(Greater) => state.zoom_in(),
-                        Some(Less) => state.zoom_out(),
-                        Some(Equal) => false,
+                    match y.partial_cmp(&0.0) {
+                        Some(Greater) => Some(Message::ZoomIn(cursor.position_in(bounds))),
+                        Some(Less) => Some(Message::ZoomOut(cursor.position_in(bounds))),
+                        Some(Equal) => None,
                         None => panic!("invalid scroll value"),
-                    };
-                    if updated {
-                        cache.clear();
                     }
                 }
 
-                canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
-                    if cursor.is_over(bounds) =>
-                {
-                    state.zoom_toggle();
-                    cache.clear();
-                }
-
-                _ => {}
+                _ => None,
             },
-            _ => {}
-        }
+            _ => None,
+        };
 
-        if updated {
-            (canvas::event::Status::Captured, None)
+        if message.is_some() {
+            (canvas::event::Status::Captured, message)
         } else {
             (canvas::event::Status::Ignored, None)
         }
@@ -0,0 +1,108 @@
+use iced::{
+    widget::canvas::{self, Cache, Frame, Geometry},
+    Color, Point, Rectangle, Renderer, Size, Theme,
+};
+use png_viewer::parse::{self, image::Image};
+
+/// Longer side of the minimap on screen.
+const MAX_SIDE: f32 = 120.0;
+/// Longer side of the downscaled image it shows, in pixels.
+const THUMBNAIL_SIDE: usize = 60;
+/// Gap between the minimap and the edges of the viewport.
+const MARGIN: f32 = 10.0;
+
+/// An overview of the whole image, marking the part that's in the viewport.
+#[derive(Debug)]
+pub struct Minimap {
+    thumbnail: Image,
+    image_size: Size,
+    cache: Cache,
+}
+
+impl Minimap {
+    pub fn new(image: &Image) -> Self {
+        let step = image
+            .width()
+            .max(image.height())
+            .div_ceil(THUMBNAIL_SIDE)
+            .max(1);
+        Self {
+            thumbnail: image.resize(image.width().div_ceil(step), image.height().div_ceil(step)),
+            image_size: Size::new(image.width() as f32, image.height() as f32),
+            cache: Cache::new(),
+        }
+    }
+
+    /// Screen pixels per thumbnail pixel.
+    fn cell(&self) -> f32 {
+        MAX_SIDE / self.thumbnail.width().max(self.thumbnail.height()).max(1) as f32
+    }
+
+    /// Where the minimap goes in a viewport of `bounds`: its bottom right corner.
+    pub fn area(&self, bounds: Size) -> Rectangle {
+        let cell = self.cell();
+        let size = Size::new(
+            self.thumbnail.width() as f32 * cell,
+            self.thumbnail.height() as f32 * cell,
+        );
+        Rectangle::new(
+            Point::new(
+                bounds.width - size.width - MARGIN,
+                bounds.height - size.height - MARGIN,
+            ),
+            size,
+        )
+    }
+
+    /// The point of the image under `point` in the minimap, clamped to the image so a drag
+    /// can leave the minimap.
+    pub fn to_image(&self, bounds: Size, point: Point) -> Point {
+        let area = self.area(bounds);
+        Point::new(
+            ((point.x - area.x) / area.width).clamp(0.0, 1.0) * self.image_size.width,
+            ((point.y - area.y) / area.height).clamp(0.0, 1.0) * self.image_size.height,
+        )
+    }
+
+    pub fn draw(
+        &self,
+        renderer: &Renderer<Theme>,
+        bounds: Size,
+        state: &parse::State,
+    ) -> Vec<Geometry> {
+        let area = self.area(bounds);
+        let thumbnail = self.cache.draw(renderer, bounds, |frame| {
+            frame.fill_rectangle(area.position(), area.size(), Color::from_rgb(0.1, 0.1, 0.1));
+            let cell = self.cell();
+            for y in 0..self.thumbnail.height() {
+                for (x, &[r, g, b, a]) in self.thumbnail.row(y).iter().enumerate() {
+                    frame.fill_rectangle(
+                        Point::new(area.x + x as f32 * cell, area.y + y as f32 * cell),
+                        Size::new(cell, cell),
+                        Color::from_rgba8(r, g, b, f32::from(a) / 255.0),
+                    );
+                }
+            }
+        });
+
+        let mut frame = Frame::new(renderer, bounds);
+        let scale = area.width / self.image_size.width;
+        let visible = state.visible_region();
+        frame.stroke(
+            &canvas::Path::rectangle(area.position(), area.size()),
+            canvas::Stroke::default()
+                .with_width(1.0)
+                .with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.6)),
+        );
+        frame.stroke(
+            &canvas::Path::rectangle(
+                Point::new(area.x + visible.x * scale, area.y + visible.y * scale),
+                Size::new(visible.width * scale, visible.height * scale),
+            ),
+            canvas::Stroke::default()
+                .with_width(2.0)
+                .with_color(Color::from_rgb(1.0, 0.9, 0.2)),
+        );
+        vec![thumbnail, frame.into_geometry()]
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::{Color, Point, Rectangle};
+use png_viewer::parse::{Rotation, State, Zoom};
+
+const USAGE: &str =
+    "Usage: png-viewer [FILE|URL] [--zoom PERCENT] [--pos X,Y] [--rotate DEGREES] [--fit]
+                  [--lenient] [--fullscreen] [--slideshow DIR] [--interval SECONDS] [--shuffle]
+                  [--kiosk DIR] [--bg COLOR] [--night KELVIN] [--board FILE] [--no-cache]
+                  [--display-profile ICC] [--features]
+
+COLOR is #RRGGBB or one of black, white, gray.
+A URL starting with http:// or https:// is downloaded, showing the image as it arrives.
+--night KELVIN starts with a warm tint for late-night viewing (N toggles it).
+--interval SECONDS sets how long each slide shows (default 5); --shuffle randomizes the order.
+--kiosk DIR runs a fullscreen slideshow of DIR with every control disabled.
+--board FILE arranges images freely on one canvas, saving the layout to FILE.
+--no-cache never stores or reuses decoded pixels of slow-to-decode files.
+--display-profile ICC converts colors for a display with that profile instead of sRGB.
+--features lists the formats and chunks this build supports.";
+
+/// Command line options. The view options are applied once the image has loaded.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub path: Option<PathBuf>,
+    pub view: ViewOptions,
+    pub fullscreen: bool,
+    pub slideshow: Option<PathBuf>,
+    /// How long each slide shows, if not the default.
+    pub interval: Option<Duration>,
+    pub shuffle: bool,
+    pub background: Option<Color>,
+    pub lenient: bool,
+    /// Locked-down fullscreen slideshow with no controls.
+    pub kiosk: bool,
+    /// Color temperature for night mode, which starts enabled if set.
+    pub night: Option<f32>,
+    /// Session file of the board to open instead of a single image.
+    pub board: Option<PathBuf>,
+    /// Don't keep decoded pixels of slow files between runs.
+    pub no_cache: bool,
+    /// ICC profile of the display, if it isn't sRGB.
+    pub display_profile: Option<PathBuf>,
+    /// Print what this build supports and exit.
+    pub features: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ViewOptions {
+    zoom: Option<Zoom>,
+    pos: Option<Point>,
+    rotation: Option<Rotation>,
+    fit: bool,
+}
+
+impl Options {
+    pub fn from_args(mut args: impl Iterator<Item = OsString>) -> Result<Self, String> {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            // anything that isn't a flag is a path, whether or not it's valid Unicode
+            match arg.to_str() {
+                Some("--zoom") => {
+                    let percent = text(&arg, &mut args)?;
+                    let percent = parse_percent(&percent)
+                        .ok_or_else(|| format!("invalid zoom percentage: {percent}"))?;
+                    options.view.zoom = Some(Zoom::from_percent(percent));
+                }
+                Some("--pos") => {
+                    let pos = text(&arg, &mut args)?;
+                    let point = parse_point(&pos)
+                        .ok_or_else(|| format!("invalid position (expected X,Y): {pos}"))?;
+                    options.view.pos = Some(point);
+                }
+                Some("--rotate") => {
+                    let degrees = text(&arg, &mut args)?;
+                    let degrees = degrees
+                        .parse()
+                        .map_err(|_| format!("invalid rotation: {degrees}"))?;
+                    options.view.rotation = Some(Rotation::from_degrees(degrees));
+                }
+                Some("--fit") => options.view.fit = true,
+                Some("--lenient") => options.lenient = true,
+                Some("--night") => {
+                    let kelvin = text(&arg, &mut args)?;
+                    let kelvin = kelvin
+                        .trim_end_matches(['K', 'k'])
+                        .parse()
+                        .ok()
+                        .filter(|kelvin| (1000.0..=40000.0).contains(kelvin))
+                        .ok_or_else(|| format!("invalid color temperature: {kelvin}"))?;
+                    options.night = Some(kelvin);
+                }
+                Some("--board") => options.board = Some(value(&arg, &mut args)?.into()),
+                Some("--no-cache") => options.no_cache = true,
+                Some("--display-profile") => {
+                    options.display_profile = Some(value(&arg, &mut args)?.into());
+                }
+                Some("--features") => options.features = true,
+                Some("--interval") => {
+                    let seconds = text(&arg, &mut args)?;
+                    let interval = seconds
+                        .trim_end_matches('s')
+                        .parse()
+                        .ok()
+                        .filter(|seconds: &f32| seconds.is_finite() && *seconds >= 0.5)
+                        .ok_or_else(|| format!("invalid slide interval: {seconds}"))?;
+                    options.interval = Some(Duration::from_secs_f32(interval));
+                }
+                Some("--shuffle") => options.shuffle = true,
+                Some("--fullscreen") => options.fullscreen = true,
+                Some("--slideshow") => options.slideshow = Some(value(&arg, &mut args)?.into()),
+                Some("--kiosk") => {
+                    options.slideshow = Some(value(&arg, &mut args)?.into());
+                    options.fullscreen = true;
+                    options.kiosk = true;
+                }
+                Some("--bg") => {
+                    let color = text(&arg, &mut args)?;
+                    options.background =
+                        Some(parse_color(&color).ok_or_else(|| format!("invalid color: {color}"))?);
+                }
+                Some("--help" | "-h") => return Err(USAGE.into()),
+                Some(flag) if flag.starts_with("--") => {
+                    return Err(format!("unknown option: {flag}\n{USAGE}"));
+                }
+                _ => options.path = Some(arg.into()),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Where settings that outlast a run are kept.
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    Some(dir?.join("png-viewer"))
+}
+
+/// Parses `X,Y`.
+pub fn parse_point(input: &str) -> Option<Point> {
+    match parse_numbers(input)?.as_slice() {
+        &[x, y] => Some(Point::new(x, y)),
+        _ => None,
+    }
+}
+
+/// Parses `X,Y,WIDTH,HEIGHT`.
+pub fn parse_region(input: &str) -> Option<Rectangle> {
+    match parse_numbers(input)?.as_slice() {
+        &[x, y, width, height] if width > 0.0 && height > 0.0 => Some(Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => None,
+    }
+}
+
+/// Parses a positive percentage, with or without the `%`.
+pub fn parse_percent(input: &str) -> Option<f32> {
+    let percent: f32 = input.trim().trim_end_matches('%').trim_end().parse().ok()?;
+    (percent.is_finite() && percent > 0.0).then_some(percent)
+}
+
+/// Parses `#RRGGBB` or a few color names.
+pub fn parse_color(input: &str) -> Option<Color> {
+    match input.to_ascii_lowercase().as_str() {
+        "black" => Some(Color::BLACK),
+        "white" => Some(Color::WHITE),
+        "gray" | "grey" => Some(Color::from_rgb8(0x80, 0x80, 0x80)),
+        hex => {
+            let hex = hex.strip_prefix('#').unwrap_or(hex);
+            if hex.len() != 6 {
+                return None;
+            }
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+            Some(Color::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+        }
+    }
+}
+
+fn parse_numbers(input: &str) -> Option<Vec<f32>> {
+    input
+        .split(',')
+        .map(|number| number.trim().parse().ok())
+        .collect()
+}
+
+fn value(flag: &OsString, args: &mut impl Iterator<Item = OsString>) -> Result<OsString, String> {
+    args.next()
+        .ok_or_else(|| format!("{} requires a value\n{USAGE}", flag.to_string_lossy()))
+}
+
+/// A value that has to be valid Unicode, unlike paths.
+fn text(flag: &OsString, args: &mut impl Iterator<Item = OsString>) -> Result<String, String> {
+    value(flag, args)?
+        .into_string()
+        .map_err(|value| format!("invalid value: {}", value.to_string_lossy()))
+}
+
+impl ViewOptions {
+    pub fn apply(self, state: &mut State) {
+        if let Some(rotation) = self.rotation {
+            state.set_rotation(rotation);
+        }
+        if let Some(zoom) = self.zoom {
+            state.set_zoom(zoom);
+        }
+        if self.fit {
+            state.fit_image();
+        }
+        if let Some(pos) = self.pos {
+            // center of the pixel rather than its corner
+            state.center_on(Point::new(pos.x + 0.5, pos.y + 0.5));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! OpenRaster (`.ora`) export: a zip holding each layer as a PNG plus a `stack.xml`
+//! describing their order, so overlays stay editable in other programs.
+
+use crate::{
+    encode::encode,
+    parse::{error::Error, image::Image},
+};
+
+const MIMETYPE: &[u8] = b"image/openraster";
+
+/// Writes `base` as the bottom layer with `layers` stacked above it in order. Each layer
+/// must be the same size as the base.
+pub fn write_ora(base: &Image, layers: &[(&str, &Image)]) -> Result<Vec<u8>, Error> {
+    let mut merged = base.clone();
+    for (_, layer) in layers {
+        merged.blend(layer);
+    }
+
+    let mut stack = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<image version=\"0.0.3\" w=\"{}\" h=\"{}\">\n<stack>\n",
+        base.width(),
+        base.height()
+    );
+    // stack.xml lists the topmost layer first
+    for (i, (name, _)) in layers.iter().enumerate().rev() {
+        stack.push_str(&format!(
+            "<layer name=\"{}\" src=\"data/layer{i}.png\" x=\"0\" y=\"0\"/>\n",
+            escape(name)
+        ));
+    }
+    stack.push_str(
+        "<layer name=\"Image\" src=\"data/base.png\" x=\"0\" y=\"0\"/>\n</stack>\n</image>\n",
+    );
+
+    let mut zip = Zip::default();
+    // must come first and uncompressed so the file can be sniffed
+    zip.add("mimetype", MIMETYPE);
+    zip.add("stack.xml", stack.as_bytes());
+    zip.add("data/base.png", &encode(base)?);
+    for (i, (_, layer)) in layers.iter().enumerate() {
+        zip.add(&format!("data/layer{i}.png"), &encode(layer)?);
+    }
+    zip.add("mergedimage.png", &encode(&merged)?);
+    Ok(zip.finish())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Minimal zip writer storing entries uncompressed (PNGs are already deflated).
+#[derive(Default)]
+struct Zip {
+    out: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, data: &[u8]) {
+        let offset = self.out.len() as u32;
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+        // version, flags, method (stored), time, date, crc, sizes, name length, extra length
+        let fields = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&20u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0x21u16.to_le_bytes());
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        self.out.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        fields(&mut self.out);
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(data);
+
+        self.central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes());
+        fields(&mut self.central);
+        // comment length, disk, internal and external attributes, local header offset
+        self.central.extend_from_slice(&[0; 10]);
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let central_offset = self.out.len() as u32;
+        let central_size = self.central.len() as u32;
+        self.out.append(&mut self.central);
+        self.out.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&self.entries.to_le_bytes());
+        self.out.extend_from_slice(&self.entries.to_le_bytes());
+        self.out.extend_from_slice(&central_size.to_le_bytes());
+        self.out.extend_from_slice(&central_offset.to_le_bytes());
+        self.out.extend_from_slice(&0u16.to_le_bytes());
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::Render;
+
+    #[test]
+    fn mimetype_first() -> Result<(), Error> {
+        let base = Image::new(2, 2);
+        let mut overlay = Image::new(2, 2);
+        overlay.draw_pixel(0, 0, iced::Color::from_rgb(1.0, 0.0, 0.0));
+
+        let ora = write_ora(&base, &[("Notes", &overlay)])?;
+        assert_eq!(&ora[..4], b"PK\x03\x04");
+        assert_eq!(&ora[30..38], b"mimetype");
+        assert_eq!(&ora[38..38 + MIMETYPE.len()], MIMETYPE);
+        assert_eq!(&ora[ora.len() - 22..][..4], b"PK\x05\x06");
+        Ok(())
+    }
+}
@@ -1,13 +1,28 @@
 pub mod chunks;
+mod decoder;
 pub mod error;
+pub mod image;
 
-use std::io::Write;
+pub use decoder::{Decoder, Row};
+
+use std::{
+    io::Write,
+    sync::{mpsc, OnceLock},
+    time::{Duration, Instant},
+};
 
 use flate2::write::ZlibDecoder;
 use iced::widget::canvas;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-use chunks::{BitDepth, Chunk, ColorType, Colors, Interlace};
+use crate::color::{self, Transform};
+use chunks::{
+    BitDepth, Chunk, ChunkType, ColorType, Colors, Counts, Handlers, Interlace, Limits, Policy,
+    RawChunk,
+};
 use error::Error;
+use image::{Filter, Image, Image16, Raster, Sample};
 use nom::{
     bits::complete::take as take_bits,
     bytes::complete::{tag, take},
@@ -16,7 +31,7 @@ use nom::{
     IResult,
 };
 
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
 pub enum Zoom {
     #[default]
     X1,
@@ -26,6 +41,8 @@ pub enum Zoom {
     X3,
     X3p5,
     X4,
+    /// A factor between the fixed levels, e.g. from a typed percentage.
+    Custom(f32),
 }
 
 impl From<Zoom> for iced::Size {
@@ -38,6 +55,7 @@ impl From<Zoom> for iced::Size {
             Zoom::X3 => 3.0,
             Zoom::X3p5 => 3.5,
             Zoom::X4 => 4.0,
+            Zoom::Custom(factor) => factor,
         }; 2]
             .into()
     }
@@ -55,17 +73,495 @@ impl std::ops::Mul<Zoom> for iced::Point {
             Zoom::X3 => [self.x * 3.0, self.y * 3.0].into(),
             Zoom::X3p5 => [self.x * 3.5, self.y * 3.5].into(),
             Zoom::X4 => [self.x * 4.0, self.y * 4.0].into(),
+            Zoom::Custom(factor) => [self.x * factor, self.y * factor].into(),
+        }
+    }
+}
+
+impl Zoom {
+    const ALL: [Self; 7] = [
+        Self::X1,
+        Self::X1p5,
+        Self::X2,
+        Self::X2p5,
+        Self::X3,
+        Self::X3p5,
+        Self::X4,
+    ];
+
+    fn factor(self) -> f32 {
+        iced::Size::from(self).width
+    }
+
+    /// The zoom level closest to `percent`, e.g. 200 for `X2`.
+    pub fn from_percent(percent: f32) -> Self {
+        Self::ALL
+            .into_iter()
+            .min_by(|a, b| {
+                let distance = |zoom: &Self| (zoom.factor() * 100.0 - percent).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Exactly `percent`, clamped to the range of the fixed levels.
+    pub fn from_percent_exact(percent: f32) -> Self {
+        let factor = (percent / 100.0).clamp(Self::X1.factor(), Self::X4.factor());
+        Self::ALL
+            .into_iter()
+            .find(|zoom| zoom.factor() == factor)
+            .unwrap_or(Self::Custom(factor))
+    }
+
+    pub fn percent(self) -> f32 {
+        self.factor() * 100.0
+    }
+}
+
+/// Clockwise rotation applied when displaying the image.
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl Rotation {
+    /// Rounds `degrees` to the nearest quarter turn.
+    pub fn from_degrees(degrees: i32) -> Self {
+        match (degrees as f32 / 90.0).round() as i32 % 4 {
+            1 | -3 => Self::R90,
+            2 | -2 => Self::R180,
+            3 | -1 => Self::R270,
+            _ => Self::R0,
+        }
+    }
+
+    pub fn clockwise(self) -> Self {
+        match self {
+            Self::R0 => Self::R90,
+            Self::R90 => Self::R180,
+            Self::R180 => Self::R270,
+            Self::R270 => Self::R0,
         }
     }
 }
 
-#[derive(Default, Clone, Debug)]
+/// Fraction of the viewport kept visible when paging through an image.
+const PAGE_OVERLAP: f32 = 0.1;
+
+/// Fraction of the remaining distance covered per frame while smooth scrolling.
+const SCROLL_EASING: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jump {
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct State {
     zoom: Zoom,
+    rotation: Rotation,
+    image_size: iced::Size,
+    viewport: iced::Size,
+    offset: iced::Vector,
+    scroll_target: Option<f32>,
+    pending: Option<Pending>,
+    color_matrix: Option<ColorMatrix>,
+    show_original: bool,
+    /// Draw at half resolution, to keep up while the view is moving.
+    draft: bool,
+    /// Interpolate between pixels when zoomed, rather than drawing each as a block.
+    smooth: bool,
+    /// Flipped left to right, before the rotation.
+    mirrored: bool,
+}
+
+/// Maps linear RGB to RGB; rows are output channels.
+pub type ColorMatrix = [[f32; 3]; 3];
+
+/// Tints the way a light of `kelvin` color temperature would, relative to 6500 K
+/// daylight (which leaves colors unchanged).
+pub fn color_temperature(kelvin: f32) -> ColorMatrix {
+    // Tanner Helland's fit of the black body curve
+    let rgb = |kelvin: f32| {
+        let t = kelvin / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.69873 * (t - 60.0).powf(-0.13320476)
+        };
+        let green = if t <= 66.0 {
+            99.4708 * t.ln() - 161.11957
+        } else {
+            288.12216 * (t - 60.0).powf(-0.07551485)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.51773 * (t - 10.0).ln() - 305.0448
+        };
+        [red, green, blue].map(|channel| channel.clamp(0.0, 255.0))
+    };
+
+    let (tint, daylight) = (rgb(kelvin), rgb(6500.0));
+    let gain = |c: usize| (tint[c] / daylight[c]).min(1.0);
+    [
+        [gain(0), 0.0, 0.0],
+        [0.0, gain(1), 0.0],
+        [0.0, 0.0, gain(2)],
+    ]
+}
+
+/// Positioning requested before the viewport size was known.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pending {
+    Center(iced::Point),
+    Fit(iced::Rectangle),
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new(iced::Size::ZERO)
+    }
 }
 
 impl State {
-    pub fn zoom_in(&mut self) -> bool {
+    pub fn new(image_size: iced::Size) -> Self {
+        Self {
+            zoom: Zoom::default(),
+            rotation: Rotation::default(),
+            image_size,
+            viewport: iced::Size::ZERO,
+            offset: iced::Vector::default(),
+            scroll_target: None,
+            pending: None,
+            color_matrix: None,
+            show_original: false,
+            draft: false,
+            smooth: false,
+            mirrored: false,
+        }
+    }
+
+    /// Returns whether the quality changed, and so the image needs redrawing.
+    pub fn set_draft(&mut self, draft: bool) -> bool {
+        std::mem::replace(&mut self.draft, draft) != draft
+    }
+
+    pub fn smooth(&self) -> bool {
+        self.smooth
+    }
+
+    pub fn set_smooth(&mut self, smooth: bool) {
+        self.smooth = smooth;
+    }
+
+    /// Final color stage applied to every pixel at display time.
+    pub fn set_color_matrix(&mut self, color_matrix: Option<ColorMatrix>) {
+        self.color_matrix = color_matrix;
+    }
+
+    /// Switches between the adjusted image and the file as decoded, for comparison.
+    /// Adjustments are kept either way.
+    pub fn toggle_original(&mut self) {
+        self.show_original = !self.show_original;
+    }
+
+    pub fn showing_original(&self) -> bool {
+        self.show_original
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+        self.offset = iced::Vector::default();
+        self.scroll_target = None;
+    }
+
+    pub fn mirrored(&self) -> bool {
+        self.mirrored
+    }
+
+    pub fn set_mirrored(&mut self, mirrored: bool) {
+        self.mirrored = mirrored;
+    }
+
+    pub fn rotate_clockwise(&mut self) {
+        self.set_rotation(self.rotation.clockwise());
+    }
+
+    pub fn set_zoom(&mut self, zoom: Zoom) {
+        let previous = self.zoom;
+        self.zoom = zoom;
+        self.rescale(previous, None);
+    }
+
+    /// Size of the image as displayed, before zooming.
+    fn displayed_size(&self) -> iced::Size {
+        match self.rotation {
+            Rotation::R0 | Rotation::R180 => self.image_size,
+            Rotation::R90 | Rotation::R270 => {
+                iced::Size::new(self.image_size.height, self.image_size.width)
+            }
+        }
+    }
+
+    /// Maps a point in image coordinates to displayed coordinates, before zooming.
+    fn rotate(&self, point: iced::Point) -> iced::Point {
+        let iced::Size { width, height } = self.image_size;
+        let point = self.mirror(point);
+        match self.rotation {
+            Rotation::R0 => point,
+            Rotation::R90 => iced::Point::new(height - point.y, point.x),
+            Rotation::R180 => iced::Point::new(width - point.x, height - point.y),
+            Rotation::R270 => iced::Point::new(point.y, width - point.x),
+        }
+    }
+
+    fn unrotate(&self, point: iced::Point) -> iced::Point {
+        let iced::Size { width, height } = self.image_size;
+        self.mirror(match self.rotation {
+            Rotation::R0 => point,
+            Rotation::R90 => iced::Point::new(point.y, height - point.x),
+            Rotation::R180 => iced::Point::new(width - point.x, height - point.y),
+            Rotation::R270 => iced::Point::new(width - point.y, point.x),
+        })
+    }
+
+    /// Flips a point in image coordinates, if the image is shown mirrored. Its own inverse.
+    fn mirror(&self, point: iced::Point) -> iced::Point {
+        if self.mirrored {
+            iced::Point::new(self.image_size.width - point.x, point.y)
+        } else {
+            point
+        }
+    }
+
+    /// Scrolls so that `point` (in image coordinates) is in the middle of the viewport.
+    /// If the viewport isn't known yet, this happens as soon as it is.
+    pub fn center_on(&mut self, point: iced::Point) {
+        if self.viewport == iced::Size::ZERO {
+            self.pending = Some(Pending::Center(point));
+            return;
+        }
+        let center = self.rotate(point) * self.zoom;
+        self.offset = iced::Vector::new(
+            center.x - self.viewport.width / 2.0,
+            center.y - self.viewport.height / 2.0,
+        );
+        self.scroll_target = None;
+    }
+
+    pub fn offset(&self) -> iced::Vector {
+        self.offset
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.zoom.factor()
+    }
+
+    pub fn zoom(&self) -> Zoom {
+        self.zoom
+    }
+
+    /// Size of the image itself, before rotation.
+    pub fn image_size(&self) -> iced::Size {
+        self.image_size
+    }
+
+    pub fn viewport(&self) -> iced::Size {
+        self.viewport
+    }
+
+    /// The same view of another image, e.g. one being compared with this one.
+    pub fn with_image_size(&self, image_size: iced::Size) -> Self {
+        Self {
+            image_size,
+            ..self.clone()
+        }
+    }
+
+    pub fn set_viewport(&mut self, viewport: iced::Size) -> bool {
+        let resized = self.viewport != viewport;
+        self.viewport = viewport;
+        match self.pending.take() {
+            Some(Pending::Center(point)) => self.center_on(point),
+            Some(Pending::Fit(region)) => self.fit(region),
+            None => {}
+        }
+        resized
+    }
+
+    pub fn to_screen(&self, point: iced::Point) -> iced::Point {
+        self.rotate(point) * self.zoom - self.offset
+    }
+
+    pub fn to_image(&self, point: iced::Point) -> iced::Point {
+        let point = point + self.offset;
+        self.unrotate(iced::Point::new(
+            point.x / self.scale(),
+            point.y / self.scale(),
+        ))
+    }
+
+    fn rotate_rect(&self, rect: iced::Rectangle) -> iced::Rectangle {
+        let a = self.rotate(rect.position());
+        let b = self.rotate(iced::Point::new(rect.x + rect.width, rect.y + rect.height));
+        iced::Rectangle {
+            x: a.x.min(b.x),
+            y: a.y.min(b.y),
+            width: (a.x - b.x).abs(),
+            height: (a.y - b.y).abs(),
+        }
+    }
+
+    /// Maps a rectangle in image coordinates to the screen.
+    pub fn rect_to_screen(&self, rect: iced::Rectangle) -> iced::Rectangle {
+        let rect = self.rotate_rect(rect);
+        let scale = self.scale();
+        iced::Rectangle {
+            x: rect.x * scale - self.offset.x,
+            y: rect.y * scale - self.offset.y,
+            width: rect.width * scale,
+            height: rect.height * scale,
+        }
+    }
+
+    /// The whole pixels spanned by a drag from `from` to `to` on screen, clamped to the image.
+    pub fn screen_region(&self, from: iced::Point, to: iced::Point) -> iced::Rectangle<usize> {
+        let (a, b) = (self.to_image(from), self.to_image(to));
+        let clamp = |value: f32, max: f32| value.clamp(0.0, max) as usize;
+        let (width, height) = (self.image_size.width, self.image_size.height);
+        let x = clamp(a.x.min(b.x).floor(), width);
+        let y = clamp(a.y.min(b.y).floor(), height);
+        iced::Rectangle {
+            x,
+            y,
+            width: clamp(a.x.max(b.x).ceil(), width) - x,
+            height: clamp(a.y.max(b.y).ceil(), height) - y,
+        }
+    }
+
+    /// Whether part of the image is outside the viewport at the current zoom.
+    pub fn overflows(&self) -> bool {
+        let size = self.displayed_size();
+        size.width * self.scale() > self.viewport.width
+            || size.height * self.scale() > self.viewport.height
+    }
+
+    /// The part of the image inside the viewport, in image coordinates.
+    pub fn visible_region(&self) -> iced::Rectangle {
+        let a = self.to_image(iced::Point::ORIGIN);
+        let b = self.to_image(iced::Point::new(self.viewport.width, self.viewport.height));
+        let x = a.x.min(b.x).max(0.0);
+        let y = a.y.min(b.y).max(0.0);
+        iced::Rectangle {
+            x,
+            y,
+            width: (a.x.max(b.x).min(self.image_size.width) - x).max(0.0),
+            height: (a.y.max(b.y).min(self.image_size.height) - y).max(0.0),
+        }
+    }
+
+    pub fn is_scrolling(&self) -> bool {
+        self.scroll_target.is_some()
+    }
+
+    /// Picks the largest zoom at which `region` (in image coordinates) fits the viewport
+    /// and centers it. If the viewport isn't known yet, this happens as soon as it is.
+    pub fn fit(&mut self, region: iced::Rectangle) {
+        if self.viewport == iced::Size::ZERO {
+            self.pending = Some(Pending::Fit(region));
+            return;
+        }
+        let region = self.rotate_rect(region);
+        self.zoom = Zoom::ALL
+            .into_iter()
+            .rev()
+            .find(|zoom| {
+                region.width * zoom.factor() <= self.viewport.width
+                    && region.height * zoom.factor() <= self.viewport.height
+            })
+            .unwrap_or_default();
+        self.scroll_target = None;
+
+        let scale = self.zoom.factor();
+        self.offset = iced::Vector::new(
+            (region.x + region.width / 2.0) * scale - self.viewport.width / 2.0,
+            (region.y + region.height / 2.0) * scale - self.viewport.height / 2.0,
+        );
+    }
+
+    pub fn fit_image(&mut self) {
+        self.fit(iced::Rectangle::with_size(self.image_size));
+    }
+
+    fn max_scroll(&self) -> f32 {
+        (self.displayed_size().height * self.zoom.factor() - self.viewport.height).max(0.0)
+    }
+
+    pub fn jump(&mut self, jump: Jump) {
+        let page = self.viewport.height * (1.0 - PAGE_OVERLAP);
+        let from = self.scroll_target.unwrap_or(self.offset.y);
+        let max_scroll = self.max_scroll();
+
+        match jump {
+            Jump::PageDown => {
+                self.scroll_target = Some((from + page).min(max_scroll));
+            }
+            Jump::PageUp => {
+                self.scroll_target = Some((from - page).max(0.0));
+            }
+            Jump::Top => {
+                self.scroll_target = None;
+                self.offset.y = 0.0;
+            }
+            Jump::Bottom => {
+                self.scroll_target = None;
+                self.offset.y = max_scroll;
+            }
+        }
+    }
+
+    pub fn tick(&mut self) -> bool {
+        let Some(target) = self.scroll_target else {
+            return false;
+        };
+        let remaining = target - self.offset.y;
+        if remaining.abs() < 1.0 {
+            self.offset.y = target;
+            self.scroll_target = None;
+        } else {
+            self.offset.y += remaining * SCROLL_EASING;
+        }
+        true
+    }
+
+    /// Keeps the content under `anchor` (in viewport coordinates, the viewport's center by
+    /// default) where it is on screen after the zoom changed from `previous`.
+    fn rescale(&mut self, previous: Zoom, anchor: Option<iced::Point>) {
+        let ratio = self.zoom.factor() / previous.factor();
+        let anchor = anchor.map_or(
+            iced::Vector::new(self.viewport.width / 2.0, self.viewport.height / 2.0),
+            |anchor| iced::Vector::new(anchor.x, anchor.y),
+        );
+        self.offset = (self.offset + anchor) * ratio - anchor;
+        self.scroll_target = None;
+    }
+
+    pub fn zoom_in(&mut self, anchor: Option<iced::Point>) -> bool {
+        let previous = self.zoom;
         let mut zoomed = true;
         self.zoom = match self.zoom {
             Zoom::X1 => Zoom::X1p5,
@@ -78,11 +574,17 @@ impl State {
                 zoomed = false;
                 Zoom::X4
             }
+            Zoom::Custom(factor) => Zoom::ALL
+                .into_iter()
+                .find(|zoom| zoom.factor() > factor)
+                .unwrap_or(Zoom::X4),
         };
+        self.rescale(previous, anchor);
         zoomed
     }
 
-    pub fn zoom_out(&mut self) -> bool {
+    pub fn zoom_out(&mut self, anchor: Option<iced::Point>) -> bool {
+        let previous = self.zoom;
         let mut zoomed = true;
         self.zoom = match self.zoom {
             Zoom::X1 => {
@@ -95,279 +597,1609 @@ impl State {
             Zoom::X3 => Zoom::X2p5,
             Zoom::X3p5 => Zoom::X3,
             Zoom::X4 => Zoom::X3p5,
+            Zoom::Custom(factor) => Zoom::ALL
+                .into_iter()
+                .rev()
+                .find(|zoom| zoom.factor() < factor)
+                .unwrap_or(Zoom::X1),
         };
+        self.rescale(previous, anchor);
         zoomed
     }
 
-    pub fn zoom_toggle(&mut self) {
+    pub fn zoom_toggle(&mut self, anchor: Option<iced::Point>) {
+        let previous = self.zoom;
         self.zoom = match self.zoom {
-            Zoom::X1 | Zoom::X1p5 | Zoom::X2 | Zoom::X2p5 | Zoom::X3 | Zoom::X3p5 => Zoom::X4,
+            Zoom::X1
+            | Zoom::X1p5
+            | Zoom::X2
+            | Zoom::X2p5
+            | Zoom::X3
+            | Zoom::X3p5
+            | Zoom::Custom(_) => Zoom::X4,
             Zoom::X4 => Zoom::X1,
         };
+        self.rescale(previous, anchor);
     }
 }
 
-pub fn render(frame: &mut canvas::Frame, data: &[u8], state: &State) -> Result<(), Error> {
+pub fn dimensions(data: &[u8]) -> Result<iced::Size, Error> {
+    let (data, _) = header(data)?;
+    let (_, chunk) = chunks::chunk(data)?;
+
+    let Chunk::Ihdr { width, height, .. } = chunk else {
+        return Err(Error::MissingCritical("IHDR"));
+    };
+
+    Ok(iced::Size::new(width as f32, height as f32))
+}
+
+/// How the file stores its pixels, from its IHDR.
+pub fn color_info(data: &[u8]) -> Result<ColorInfo, Error> {
     let (data, _) = header(data)?;
-    let (data, chunk) = chunks::chunk(data)?;
+    let (_, chunk) = chunks::chunk(data)?;
 
     let Chunk::Ihdr {
-        width,
-        height,
         bit_depth,
         color_type,
         interlace,
+        ..
     } = chunk
     else {
         return Err(Error::MissingCritical("IHDR"));
     };
-
-    let mut decoder = ZlibDecoder::new(Renderer::new(
-        frame,
-        state,
-        width as usize,
-        height as usize,
+    Ok(ColorInfo {
         bit_depth,
         color_type,
         interlace,
-    )?);
+    })
+}
 
-    for chunk in &mut iterator(data, chunks::chunk) {
-        match chunk {
-            Chunk::Ihdr { .. } => {
-                return Err(Error::DuplicateIhdr);
+/// Short pixel format label such as `RGBA8`, `P4` or `G16`.
+pub fn format_label(data: &[u8]) -> Result<String, Error> {
+    let (data, _) = header(data)?;
+    let (_, chunk) = chunks::chunk(data)?;
+
+    let Chunk::Ihdr {
+        bit_depth,
+        color_type,
+        ..
+    } = chunk
+    else {
+        return Err(Error::MissingCritical("IHDR"));
+    };
+
+    let channels = match color_type {
+        ColorType::GrayScale => "G",
+        ColorType::GrayScaleAlpha => "GA",
+        ColorType::Rgb => "RGB",
+        ColorType::RgbAlpha => "RGBA",
+        ColorType::Palette => "P",
+    };
+    Ok(format!("{channels}{}", bit_depth as u8))
+}
+
+/// A straight-alpha color with channels from 0 to 1, at the precision of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Rgba {
+    pub fn to_rgba8(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a].map(|channel| (channel * 255.0).round() as u8)
+    }
+}
+
+impl From<iced::Color> for Rgba {
+    fn from(color: iced::Color) -> Self {
+        let iced::Color { r, g, b, a } = color;
+        Self { r, g, b, a }
+    }
+}
+
+impl From<Rgba> for iced::Color {
+    fn from(color: Rgba) -> Self {
+        let Rgba { r, g, b, a } = color;
+        Self { r, g, b, a }
+    }
+}
+
+/// How the file stores its pixels, for sinks that care about the source format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorInfo {
+    pub bit_depth: BitDepth,
+    pub color_type: ColorType,
+    pub interlace: Interlace,
+}
+
+/// Where decoded pixels go.
+///
+/// Implement [`draw_row`](Render::draw_row) to take whole rows, or
+/// [`draw_pixel`](Render::draw_pixel) to take them one pixel at a time.
+pub trait Render {
+    /// Bytes each decoded pixel of an image stored as `info` takes, counted against
+    /// [`Limits::max_decoded_bytes`].
+    fn bytes_per_pixel(_info: ColorInfo) -> u64 {
+        4
+    }
+
+    /// Called once, before any rows.
+    fn begin_image(&mut self, _width: usize, _height: usize, _info: ColorInfo) {}
+
+    /// Called with each scanline's filter byte and its unfiltered bytes, before its
+    /// pixels are drawn. `index` counts scanlines in the order they're stored, which for
+    /// an interlaced image runs through every pass and so past its height.
+    fn scanline(&mut self, _index: usize, _filter: u8, _data: &[u8]) {}
+
+    /// Whether the row's pixels are needed at all, so sinks that skip rows, like
+    /// thumbnails, don't pay for converting them.
+    fn wants_row(&self, _y: usize) -> bool {
+        true
+    }
+
+    /// Whether an interlaced image should be drawn after each of its passes, each pixel
+    /// decoded so far filling the block it stands for, rather than only once it's whole.
+    fn wants_passes(&self) -> bool {
+        false
+    }
+
+    /// Called after each pass of an interlaced image, once it's been drawn if wanted.
+    fn end_pass(&mut self, _pass: Pass) {}
+
+    /// Called with every pixel of a row, in order. Draws them one by one by default.
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        for (x, &color) in row.iter().enumerate() {
+            self.draw_pixel(x, y, color.into());
+        }
+    }
+
+    fn draw_pixel(&mut self, _x: usize, _y: usize, _color: iced::Color) {}
+
+    /// Called once the image has decoded successfully.
+    fn finish(&mut self) {}
+}
+
+/// Wraps a sink, reporting how many rows' worth of scanlines have been read to `on_row`.
+pub struct Progress<R, F> {
+    pub inner: R,
+    on_row: F,
+    /// The image height and its scanlines across every pass.
+    height: usize,
+    scanlines: usize,
+}
+
+impl<R: Render, F: FnMut(usize)> Progress<R, F> {
+    pub fn new(inner: R, on_row: F) -> Self {
+        Self {
+            inner,
+            on_row,
+            height: 1,
+            scanlines: 1,
+        }
+    }
+}
+
+impl<R: Render, F: FnMut(usize)> Render for Progress<R, F> {
+    fn bytes_per_pixel(info: ColorInfo) -> u64 {
+        R::bytes_per_pixel(info)
+    }
+
+    fn begin_image(&mut self, width: usize, height: usize, info: ColorInfo) {
+        self.height = height;
+        self.scanlines = Pass::stored(width, height, info.interlace)
+            .iter()
+            .map(|pass| pass.height(height))
+            .sum::<usize>()
+            .max(1);
+        self.inner.begin_image(width, height, info);
+    }
+
+    fn scanline(&mut self, index: usize, filter: u8, data: &[u8]) {
+        self.inner.scanline(index, filter, data);
+        (self.on_row)((index + 1) * self.height / self.scanlines);
+    }
+
+    fn wants_row(&self, y: usize) -> bool {
+        self.inner.wants_row(y)
+    }
+
+    fn wants_passes(&self) -> bool {
+        self.inner.wants_passes()
+    }
+
+    fn end_pass(&mut self, pass: Pass) {
+        self.inner.end_pass(pass);
+    }
+
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        self.inner.draw_row(y, row);
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: iced::Color) {
+        self.inner.draw_pixel(x, y, color);
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+    }
+}
+
+/// 4x4 ordered dither thresholds, in sixteenths.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+struct Canvas<'frame, 'state> {
+    frame: &'frame mut canvas::Frame,
+    state: &'state State,
+    /// Whether the source has more precision than the display, so rounding it would band.
+    dither: bool,
+}
+
+impl Canvas<'_, '_> {
+    /// Draws `image` resampled to the screen, only as far as the viewport shows it.
+    fn draw_smooth<S: Sample>(&mut self, image: &Raster<S>) {
+        /// Drawn cells per image pixel at most, which bounds the work when zoomed far in.
+        const MAX_CELLS: f32 = 4.0;
+
+        // a pixel of margin, so neighbors off screen still blend into the edges
+        let visible = self.state.visible_region();
+        let x = (visible.x.floor() as usize).saturating_sub(1);
+        let y = (visible.y.floor() as usize).saturating_sub(1);
+        let right = ((visible.x + visible.width).ceil() as usize + 1).min(image.width());
+        let bottom = ((visible.y + visible.height).ceil() as usize + 1).min(image.height());
+        if right <= x || bottom <= y {
+            return;
+        }
+        let region = iced::Rectangle {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        };
+
+        let cells = self.state.scale().min(MAX_CELLS);
+        let width = (region.width as f32 * cells).round().max(1.0) as usize;
+        let height = (region.height as f32 * cells).round().max(1.0) as usize;
+        let scaled = image.crop(region).resample(width, height, Filter::Bilinear);
+        let cell = iced::Size::new(
+            region.width as f32 / width as f32,
+            region.height as f32 / height as f32,
+        );
+        let max = S::MAX as f32;
+        let scale = self.state.scale();
+        for row in 0..height {
+            for (column, pixel) in scaled.row(row).iter().enumerate() {
+                let [r, g, b, a] = pixel.map(|channel| channel.into() as f32 / max);
+                let color = self.tint(column, row, iced::Color { r, g, b, a });
+                let rect = self.state.rotate_rect(iced::Rectangle::new(
+                    iced::Point::new(
+                        x as f32 + column as f32 * cell.width,
+                        y as f32 + row as f32 * cell.height,
+                    ),
+                    cell,
+                ));
+                self.frame.fill_rectangle(
+                    iced::Point::new(rect.x * scale, rect.y * scale),
+                    iced::Size::new(rect.width * scale, rect.height * scale),
+                    color,
+                );
             }
-            Chunk::Plte(colors) => {
-                decoder.get_mut().set_palette(colors);
+        }
+    }
+
+    /// Applies the color stage and any dither to the pixel at `x`, `y`.
+    fn tint(&self, x: usize, y: usize, mut color: iced::Color) -> iced::Color {
+        if let Some(matrix) = self
+            .state
+            .color_matrix
+            .filter(|_| !self.state.show_original)
+        {
+            let rgb = [color.r, color.g, color.b];
+            let [r, g, b] = matrix.map(|row| (0..3).map(|c| row[c] * rgb[c]).sum::<f32>());
+            (color.r, color.g, color.b) = (r.min(1.0), g.min(1.0), b.min(1.0));
+        }
+        if self.dither {
+            // up to half an 8-bit step either way
+            let offset = (BAYER[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+            for channel in [&mut color.r, &mut color.g, &mut color.b] {
+                *channel = (*channel + offset / 255.0).clamp(0.0, 1.0);
             }
-            Chunk::Idat(data) => {
-                decoder.write_all(data.into())?;
+        }
+        color
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: iced::Color) {
+        // in draft mode, each 2x2 block is drawn in the color of its top-left pixel
+        let block = if self.state.draft {
+            if !x.is_multiple_of(2) || !y.is_multiple_of(2) {
+                return;
             }
-            Chunk::Iend => {
-                return Ok(());
+            2.0
+        } else {
+            1.0
+        };
+        let color = self.tint(x, y, color);
+        let half = block / 2.0;
+        let size = iced::Size::from(self.state.zoom);
+        let center = self
+            .state
+            .rotate(iced::Point::new(x as f32 + half, y as f32 + half));
+        self.frame.fill_rectangle(
+            iced::Point::new(center.x - half, center.y - half) * self.state.zoom,
+            iced::Size::new(size.width * block, size.height * block),
+            color,
+        );
+    }
+}
+
+/// Collects text chunks up to `limits`; a warning on the last one notes any that were skipped.
+pub fn text_chunks(data: &[u8], limits: Limits) -> Result<Vec<chunks::Text>, Error> {
+    let (data, _) = header(data)?;
+    let mut counts = Counts::new(limits);
+    let mut texts: Vec<chunks::Text> = vec![];
+    let mut iter = iterator(data, chunks::raw_chunk);
+    for raw in &mut iter {
+        if !matches!(&raw.ty.upper(), b"TEXT" | b"ITXT") || !counts.admit(raw.ty) {
+            continue;
+        }
+        if let Chunk::Text(text) = chunks::parse_with(raw, &limits)? {
+            texts.push(text);
+        }
+    }
+    iter.finish()?;
+
+    if let Some(last) = texts.last_mut() {
+        last.warnings.extend(counts.warnings());
+    }
+    Ok(texts)
+}
+
+/// The file's gAMA value, if it has one.
+pub fn gamma(data: &[u8]) -> Result<Option<f32>, Error> {
+    let (data, _) = header(data)?;
+    let mut iter = iterator(data, chunks::raw_chunk);
+    let gama = (&mut iter).find(|raw| raw.ty.upper() == *b"GAMA");
+    iter.finish()?;
+    Ok(match gama.map(chunks::parse).transpose()? {
+        Some(Chunk::Gama(gamma)) => Some(gamma),
+        _ => None,
+    })
+}
+
+/// Draws already decoded pixels as the viewer shows them: zoomed, rotated and tinted.
+pub fn render<S: Sample>(frame: &mut canvas::Frame, image: &Raster<S>, state: &State) {
+    frame.translate(iced::Vector::new(-state.offset.x, -state.offset.y));
+    let mut canvas = Canvas {
+        frame,
+        state,
+        // more precision than the display has would band if rounded
+        dither: S::BITS > 8,
+    };
+    if state.smooth && !state.draft && state.scale() != 1.0 {
+        canvas.draw_smooth(image);
+        return;
+    }
+    let max = S::MAX as f32;
+    for y in 0..image.height() {
+        for (x, pixel) in image.row(y).iter().enumerate() {
+            let [r, g, b, a] = pixel.map(|channel| channel.into() as f32 / max);
+            canvas.draw_pixel(x, y, iced::Color { r, g, b, a });
+        }
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, Error> {
+    decode_with(data, &mut Handlers::default())
+}
+
+/// Decodes the image, passing the payload of any unknown chunk to its registered handler.
+pub fn decode_with(data: &[u8], handlers: &mut Handlers) -> Result<Image, Error> {
+    draw(data, Image::new, handlers)
+}
+
+/// Decodes at 16 bits per channel, so 16-bit files keep their full precision.
+pub fn decode16(data: &[u8]) -> Result<Image16, Error> {
+    decode16_with(data, &mut Handlers::default())
+}
+
+pub fn decode16_with(data: &[u8], handlers: &mut Handlers) -> Result<Image16, Error> {
+    draw(data, Image16::new, handlers)
+}
+
+/// Decodes the image into a custom sink, created once the dimensions are known.
+pub fn draw<R: Render>(
+    data: &[u8],
+    sink: impl FnOnce(usize, usize) -> R,
+    handlers: &mut Handlers,
+) -> Result<R, Error> {
+    let (data, _) = header(data)?;
+
+    let decode = |crc_errors: mpsc::Receiver<ChunkType>| {
+        let (rest, ihdr) = chunks::chunk(data)?;
+        let mut decoder =
+            ChunkDecoder::new(ihdr, |w, h, info| Drawer::new(sink, w, h, info), handlers)?;
+        let mut iter = iterator(rest, chunks::raw_chunk);
+        for raw in &mut iter {
+            #[cfg(not(feature = "parallel"))]
+            if !raw.crc_matches() {
+                decoder.tolerate(Error::CrcMismatch(raw.ty))?;
+            }
+            for ty in crc_errors.try_iter() {
+                decoder.tolerate(Error::CrcMismatch(ty))?;
+            }
+            if decoder.feed(raw)? {
+                return Ok(decoder.finish(crc_errors.iter())?.into_sink());
+            }
+        }
+
+        decoder.tolerate(Error::MissingCritical("IEND"))?;
+        if let Ok((rest, ())) = iter.finish() {
+            decoder.feed_truncated(rest);
+        }
+        Ok(decoder.finish(crc_errors.iter())?.into_sink())
+    };
+
+    // CRCs are independent per chunk, so they're verified alongside inflate
+    #[cfg(feature = "parallel")]
+    return std::thread::scope(|scope| {
+        let (crc_send, crc_errors) = mpsc::channel();
+        scope.spawn(move || {
+            for raw in &mut iterator(data, chunks::raw_chunk) {
+                if !raw.crc_matches() && crc_send.send(raw.ty).is_err() {
+                    break;
+                }
+            }
+        });
+        decode(crc_errors)
+    });
+    // without threads to spare, each chunk is checked as it's fed instead, and nothing
+    // ever arrives on the channel: dropping its sender lets `finish` stop waiting on it
+    #[cfg(not(feature = "parallel"))]
+    {
+        let (crc_send, crc_errors) = mpsc::channel();
+        drop(crc_send);
+        decode(crc_errors)
+    }
+}
+
+/// Decodes an image read incrementally, without holding the whole file in memory.
+pub fn draw_reader<R: Render>(
+    mut reader: impl std::io::Read,
+    sink: impl FnOnce(usize, usize) -> R,
+    handlers: &mut Handlers,
+) -> Result<R, Error> {
+    let mut stream = Stream::new(sink, handlers);
+    std::io::copy(&mut reader, &mut stream)?;
+    stream.finish()
+}
+
+/// A decoder fed through [`Write`] as bytes arrive, e.g. from a file or a socket.
+///
+/// Image data is inflated as it arrives, with each IDAT's CRC checked once its last byte
+/// is in. Other chunks are buffered until complete, then checked and decoded, so memory use
+/// is bounded by the largest of those rather than the whole file.
+pub struct Stream<'h, 'a, R: Render, F> {
+    buffer: Vec<u8>,
+    seen_header: bool,
+    start: Option<(F, &'h mut Handlers<'a>)>,
+    decoder: Option<ChunkDecoder<'h, 'a, Drawer<R>>>,
+    /// The IDAT chunk part way through arriving.
+    idat: Option<PartialIdat>,
+    done: bool,
+}
+
+/// How much of an IDAT chunk's payload is still to come, and the CRC of what already has.
+struct PartialIdat {
+    left: usize,
+    crc: crc32fast::Hasher,
+}
+
+impl<'h, 'a, R: Render, F: FnOnce(usize, usize) -> R> Stream<'h, 'a, R, F> {
+    pub fn new(sink: F, handlers: &'h mut Handlers<'a>) -> Self {
+        Self {
+            buffer: Vec::new(),
+            seen_header: false,
+            start: Some((sink, handlers)),
+            decoder: None,
+            idat: None,
+            done: false,
+        }
+    }
+
+    /// Finishes decoding once the input has run out.
+    pub fn finish(mut self) -> Result<R, Error> {
+        let Some(mut decoder) = self.decoder.take() else {
+            return Err(if self.seen_header {
+                Error::MissingCritical("IHDR")
+            } else {
+                header(&self.buffer)
+                    .err()
+                    .map(Into::into)
+                    .unwrap_or_default()
+            });
+        };
+        if !self.done {
+            decoder.tolerate(Error::MissingCritical("IEND"))?;
+            decoder.feed_truncated(&self.buffer);
+        }
+        Ok(decoder.finish([])?.into_sink())
+    }
+
+    fn process(&mut self) -> Result<(), Error> {
+        let mut input = self.buffer.as_slice();
+        if !self.seen_header {
+            if input.len() < 8 {
+                return Ok(());
+            }
+            (input, _) = header(input)?;
+            self.seen_header = true;
+        }
+
+        while !self.done {
+            if let (Some(idat), Some(decoder)) = (&mut self.idat, &mut self.decoder) {
+                if idat.left > 0 {
+                    if input.is_empty() {
+                        break;
+                    }
+                    let (data, rest) = input.split_at(idat.left.min(input.len()));
+                    input = rest;
+                    idat.left -= data.len();
+                    idat.crc.update(data);
+                    self.done = decoder.write_idat(data)?;
+                    continue;
+                }
+                let Some(crc) = input.get(..4) else {
+                    break;
+                };
+                input = &input[4..];
+                let computed = self.idat.take().expect("just matched").crc.finalize();
+                if computed != u32::from_be_bytes(crc.try_into().expect("took 4 bytes")) {
+                    decoder.tolerate(Error::CrcMismatch(ChunkType(*b"IDAT")))?;
+                }
+                continue;
+            }
+
+            let Some(length) = input.get(..4) else {
+                break;
+            };
+            let length = u32::from_be_bytes(length.try_into().expect("took 4 bytes"));
+            // image data is decoded as it comes, from just its length and type
+            if let (Some(decoder), Some(b"IDAT")) = (&mut self.decoder, input.get(4..8)) {
+                if length <= i32::MAX as u32 {
+                    // the checks and setup a whole chunk would get, minus its payload
+                    decoder.interpret(RawChunk {
+                        ty: ChunkType(*b"IDAT"),
+                        data: (&[][..]).into(),
+                        crc: 0,
+                    })?;
+                    let mut crc = crc32fast::Hasher::new();
+                    crc.update(b"IDAT");
+                    self.idat = Some(PartialIdat {
+                        left: length as usize,
+                        crc,
+                    });
+                    input = &input[8..];
+                    continue;
+                }
+            }
+            // anything else waits for the whole chunk: length, type, data and CRC
+            if length <= i32::MAX as u32 && input.len() < 12 + length as usize {
+                break;
+            }
+            let (rest, raw) = chunks::raw_chunk(input)?;
+            input = rest;
+
+            match &mut self.decoder {
+                Some(decoder) => {
+                    if !raw.crc_matches() {
+                        decoder.tolerate(Error::CrcMismatch(raw.ty))?;
+                    }
+                    self.done = decoder.feed(raw)?;
+                }
+                None => {
+                    let (sink, handlers) = self.start.take().expect("taken only to start decoding");
+                    let ihdr = chunks::parse(raw)?;
+                    let decoder = self.decoder.insert(ChunkDecoder::new(
+                        ihdr,
+                        |w, h, info| Drawer::new(sink, w, h, info),
+                        handlers,
+                    )?);
+                    if !raw.crc_matches() {
+                        decoder.tolerate(Error::CrcMismatch(raw.ty))?;
+                    }
+                }
+            }
+        }
+
+        let consumed = self.buffer.len() - input.len();
+        self.buffer.drain(..consumed);
+        Ok(())
+    }
+}
+
+impl<R: Render, F: FnOnce(usize, usize) -> R> Write for Stream<'_, '_, R, F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.done {
+            self.buffer.extend_from_slice(buf);
+            self.process().map_err(std::io::Error::other)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Interprets chunks one at a time, passing image data on to be split into rows.
+struct ChunkDecoder<'h, 'a, S: RowSink> {
+    decoder: ZlibDecoder<Scanlines<S>>,
+    handlers: &'h mut Handlers<'a>,
+    counts: Counts,
+    seen_idat: bool,
+    /// Some other chunk has followed the IDATs, so no more may come.
+    idat_ended: bool,
+    /// The color space declared so far, settled at the first IDAT.
+    color: color::Source,
+}
+
+/// What a chunk meant for the image data.
+enum Fed<'data> {
+    More,
+    Idat(&'data [u8]),
+    End,
+}
+
+impl<'h, 'a, S: RowSink> ChunkDecoder<'h, 'a, S> {
+    fn new(
+        ihdr: Chunk,
+        rows: impl FnOnce(usize, usize, ColorInfo) -> S,
+        handlers: &'h mut Handlers<'a>,
+    ) -> Result<Self, Error> {
+        let Chunk::Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlace,
+        } = ihdr
+        else {
+            return Err(Error::MissingCritical("IHDR"));
+        };
+        let info = ColorInfo {
+            bit_depth,
+            color_type,
+            interlace,
+        };
+        handlers
+            .limits
+            .check_dimensions(width, height, S::bytes_per_pixel(info))?;
+
+        Ok(Self {
+            decoder: ZlibDecoder::new(Scanlines::new(
+                rows(width as usize, height as usize, info),
+                width as usize,
+                height as usize,
+                bit_depth,
+                color_type,
+                interlace,
+            )?),
+            counts: Counts::new(handlers.limits),
+            handlers,
+            seen_idat: false,
+            idat_ended: false,
+            color: color::Source::default(),
+        })
+    }
+
+    /// Under the lenient policy, turns an error into a warning.
+    fn tolerate(&mut self, error: Error) -> Result<(), Error> {
+        if self.handlers.policy == Policy::Lenient {
+            self.handlers.warn(error.to_string());
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Handles the next chunk after IHDR, returning whether the image data has ended.
+    fn feed(&mut self, raw: RawChunk) -> Result<bool, Error> {
+        match self.interpret(raw)? {
+            Fed::More => Ok(false),
+            Fed::Idat(data) => self.write_idat(data),
+            Fed::End => Ok(true),
+        }
+    }
+
+    /// Handles everything about the next chunk except decoding its image data.
+    fn interpret<'data>(&mut self, raw: RawChunk<'data>) -> Result<Fed<'data>, Error> {
+        // skipped chunks still split the image data
+        if raw.ty.0 == *b"IDAT" {
+            if self.idat_ended {
+                self.tolerate(Error::SplitIdat)?;
+            }
+        } else if self.seen_idat {
+            self.idat_ended = true;
+        }
+        if !self.counts.admit(raw.ty) {
+            return Ok(Fed::More);
+        }
+        let ty = raw.ty;
+        let chunk = match chunks::parse_with(raw, &self.handlers.limits) {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                self.tolerate(error.into())?;
+                return Ok(Fed::More);
+            }
+        };
+        if self.seen_idat
+            && matches!(
+                chunk,
+                Chunk::Plte(_)
+                    | Chunk::Gama(_)
+                    | Chunk::Srgb(_)
+                    | Chunk::Chrm(_)
+                    | Chunk::Trns(_)
+                    | Chunk::Iccp { .. }
+            )
+        {
+            self.tolerate(Error::ChunkOrder(ty))?;
+        }
+
+        match chunk {
+            Chunk::Ihdr { .. } => {
+                return Err(Error::DuplicateIhdr);
+            }
+            Chunk::Plte(colors) => {
+                let format = self.format();
+                // an index can't reach entries past 2^depth
+                if format.color_type == ColorType::Palette
+                    && colors.len() > 1 << format.bits_per_pixel
+                {
+                    self.tolerate(Error::InvalidPaletteSize(colors.len() * 3))?;
+                }
+                self.decoder.get_mut().set_palette(colors);
+            }
+            Chunk::Idat(data) => {
+                if !self.seen_idat {
+                    let format = self.format();
+                    if format.color_type == ColorType::Palette && format.palette.is_none() {
+                        return Err(Error::MissingCritical("PLTE"));
+                    }
+                    if let Some(display) = self.handlers.display {
+                        let transform = Transform::new(
+                            &self.color.profile(self.handlers.limits.max_metadata_bytes),
+                            display,
+                        );
+                        self.decoder.get_mut().format.set_transform(transform);
+                    }
+                }
+                self.seen_idat = true;
+                return Ok(Fed::Idat(data.into()));
+            }
+            Chunk::Iend => {
+                for warning in self.counts.warnings() {
+                    tracing::warn!("{warning}");
+                }
+                return Ok(Fed::End);
+            }
+            Chunk::Gama(gamma) => self.color.gamma = Some(gamma),
+            Chunk::Srgb(_) => self.color.srgb = true,
+            Chunk::Chrm(chromaticities) => self.color.chromaticities = Some(chromaticities),
+            Chunk::Iccp { profile, .. } => self.color.icc = Some(<&[u8]>::from(profile).to_vec()),
+            Chunk::Text(_) => {}
+            Chunk::Trns(alpha) => {
+                let format = &mut self.decoder.get_mut().format;
+                if let Err(error) = format.set_transparency(alpha.into()) {
+                    self.tolerate(error)?;
+                }
+            }
+            Chunk::Unknown(ty, data) => {
+                self.handlers.handle(ty, data.into());
+            }
+        }
+        Ok(Fed::More)
+    }
+
+    /// Decodes some IDAT payload, returning whether the image data has ended early.
+    fn write_idat(&mut self, data: &[u8]) -> Result<bool, Error> {
+        if let Err(error) = self.decoder.write_all(data) {
+            // the rest of the image data can't be recovered
+            self.tolerate(error.into())?;
+            return Ok(true);
+        }
+        self.check_expansion()?;
+        Ok(false)
+    }
+
+    /// Stops a stream that inflates far beyond its compressed size, or beyond anything the
+    /// image could need. Never tolerated, since carrying on is what the limits prevent.
+    fn check_expansion(&self) -> Result<(), Error> {
+        /// Small streams can legitimately have any ratio.
+        const MIN_CHECKED: u64 = 1 << 20;
+
+        let limits = &self.handlers.limits;
+        let (inflated, compressed) = (self.decoder.total_out(), self.decoder.total_in());
+        let ratio = inflated / compressed.max(1);
+        if inflated >= MIN_CHECKED && ratio > limits.max_expansion {
+            return Err(Error::LimitExceeded(
+                "IDAT expansion ratio",
+                ratio,
+                limits.max_expansion,
+            ));
+        }
+        // 16-bit RGBA takes twice the four bytes a decoded pixel does, plus the filter bytes
+        let most = limits.max_decoded_bytes * 2 + self.decoder.get_ref().scanlines as u64;
+        if inflated > most {
+            return Err(Error::LimitExceeded("inflated image data", inflated, most));
+        }
+        Ok(())
+    }
+
+    /// Decodes what's left of a file cut off inside an IDAT chunk.
+    fn feed_truncated(&mut self, rest: &[u8]) {
+        if rest.get(4..8) == Some(b"IDAT") {
+            let _ = self.decoder.write_all(&rest[8..]);
+        }
+    }
+
+    /// Flushes the image data, keeping whatever rows were decoded if tolerating errors,
+    /// then collects any CRC mismatches still outstanding.
+    fn end(&mut self, crc_errors: impl IntoIterator<Item = ChunkType>) -> Result<(), Error> {
+        if let Err(error) = self.decoder.try_finish() {
+            self.tolerate(error.into())?;
+        }
+        if let Err(error) = self.decoder.get_mut().flush() {
+            self.tolerate(error.into())?;
+        }
+        for ty in crc_errors {
+            self.tolerate(Error::CrcMismatch(ty))?;
+        }
+        let scanlines = self.decoder.get_ref();
+        let (rows, total) = (scanlines.scanline, scanlines.scanlines);
+        if rows < total {
+            self.tolerate(Error::Truncated(rows, total))?;
+        }
+        Ok(())
+    }
+
+    fn format(&self) -> &PixelFormat {
+        &self.decoder.get_ref().format
+    }
+
+    fn rows_mut(&mut self) -> Option<&mut S> {
+        self.decoder.get_mut().rows.as_mut()
+    }
+
+    fn finish(mut self, crc_errors: impl IntoIterator<Item = ChunkType>) -> Result<S, Error> {
+        self.end(crc_errors)?;
+        self.decoder.get_mut().rows.take().ok_or(Error::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Sub),
+            2 => Ok(Self::Up),
+            3 => Ok(Self::Average),
+            4 => Ok(Self::Paeth),
+            _ => Err(Error::InvalidFilterType(value)),
+        }
+    }
+}
+
+impl FilterType {
+    pub const ALL: [Self; 5] = [Self::None, Self::Sub, Self::Up, Self::Average, Self::Paeth];
+}
+
+/// The filter type of every scanline, in the order they were decoded. As a sink it skips
+/// converting pixels, so recording them costs little more than inflating.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filters(Vec<FilterType>);
+
+impl Filters {
+    pub fn sequence(&self) -> &[FilterType] {
+        &self.0
+    }
+
+    /// Scanlines using each filter type, indexed by its value.
+    pub fn counts(&self) -> [usize; 5] {
+        let mut counts = [0; 5];
+        for &filter in &self.0 {
+            counts[filter as usize] += 1;
+        }
+        counts
+    }
+
+    /// The sequence as runs of the same filter type.
+    pub fn runs(&self) -> Vec<(FilterType, usize)> {
+        let mut runs: Vec<(FilterType, usize)> = vec![];
+        for &filter in &self.0 {
+            match runs.last_mut() {
+                Some((last, count)) if *last == filter => *count += 1,
+                _ => runs.push((filter, 1)),
+            }
+        }
+        runs
+    }
+}
+
+impl Render for Filters {
+    fn scanline(&mut self, _index: usize, filter: u8, _data: &[u8]) {
+        // the decoder has already rejected unknown filter types
+        if let Ok(filter) = FilterType::try_from(filter) {
+            self.0.push(filter);
+        }
+    }
+
+    fn wants_row(&self, _y: usize) -> bool {
+        false
+    }
+}
+
+/// Records which filter each scanline uses.
+pub fn filters(data: &[u8], handlers: &mut Handlers) -> Result<Filters, Error> {
+    draw(data, |_, _| Filters::default(), handlers)
+}
+
+/// Reverses the filter on `line`, a scanline starting with its filter byte, given the
+/// unfiltered line above (empty for the first row). The filter byte is zeroed.
+pub fn unfilter(line: &mut [u8], prev: &[u8], bytes_per_pixel: usize) -> Result<FilterType, Error> {
+    let (_, filter_type) = one_byte_as::<FilterType>(line)?;
+    line[0] = 0;
+
+    match filter_type {
+        FilterType::None => {}
+
+        FilterType::Sub => {
+            for i in 1..line.len() {
+                let prior = i.saturating_sub(bytes_per_pixel);
+                line[i] = line[i].wrapping_add(line[prior]);
+            }
+        }
+
+        FilterType::Up => {
+            if !prev.is_empty() {
+                for i in 1..line.len() {
+                    line[i] = line[i].wrapping_add(prev[i]);
+                }
+            }
+        }
+
+        FilterType::Average => {
+            for i in 1..line.len() {
+                let prior = i.saturating_sub(bytes_per_pixel);
+                let left = line[prior] as u16;
+                let up = *prev.get(i).unwrap_or(&0) as u16;
+                line[i] = line[i].wrapping_add(((left + up) / 2) as u8);
+            }
+        }
+
+        FilterType::Paeth => {
+            if prev.len() != line.len() {
+                // with no row above, the predictor always picks the left byte
+                for i in 1..line.len() {
+                    let prior = i.saturating_sub(bytes_per_pixel);
+                    line[i] = line[i].wrapping_add(line[prior]);
+                }
+            } else {
+                Paeth::fastest().unfilter(line, prev, bytes_per_pixel);
+            }
+        }
+    }
+
+    Ok(filter_type)
+}
+
+/// The two ways of computing the Paeth predictor. Which is quicker depends on the CPU and
+/// what the compiler makes of the branches, so decoding times both once and keeps the
+/// faster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paeth {
+    /// As the spec writes it: the estimate, its distance to each neighbour, then up to
+    /// three comparisons.
+    Spec,
+    /// See [`paeth`].
+    Branchless,
+}
+
+impl Paeth {
+    pub const ALL: [Self; 2] = [Self::Spec, Self::Branchless];
+
+    /// Reverses the Paeth filter on `line`, given the unfiltered row above it at the same
+    /// length. Index 0 holds the zeroed filter byte, so it doubles as the out-of-bounds
+    /// neighbour for the first pixel.
+    pub fn unfilter(self, line: &mut [u8], prev: &[u8], bytes_per_pixel: usize) {
+        match self {
+            Self::Spec => unfilter_paeth(line, prev, bytes_per_pixel, paeth_spec),
+            Self::Branchless => unfilter_paeth(line, prev, bytes_per_pixel, paeth),
+        }
+    }
+
+    /// The quicker formulation here, timed on a row of noise the first time it's asked for.
+    pub fn fastest() -> Self {
+        /// Long enough to swamp the timer's resolution, short enough not to hold up the
+        /// first decode.
+        const ROW: usize = 16 * 1024;
+        const ROUNDS: usize = 8;
+        static FASTEST: OnceLock<Paeth> = OnceLock::new();
+
+        *FASTEST.get_or_init(|| {
+            let mut seed = 0x2545_f491_u32;
+            let mut noise = || {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            };
+            let prev: Vec<u8> = (0..ROW).map(|_| noise()).collect();
+            let row: Vec<u8> = (0..ROW).map(|_| noise()).collect();
+
+            let mut best = [Duration::MAX; 2];
+            // interleaved, so neither gets the warmer cache throughout
+            for _ in 0..ROUNDS {
+                for (paeth, best) in Self::ALL.into_iter().zip(&mut best) {
+                    let mut line = row.clone();
+                    let started = Instant::now();
+                    paeth.unfilter(std::hint::black_box(&mut line), &prev, 4);
+                    *best = (*best).min(started.elapsed());
+                }
+            }
+            let fastest = if best[1] <= best[0] {
+                Self::Branchless
+            } else {
+                Self::Spec
+            };
+            tracing::debug!("Paeth timings {best:?}, using {fastest:?}");
+            fastest
+        })
+    }
+}
+
+#[inline(always)]
+fn unfilter_paeth(
+    line: &mut [u8],
+    prev: &[u8],
+    bytes_per_pixel: usize,
+    predict: impl Fn(u8, u8, u8) -> u8,
+) {
+    for i in 1..line.len() {
+        let prior = i.saturating_sub(bytes_per_pixel);
+        line[i] = line[i].wrapping_add(predict(line[prior], prev[i], prev[prior]));
+    }
+}
+
+/// Paeth predictor as the spec writes it.
+#[inline]
+fn paeth_spec(left: u8, up: u8, up_left: u8) -> u8 {
+    let (left, up, up_left) = (left as i16, up as i16, up_left as i16);
+    let p = left + up - up_left;
+    let (p_left, p_up, p_up_left) = (p.abs_diff(left), p.abs_diff(up), p.abs_diff(up_left));
+    (if p_left <= p_up && p_left <= p_up_left {
+        left
+    } else if p_up <= p_up_left {
+        up
+    } else {
+        up_left
+    }) as u8
+}
+
+/// Paeth predictor with the distances rewritten so that `p` is never materialized and
+/// the final choice compiles to conditional moves instead of branches.
+#[inline]
+pub(crate) fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let (a, b, c) = (left as i16, up as i16, up_left as i16);
+    let p_left = (b - c).abs();
+    let p_up = (a - c).abs();
+    let p_up_left = (a + b - 2 * c).abs();
+    let up_or_up_left = if p_up <= p_up_left { up } else { up_left };
+    if p_left <= p_up && p_left <= p_up_left {
+        left
+    } else {
+        up_or_up_left
+    }
+}
+
+fn one_byte_as<Into: TryFrom<u8, Error = Error>>(input: &[u8]) -> IResult<&[u8], Into, Error> {
+    map_res(map(take(1usize), |input: &[u8]| input[0]), |b| {
+        Into::try_from(b)
+    })(input)
+}
+
+pub fn header(input: &[u8]) -> IResult<&[u8], &[u8], Error> {
+    recognize(tuple((
+        tag(&[0x89]),
+        tag(b"PNG"),
+        tag(&[0x0D, 0x0A, 0x1A, 0x0A]),
+    )))(input)
+}
+
+/// Part of the image stored as its own run of scanlines: every `dx`th pixel from column
+/// `x` of every `dy`th row from row `y`. An image that isn't interlaced is one pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    pub x: usize,
+    pub y: usize,
+    pub dx: usize,
+    pub dy: usize,
+}
+
+impl Pass {
+    /// Every pixel, as stored without interlacing.
+    pub const FULL: Self = Self::new(0, 0, 1, 1);
+
+    /// The seven passes of Adam7 interlacing, coarse to fine.
+    pub const ADAM7: [Self; 7] = [
+        Self::new(0, 0, 8, 8),
+        Self::new(4, 0, 8, 8),
+        Self::new(0, 4, 4, 8),
+        Self::new(2, 0, 4, 4),
+        Self::new(0, 2, 2, 4),
+        Self::new(1, 0, 2, 2),
+        Self::new(0, 1, 1, 2),
+    ];
+
+    const fn new(x: usize, y: usize, dx: usize, dy: usize) -> Self {
+        Self { x, y, dx, dy }
+    }
+
+    /// The passes that hold any pixels of an image this size, in the order they're stored.
+    pub fn stored(width: usize, height: usize, interlace: Interlace) -> Vec<Self> {
+        match interlace {
+            Interlace::None => vec![Self::FULL],
+            Interlace::Adam7 => Self::ADAM7
+                .into_iter()
+                .filter(|pass| pass.width(width) > 0 && pass.height(height) > 0)
+                .collect(),
+        }
+    }
+
+    /// Pixels in each of the pass's rows.
+    pub fn width(&self, width: usize) -> usize {
+        width.saturating_sub(self.x).div_ceil(self.dx)
+    }
+
+    /// Rows in the pass.
+    pub fn height(&self, height: usize) -> usize {
+        height.saturating_sub(self.y).div_ceil(self.dy)
+    }
+
+    /// The width and height of the block each pixel decoded so far stands for, once this
+    /// pass and those before it are done.
+    pub fn block(&self) -> (usize, usize) {
+        let across = if self.x > 0 { self.x } else { self.dx };
+        let down = if self.y > 0 { self.y } else { self.dy };
+        (across, down)
+    }
+}
+
+/// Inflated image data is written here and split into scanlines, which are unfiltered and
+/// handed to `rows` in order.
+struct Scanlines<S> {
+    rows: Option<S>,
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    /// The passes still to come, last first.
+    passes: Vec<Pass>,
+    /// Rows of the current pass so far.
+    pass_row: usize,
+    /// Scanlines so far, across every pass, and how many the image holds.
+    scanline: usize,
+    scanlines: usize,
+    /// Length of the current pass's scanlines, with the filter byte.
+    scanline_len: usize,
+    next_scanline: Vec<u8>,
+    prev_scanline: Vec<u8>,
+}
+
+impl<S: RowSink> Scanlines<S> {
+    fn new(
+        rows: S,
+        width: usize,
+        height: usize,
+        bit_depth: BitDepth,
+        color_type: ColorType,
+        interlace: Interlace,
+    ) -> Result<Self, Error> {
+        tracing::debug!("width: {width} height: {height} bit_depth: {bit_depth:?}");
+        tracing::debug!("color_type: {color_type:?} interlace: {interlace:?}");
+        let mut passes = Pass::stored(width, height, interlace);
+        passes.reverse();
+        let mut scanlines = Self {
+            rows: Some(rows),
+            width,
+            height,
+            format: PixelFormat::new(bit_depth, color_type)?,
+            scanline: 0,
+            scanlines: passes.iter().map(|pass| pass.height(height)).sum(),
+            passes,
+            pass_row: 0,
+            scanline_len: 0,
+            next_scanline: Vec::new(),
+            prev_scanline: Vec::new(),
+        };
+        scanlines.start_pass();
+        Ok(scanlines)
+    }
+
+    /// Gets ready for the rows of the next pass, which are unfiltered without any before.
+    fn start_pass(&mut self) {
+        let Some(pass) = self.passes.last() else {
+            return;
+        };
+        self.scanline_len = (pass.width(self.width) * self.format.bits_per_pixel).div_ceil(8) + 1;
+        self.pass_row = 0;
+        self.prev_scanline.clear();
+        self.next_scanline.clear();
+        self.next_scanline.reserve(self.scanline_len);
+    }
+
+    fn set_palette(&mut self, colors: Colors) {
+        self.format.set_palette(colors);
+    }
+
+    fn filter(&mut self) -> Result<FilterType, Error> {
+        let bytes_per_pixel = self.format.bits_per_pixel.div_ceil(8);
+        unfilter(
+            &mut self.next_scanline,
+            &self.prev_scanline,
+            bytes_per_pixel,
+        )
+    }
+}
+
+/// Receives each scanline once it has been unfiltered.
+trait RowSink {
+    fn bytes_per_pixel(_info: ColorInfo) -> u64 {
+        4
+    }
+
+    /// `data` is the unfiltered scanline without its filter byte, holding the pixels of
+    /// `pass` in row `y` of the image.
+    fn row(
+        &mut self,
+        format: &PixelFormat,
+        y: usize,
+        pass: Pass,
+        filter: FilterType,
+        data: &[u8],
+    ) -> Result<(), Error>;
+
+    /// Called once every row of a pass has been given.
+    fn end_pass(&mut self, _format: &PixelFormat, _pass: Pass) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called when the image data ends.
+    fn flush(&mut self, _format: &PixelFormat) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Scanlines converted to pixels at a time, spread across the thread pool.
+const PARALLEL_ROWS: usize = 32;
+
+/// Converts rows to colors and draws them.
+struct Drawer<R> {
+    sink: R,
+    /// Rows waiting to be converted, and where they go.
+    rows: Vec<u8>,
+    ys: Vec<(usize, Pass)>,
+    row_len: usize,
+    width: usize,
+    /// Scanlines given to the sink so far.
+    scanlines: usize,
+    /// Where an interlaced image is pieced together, since no row is whole until the
+    /// last pass.
+    woven: Option<Woven>,
+}
+
+/// The pixels of an interlaced image decoded so far.
+struct Woven {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgba>,
+    /// The passes still to come, last first.
+    passes: Vec<Pass>,
+    /// The last pass finished.
+    done: Option<Pass>,
+}
+
+impl<R: Render> Drawer<R> {
+    /// Creates the sink and tells it what's coming.
+    fn new(
+        sink: impl FnOnce(usize, usize) -> R,
+        width: usize,
+        height: usize,
+        info: ColorInfo,
+    ) -> Self {
+        let mut sink = sink(width, height);
+        sink.begin_image(width, height, info);
+        let woven = (info.interlace == Interlace::Adam7).then(|| {
+            let mut passes = Pass::stored(width, height, info.interlace);
+            passes.reverse();
+            Woven {
+                width,
+                height,
+                pixels: vec![Rgba::default(); width * height],
+                passes,
+                done: None,
             }
-            Chunk::Gama(gamma) => {
-                decoder.get_mut().set_gamma(gamma);
+        });
+        Self {
+            sink,
+            rows: Vec::new(),
+            ys: Vec::new(),
+            row_len: 0,
+            width,
+            scanlines: 0,
+            woven,
+        }
+    }
+
+    fn into_sink(mut self) -> R {
+        self.sink.finish();
+        self.sink
+    }
+
+    /// Converts the buffered rows in parallel where threads are available, then draws them in order.
+    fn render_rows(&mut self, format: &PixelFormat) -> Result<(), Error> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        #[cfg(feature = "parallel")]
+        let rows = self.rows.par_chunks(self.row_len);
+        #[cfg(not(feature = "parallel"))]
+        let rows = self.rows.chunks(self.row_len);
+        // the buffered rows all come from one pass, so they share a width
+        let width = self.ys[0].1.width(self.width);
+        let rows = rows
+            .map(|row| format.colors(row, width))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut row = Vec::new();
+        for (&(y, pass), colors) in self.ys.iter().zip(rows) {
+            row.clear();
+            row.extend(colors.into_iter().map(Rgba::from));
+            match &mut self.woven {
+                Some(woven) => woven.weave(y, pass, &row),
+                None => self.sink.draw_row(y, &row),
             }
-            Chunk::Unknown => {}
         }
+        self.rows.clear();
+        self.ys.clear();
+        Ok(())
     }
 
-    Err(Error::MissingCritical("IEND"))
+    /// Draws every row of an interlaced image, with each pixel decoded so far filling
+    /// the block it stands for.
+    fn draw_woven(&mut self) {
+        let Some(woven) = &self.woven else {
+            return;
+        };
+        // before the first pass is done, only its pixels are known at all
+        let (across, down) = woven.done.unwrap_or(Pass::ADAM7[0]).block();
+        let mut row = Vec::with_capacity(woven.width);
+        for y in 0..woven.height {
+            if !self.sink.wants_row(y) {
+                continue;
+            }
+            let above = &woven.pixels[y / down * down * woven.width..][..woven.width];
+            row.clear();
+            row.extend((0..woven.width).map(|x| above[x / across * across]));
+            self.sink.draw_row(y, &row);
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-#[repr(u8)]
-enum FilterType {
-    None = 0,
-    Sub = 1,
-    Up = 2,
-    Average = 3,
-    Paeth = 4,
+impl Woven {
+    fn weave(&mut self, y: usize, pass: Pass, row: &[Rgba]) {
+        if y >= self.height {
+            return;
+        }
+        let pixels = &mut self.pixels[y * self.width..][..self.width];
+        for (x, &color) in (pass.x..self.width).step_by(pass.dx).zip(row) {
+            pixels[x] = color;
+        }
+    }
 }
 
-impl TryFrom<u8> for FilterType {
-    type Error = Error;
+impl<R: Render> RowSink for Drawer<R> {
+    fn bytes_per_pixel(info: ColorInfo) -> u64 {
+        R::bytes_per_pixel(info)
+    }
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            0 => Ok(Self::None),
-            1 => Ok(Self::Sub),
-            2 => Ok(Self::Up),
-            3 => Ok(Self::Average),
-            4 => Ok(Self::Paeth),
-            _ => Err(Error::InvalidFilterType(value)),
+    fn row(
+        &mut self,
+        format: &PixelFormat,
+        y: usize,
+        pass: Pass,
+        filter: FilterType,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.sink.scanline(self.scanlines, filter as u8, data);
+        self.scanlines += 1;
+        // drawing each pass fills skipped rows from the ones above
+        let needed = self.sink.wants_row(y) || self.woven.is_some() && self.sink.wants_passes();
+        if !needed {
+            return Ok(());
+        }
+        // the rows of each pass have their own length and width
+        if self.ys.last().is_some_and(|&(_, last)| last != pass) {
+            self.render_rows(format)?;
+        }
+        if self.rows.is_empty() {
+            self.row_len = data.len();
+            self.rows.reserve(PARALLEL_ROWS * data.len());
+        }
+        self.rows.extend_from_slice(data);
+        self.ys.push((y, pass));
+        if self.rows.len() >= PARALLEL_ROWS * self.row_len {
+            self.render_rows(format)?;
         }
+        Ok(())
     }
-}
 
-fn one_byte_as<Into: TryFrom<u8, Error = Error>>(input: &[u8]) -> IResult<&[u8], Into, Error> {
-    map_res(map(take(1usize), |input: &[u8]| input[0]), |b| {
-        Into::try_from(b)
-    })(input)
+    /// Draws an interlaced image once it's whole, or after every pass if the sink wants.
+    fn end_pass(&mut self, format: &PixelFormat, pass: Pass) -> Result<(), Error> {
+        let Some(woven) = &mut self.woven else {
+            return Ok(());
+        };
+        woven.passes.pop();
+        woven.done = Some(pass);
+        let last = woven.passes.is_empty();
+        if last || self.sink.wants_passes() {
+            self.render_rows(format)?;
+            self.draw_woven();
+        }
+        self.sink.end_pass(pass);
+        Ok(())
+    }
+
+    /// Draws any rows still waiting to be converted, and an interlaced image cut short
+    /// as far as it got.
+    fn flush(&mut self, format: &PixelFormat) -> Result<(), Error> {
+        self.render_rows(format)?;
+        if self
+            .woven
+            .as_ref()
+            .is_some_and(|woven| !woven.passes.is_empty())
+        {
+            self.draw_woven();
+        }
+        Ok(())
+    }
 }
 
-pub fn header(input: &[u8]) -> IResult<&[u8], &[u8], Error> {
-    recognize(tuple((
-        tag(&[0x89]),
-        tag(b"PNG"),
-        tag(&[0x0D, 0x0A, 0x1A, 0x0A]),
-    )))(input)
+fn palette_color(palette: &[iced::Color], index: u8) -> Result<iced::Color, Error> {
+    palette
+        .get(index as usize)
+        .copied()
+        .ok_or(Error::PaletteIndex(index, palette.len()))
 }
 
-struct Renderer<'frame, 'data, 'state> {
-    frame: Option<&'frame mut canvas::Frame>,
-    state: &'state State,
-    //dimensions: iced::Size,
+/// How a scanline's bytes map to colors.
+pub struct PixelFormat {
     color_type: ColorType,
     bits_per_pixel: usize,
-    //interlace: Interlace,
-    palette: Option<Colors<'data>>,
-    gamma: Option<f32>,
-    scanline: usize,
-    next_scanline: Vec<u8>,
-    prev_scanline: Vec<u8>,
+    palette: Option<Vec<iced::Color>>,
+    /// Gray level drawn fully transparent, from tRNS.
+    gray_key: Option<u16>,
+    /// Red, green and blue samples drawn fully transparent, from tRNS.
+    rgb_key: Option<[u16; 3]>,
+    /// Converts to the display's color space, unless the file is already in it.
+    transform: Option<Transform>,
 }
 
-impl<'frame, 'data, 'state> Renderer<'frame, 'data, 'state> {
-    fn new(
-        frame: &'frame mut canvas::Frame,
-        state: &'state State,
-        width: usize,
-        height: usize,
-        bit_depth: BitDepth,
-        color_type: ColorType,
-        interlace: Interlace,
-    ) -> Result<Self, Error> {
-        let bits_per_pixel = {
-            use BitDepth as BD;
-            use ColorType as CT;
-
-            match (bit_depth, color_type) {
-                (BD::One, CT::GrayScale | CT::Palette) => 1,
-                (BD::Two, CT::GrayScale | CT::Palette) => 2,
-                (BD::Four, CT::GrayScale | CT::Palette) => 4,
-                (BD::Eight, CT::GrayScale | CT::Palette) => 8,
-                (BD::Eight, CT::GrayScaleAlpha) | (BD::Sixteen, CT::GrayScale) => 16,
-                (BD::Eight, CT::Rgb) => 24,
-                (BD::Eight, CT::RgbAlpha) | (BD::Sixteen, CT::GrayScaleAlpha) => 32,
-                (BD::Sixteen, CT::Rgb) => 48,
-                (BD::Sixteen, CT::RgbAlpha) => 64,
-                _ => {
-                    return Err(Error::InvalidBitColorCombo(
-                        bit_depth as u8,
-                        color_type as u8,
-                    ))
-                }
-            }
-        };
-        let scanline_len = (width * bits_per_pixel).div_ceil(8) + 1;
-
-        tracing::debug!("width: {width} height: {height} bit_depth: {bit_depth:?}");
-        tracing::debug!("color_type: {color_type:?} interlace: {interlace:?}");
+impl PixelFormat {
+    pub fn new(bit_depth: BitDepth, color_type: ColorType) -> Result<Self, Error> {
         Ok(Self {
-            frame: Some(frame),
-            state,
-            //dimensions: iced::Size::new(width as f32, height as f32),
             color_type,
-            bits_per_pixel,
-            //interlace,
+            bits_per_pixel: chunks::bits_per_pixel(bit_depth, color_type)?,
             palette: None,
-            gamma: None,
-            scanline: 0,
-            next_scanline: Vec::with_capacity(scanline_len),
-            prev_scanline: Vec::with_capacity(scanline_len),
+            gray_key: None,
+            rgb_key: None,
+            transform: None,
         })
     }
 
-    fn set_palette(&mut self, colors: Colors<'data>) {
-        self.palette = Some(colors);
+    pub fn bits_per_pixel(&self) -> usize {
+        self.bits_per_pixel
     }
 
-    fn set_gamma(&mut self, gamma: f32) {
-        self.gamma = Some(gamma);
+    pub fn set_palette(&mut self, colors: Colors) {
+        self.palette = Some((0..colors.len()).map(|i| colors.get(i)).collect());
     }
 
-    fn filter(&mut self) -> Result<(), Error> {
-        let (_, filter_type) = one_byte_as::<FilterType>(&self.next_scanline)?;
-        let bytes_per_pixel = (self.bits_per_pixel + 7) / 8;
-        self.next_scanline[0] = 0;
-
-        match filter_type {
-            FilterType::None => {}
-
-            FilterType::Sub => {
-                for i in 1..self.next_scanline.len() {
-                    let prior = i.saturating_sub(bytes_per_pixel);
-                    self.next_scanline[i] =
-                        self.next_scanline[i].wrapping_add(self.next_scanline[prior]);
-                }
-            }
-
-            FilterType::Up => {
-                if !self.prev_scanline.is_empty() {
-                    for i in 1..self.next_scanline.len() {
-                        self.next_scanline[i] =
-                            self.next_scanline[i].wrapping_add(self.prev_scanline[i]);
-                    }
+    /// Applies a tRNS chunk, which for palette images gives the alpha of the first entries.
+    pub fn set_transparency(&mut self, data: &[u8]) -> Result<(), Error> {
+        match (self.color_type, &mut self.palette) {
+            (ColorType::Palette, Some(palette)) if data.len() <= palette.len() => {
+                for (color, &alpha) in palette.iter_mut().zip(data) {
+                    color.a = f32::from(alpha) / 255.0;
                 }
+                Ok(())
             }
-
-            FilterType::Average => {
-                for i in 1..self.next_scanline.len() {
-                    let prior = i.saturating_sub(bytes_per_pixel);
-                    let left = self.next_scanline[prior] as u16;
-                    let up = *self.prev_scanline.get(i).unwrap_or(&0) as u16;
-                    self.next_scanline[i] =
-                        self.next_scanline[i].wrapping_add(((left + up) / 2) as u8);
-                }
+            (ColorType::Palette, None) => Err(Error::MissingCritical("PLTE")),
+            (ColorType::GrayScale, _) if let &[high, low] = data => {
+                self.gray_key = Some(u16::from_be_bytes([high, low]));
+                Ok(())
             }
-
-            FilterType::Paeth => {
-                for i in 1..self.next_scanline.len() {
-                    let prior = i.saturating_sub(bytes_per_pixel);
-                    let left = self.next_scanline[prior] as i16;
-                    let up = *self.prev_scanline.get(i).unwrap_or(&0) as i16;
-                    let up_left = *self.prev_scanline.get(prior).unwrap_or(&0) as i16;
-                    let p = left + up - up_left;
-                    let (p_left, p_up, p_up_left) =
-                        (p.abs_diff(left), p.abs_diff(up), p.abs_diff(up_left));
-                    let paeth = if p_left <= p_up && p_left <= p_up_left {
-                        left
-                    } else if p_up <= p_up_left {
-                        up
-                    } else {
-                        up_left
-                    } as u8;
-                    self.next_scanline[i] = self.next_scanline[i].wrapping_add(paeth);
-                }
+            (ColorType::Rgb, _) if let &[r1, r2, g1, g2, b1, b2] = data => {
+                self.rgb_key = Some([
+                    u16::from_be_bytes([r1, r2]),
+                    u16::from_be_bytes([g1, g2]),
+                    u16::from_be_bytes([b1, b2]),
+                ]);
+                Ok(())
             }
+            _ => Err(Error::InvalidTransparency(data.len())),
         }
-
-        Ok(())
     }
 
-    fn draw_pixel(&self, frame: &mut canvas::Frame, x: usize, y: usize, color: iced::Color) {
-        // if let Some(gamma) = self.gamma {
-        //     color.r = color.r.powf(gamma);
-        //     color.g = color.g.powf(gamma);
-        //     color.b = color.b.powf(gamma);
-        // }
+    /// Makes `color` transparent if its raw gray `level` is the tRNS key.
+    fn key_gray(&self, level: u16, color: iced::Color) -> iced::Color {
+        if self.gray_key == Some(level) {
+            iced::Color { a: 0.0, ..color }
+        } else {
+            color
+        }
+    }
 
-        frame.fill_rectangle(
-            iced::Point::new(x as f32, y as f32) * self.state.zoom,
-            self.state.zoom.into(),
-            color,
-        );
+    /// Makes `color` transparent if its raw `samples` are the tRNS key.
+    fn key_rgb(&self, samples: [u16; 3], color: iced::Color) -> iced::Color {
+        if self.rgb_key == Some(samples) {
+            iced::Color { a: 0.0, ..color }
+        } else {
+            color
+        }
+    }
 
-        self.draw_pixel_test(color, x == 0);
+    pub fn set_transform(&mut self, transform: Option<Transform>) {
+        self.transform = transform;
     }
 
-    fn render(&mut self) -> Result<(), Error> {
-        let frame = self.frame.take().ok_or(Error::default())?;
+    /// Converts the first `width` pixels of one unfiltered scanline, without its filter
+    /// byte, to colors. Any bits after them only pad the scanline to a whole byte.
+    pub fn colors(&self, scanline: &[u8], width: usize) -> Result<Vec<iced::Color>, Error> {
+        let mut colors = Vec::with_capacity(width);
 
         let from_two_bytes =
             |bytes: &[u8]| u16::from_be_bytes(bytes.try_into().unwrap()) as f32 / u16::MAX as f32;
 
         if self.bits_per_pixel < 8 {
-            let input = (&self.next_scanline[1..], 0);
+            let input = (scanline, 0);
             let mut iter = iterator(input, take_bits::<_, u8, _, _>(self.bits_per_pixel));
 
             match self.color_type {
                 ColorType::GrayScale => {
-                    let max_grayscale = 2f32.powi(self.bits_per_pixel as i32);
-                    for (i, bits) in (&mut iter).enumerate() {
+                    let max_grayscale = 2f32.powi(self.bits_per_pixel as i32) - 1.0;
+                    for bits in (&mut iter).take(width) {
                         let grayscale = bits as f32 / max_grayscale;
                         let color = iced::Color::from_rgb(grayscale, grayscale, grayscale);
-                        self.draw_pixel(frame, i, self.scanline, color);
+                        colors.push(self.key_gray(bits.into(), color));
                     }
                 }
 
                 ColorType::Palette => {
                     if let Some(palette) = self.palette.as_ref() {
-                        for (i, bits) in (&mut iter).enumerate() {
-                            let color = palette.get(bits as usize);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                        for bits in (&mut iter).take(width) {
+                            colors.push(palette_color(palette, bits)?);
                         }
                     }
                 }
@@ -377,41 +2209,47 @@ impl<'frame, 'data, 'state> Renderer<'frame, 'data, 'state> {
 
             iter.finish()?;
         } else {
-            let input = &self.next_scanline[1..];
+            let input = scanline;
             let bytes_per_pixel = self.bits_per_pixel / 8;
             let mut iter = iterator(input, take(bytes_per_pixel));
 
             match self.color_type {
                 ColorType::GrayScale => {
-                    for (i, bytes) in (&mut iter).enumerate() {
-                        let grayscale = if bytes_per_pixel == 1 {
-                            bytes[0] as f32 / u8::MAX as f32
+                    for bytes in &mut iter {
+                        let (level, grayscale) = if bytes_per_pixel == 1 {
+                            (bytes[0].into(), bytes[0] as f32 / u8::MAX as f32)
                         } else {
-                            from_two_bytes(&bytes[..2])
+                            (
+                                u16::from_be_bytes([bytes[0], bytes[1]]),
+                                from_two_bytes(&bytes[..2]),
+                            )
                         };
                         let color = iced::Color::from_rgb(grayscale, grayscale, grayscale);
-                        self.draw_pixel(frame, i, self.scanline, color);
+                        colors.push(self.key_gray(level, color));
                     }
                 }
 
                 ColorType::Rgb => match bytes_per_pixel {
                     3 => {
-                        for (i, bytes) in (&mut iter).enumerate() {
+                        for bytes in &mut iter {
                             let &[red, green, blue] = bytes else {
                                 unreachable!("must be 3 bytes per pixel")
                             };
                             let color = iced::Color::from_rgb8(red, green, blue);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                            let samples = [red, green, blue].map(u16::from);
+                            colors.push(self.key_rgb(samples, color));
                         }
                     }
 
                     6 => {
-                        for (i, bytes) in (&mut iter).enumerate() {
+                        for bytes in &mut iter {
                             let red = from_two_bytes(&bytes[..2]);
                             let green = from_two_bytes(&bytes[2..4]);
                             let blue = from_two_bytes(&bytes[4..6]);
                             let color = iced::Color::from_rgb(red, green, blue);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                            let samples =
+                                [0, 2, 4].map(|i| u16::from_be_bytes([bytes[i], bytes[i + 1]]));
+                            colors.push(self.key_rgb(samples, color));
                         }
                     }
 
@@ -420,15 +2258,14 @@ impl<'frame, 'data, 'state> Renderer<'frame, 'data, 'state> {
 
                 ColorType::Palette => {
                     if let Some(palette) = self.palette.as_ref() {
-                        for (i, byte) in (&mut iter).enumerate() {
-                            let color = palette.get(byte[0] as usize);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                        for byte in &mut iter {
+                            colors.push(palette_color(palette, byte[0])?);
                         }
                     }
                 }
 
                 ColorType::GrayScaleAlpha => {
-                    for (i, bytes) in (&mut iter).enumerate() {
+                    for bytes in &mut iter {
                         let (grayscale, alpha) = if bytes_per_pixel == 2 {
                             (
                                 bytes[0] as f32 / u8::MAX as f32,
@@ -438,30 +2275,30 @@ impl<'frame, 'data, 'state> Renderer<'frame, 'data, 'state> {
                             (from_two_bytes(&bytes[..2]), from_two_bytes(&bytes[2..4]))
                         };
                         let color = iced::Color::from_rgba(grayscale, grayscale, grayscale, alpha);
-                        self.draw_pixel(frame, i, self.scanline, color);
+                        colors.push(color);
                     }
                 }
 
                 ColorType::RgbAlpha => match bytes_per_pixel {
                     4 => {
-                        for (i, bytes) in (&mut iter).enumerate() {
+                        for bytes in &mut iter {
                             let &[red, green, blue, alpha] = bytes else {
                                 unreachable!("must be 4 bytes per pixel")
                             };
                             let alpha = alpha as f32 / u8::MAX as f32;
                             let color = iced::Color::from_rgba8(red, green, blue, alpha);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                            colors.push(color);
                         }
                     }
 
                     8 => {
-                        for (i, bytes) in (&mut iter).enumerate() {
+                        for bytes in &mut iter {
                             let red = from_two_bytes(&bytes[..2]);
                             let green = from_two_bytes(&bytes[2..4]);
                             let blue = from_two_bytes(&bytes[4..6]);
                             let alpha = from_two_bytes(&bytes[6..8]);
                             let color = iced::Color::from_rgba(red, green, blue, alpha);
-                            self.draw_pixel(frame, i, self.scanline, color);
+                            colors.push(color);
                         }
                     }
 
@@ -472,59 +2309,59 @@ impl<'frame, 'data, 'state> Renderer<'frame, 'data, 'state> {
             iter.finish()?;
         }
 
-        self.frame = Some(frame);
-        Ok(())
+        if let Some(transform) = &self.transform {
+            for color in &mut colors {
+                *color = transform.apply(*color);
+            }
+        }
+        Ok(colors)
     }
 }
 
-impl Write for Renderer<'_, '_, '_> {
+impl<S: RowSink> Write for Scanlines<S> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let mut remainder = buf;
-        loop {
-            let scanline_spare_len = self.next_scanline.capacity() - self.next_scanline.len();
+        // anything past the last pass is ignored
+        while let Some(&pass) = self.passes.last() {
+            let scanline_spare_len = self.scanline_len - self.next_scanline.len();
             if remainder.len() < scanline_spare_len {
                 self.next_scanline.extend_from_slice(remainder);
-                break Ok(buf.len());
+                break;
             }
             self.next_scanline
                 .extend_from_slice(&remainder[..scanline_spare_len]);
             remainder = &remainder[scanline_spare_len..];
-            self.filter().map_err(std::io::Error::other)?;
-            self.render().map_err(std::io::Error::other)?;
+            let filter_type = self.filter().map_err(std::io::Error::other)?;
+            let y = pass.y + self.pass_row * pass.dy;
+            if let Some(rows) = self.rows.as_mut() {
+                rows.row(&self.format, y, pass, filter_type, &self.next_scanline[1..])
+                    .map_err(std::io::Error::other)?;
+            }
             std::mem::swap(&mut self.next_scanline, &mut self.prev_scanline);
             self.next_scanline.clear();
             self.scanline += 1;
+            self.pass_row += 1;
+
+            if self.pass_row == pass.height(self.height) {
+                if let Some(rows) = self.rows.as_mut() {
+                    rows.end_pass(&self.format, pass)
+                        .map_err(std::io::Error::other)?;
+                }
+                self.passes.pop();
+                self.start_pass();
+            }
         }
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
-    }
-}
-
-#[cfg(feature = "termcolor")]
-impl Renderer<'_, '_, '_> {
-    fn draw_pixel_test(&self, color: iced::Color, newline: bool) {
-        use termcolor::WriteColor;
-
-        let mut out = termcolor::StandardStream::stdout(termcolor::ColorChoice::Always);
-        let [r, g, b, _] = color.into_rgba8();
-        out.set_color(termcolor::ColorSpec::new().set_bg(Some(termcolor::Color::Rgb(r, g, b))))
-            .unwrap();
-
-        if newline {
-            out.write(b"\n").unwrap();
+        match self.rows.as_mut() {
+            Some(rows) => rows.flush(&self.format).map_err(std::io::Error::other),
+            None => Ok(()),
         }
-        out.write(b" ").unwrap();
-        out.flush().unwrap();
     }
 }
 
-#[cfg(not(feature = "termcolor"))]
-impl Renderer<'_, '_, '_> {
-    fn draw_pixel_test(&self, _color: iced::Color, _newline: bool) {}
-}
-
 #[cfg(test)]
 mod test {
     use super::chunks::*;
@@ -534,6 +2371,29 @@ mod test {
 
     const PNG: &[u8] = include_bytes!("../assets/xkcd.png");
 
+    /// A PNG with an IHDR for a plain image of the given size and format, then `chunks`
+    /// and IEND.
+    fn png<'a>(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color: u8,
+        chunks: impl IntoIterator<Item = (&'a [u8; 4], &'a [u8])>,
+    ) -> Vec<u8> {
+        let ihdr = [
+            &width.to_be_bytes()[..],
+            &height.to_be_bytes(),
+            &[depth, color, 0, 0, 0],
+        ]
+        .concat();
+        let mut png = crate::encode::write_png([(b"IHDR", ihdr.as_slice())]);
+        for (ty, data) in chunks {
+            crate::encode::write_chunk(&mut png, ty, data);
+        }
+        crate::encode::write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
     macro_rules! assert_bytes {
         ($left:expr, $right:expr $(,)?) => {
             assert_eq!(Bytes::from($left as &[u8]), Bytes::from($right as &[u8]),)
@@ -573,4 +2433,522 @@ mod test {
         assert_eq!(last_chunk, Some(Chunk::Iend));
         Ok(())
     }
+
+    #[test]
+    fn page_through_tall_image() {
+        let mut state = State::new(iced::Size::new(100.0, 250.0));
+        state.set_viewport(iced::Size::new(100.0, 100.0));
+
+        state.jump(Jump::PageDown);
+        while state.tick() {}
+        assert_eq!(state.offset().y, 90.0);
+
+        state.jump(Jump::PageDown);
+        state.jump(Jump::PageDown);
+        while state.tick() {}
+        assert_eq!(state.offset().y, 150.0);
+
+        state.jump(Jump::Top);
+        assert_eq!(state.offset().y, 0.0);
+        state.jump(Jump::Bottom);
+        assert_eq!(state.offset().y, 150.0);
+    }
+
+    #[test]
+    fn unknown_chunk_handler() -> Result<(), Box<dyn Error>> {
+        let mut phys = Vec::new();
+        decode_with(
+            PNG,
+            Handlers::default().on(b"pHYs", |data| phys = data.to_vec()),
+        )?;
+        assert_eq!(phys.len(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn handlers_skip_critical_chunks() -> Result<(), Box<dyn Error>> {
+        let (mut input, _) = header(PNG)?;
+        let (mut ancillary, mut critical) = (0, 0);
+        let mut handlers = Handlers::default();
+        handlers
+            .on(b"pHYs", |_| ancillary += 1)
+            .on(b"PRIV", |_| critical += 1);
+        while !input.is_empty() {
+            (input, _) = chunk_with(input, &mut handlers)?;
+        }
+        // an empty critical chunk this decoder has never heard of
+        chunk_with(b"\0\0\0\0PRIV\0\0\0\0", &mut handlers)?;
+        drop(handlers);
+        assert_eq!((ancillary, critical), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn crcs_match() -> Result<(), Box<dyn Error>> {
+        let (input, _) = header(PNG)?;
+        let mut iter = iterator(input, raw_chunk);
+        assert!((&mut iter).all(|raw| raw.crc_matches()));
+        iter.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn crc_mismatch_reported() {
+        let mut corrupt = PNG.to_vec();
+        // the last byte of the IEND CRC
+        *corrupt.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            decode(&corrupt),
+            Err(error::Error::CrcMismatch(ChunkType(ty))) if &ty == b"IEND"
+        ));
+
+        let mut handlers = Handlers::default();
+        handlers.policy(Policy::Lenient);
+        decode_with(&corrupt, &mut handlers).expect("lenient");
+        assert_eq!(handlers.warnings().len(), 1);
+    }
+
+    #[test]
+    fn stream_inflates_image_data_as_it_arrives() -> Result<(), Box<dyn Error>> {
+        // a single IDAT for the whole image
+        let png = crate::encode::encode(&decode(PNG)?)?;
+        let mut handlers = Handlers::default();
+        let mut stream = Stream::new(Image::new, &mut handlers);
+        for piece in png.chunks(1000) {
+            stream.write_all(piece)?;
+            assert!(stream.buffer.len() < 12);
+        }
+        assert_eq!(stream.finish()?, decode(PNG)?);
+
+        // the CRC is still checked, once the whole payload is in
+        let mut corrupt = png.clone();
+        let idat = png
+            .windows(4)
+            .position(|ty| ty == b"IDAT")
+            .expect("has IDAT");
+        let length = u32::from_be_bytes(png[idat - 4..idat].try_into()?) as usize;
+        corrupt[idat + 4 + length] ^= 1;
+        let mut handlers = Handlers::default();
+        let mut stream = Stream::new(Image::new, &mut handlers);
+        let error = stream.write_all(&corrupt).expect_err("bad CRC");
+        assert!(error.to_string().contains("CRC mismatch"));
+        Ok(())
+    }
+
+    #[test]
+    fn stream_matches_slice() -> Result<(), Box<dyn Error>> {
+        let mut handlers = Handlers::default();
+        let mut stream = Stream::new(Image::new, &mut handlers);
+        // small enough to split every chunk header
+        for piece in PNG.chunks(5) {
+            stream.write_all(piece)?;
+        }
+        assert_eq!(stream.finish()?, decode(PNG)?);
+        Ok(())
+    }
+
+    #[test]
+    fn pulled_rows_match_drawn() -> Result<(), Box<dyn Error>> {
+        let image = decode(PNG)?;
+        let mut handlers = Handlers::default();
+        let mut decoder = Decoder::new(PNG, &mut handlers)?;
+        assert_eq!(decoder.width(), image.width());
+
+        let mut rows = 0;
+        while let Some(row) = decoder.next_row()? {
+            assert_eq!(row.y, rows);
+            let colors: Vec<_> = row.colors()?.into_iter().map(|c| c.into_rgba8()).collect();
+            assert_eq!(colors[..image.width()], *image.row(row.y));
+            rows += 1;
+        }
+        assert_eq!(rows, image.height());
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_text_warns() -> Result<(), Box<dyn Error>> {
+        let mut data = Vec::new();
+        crate::encode::write_chunk(&mut data, b"tEXt", b"Comment\0caf\xe9\x01");
+        crate::encode::write_chunk(&mut data, b"iTXt", b"Title\0\0\0en\0\0\xff ok");
+        let mut iter = iterator(data.as_slice(), chunk);
+        let texts: Vec<_> = (&mut iter)
+            .filter_map(|chunk| match chunk {
+                Chunk::Text(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        iter.finish()?;
+
+        assert_eq!(texts[0].text, "caf\u{e9}\u{fffd}");
+        assert_eq!(texts[0].warnings.len(), 1);
+        assert_eq!(texts[1].language, "en");
+        assert_eq!(texts[1].text, "\u{fffd} ok");
+        assert_eq!(texts[1].warnings, ["text is not valid UTF-8"]);
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_text_capped() -> Result<(), Box<dyn Error>> {
+        // keyword, compression flag and method, empty language and translated keyword, then
+        // 64 MiB of text deflated to about 64 KB
+        let mut bomb =
+            flate2::write::ZlibEncoder::new(b"Bomb\0\x01\0\0\0".to_vec(), Default::default());
+        bomb.write_all(&vec![b'a'; 64 << 20])?;
+        let mut data = Vec::new();
+        crate::encode::write_chunk(&mut data, b"iTXt", &bomb.finish()?);
+        let (_, Chunk::Text(text)) = chunk(&data)? else {
+            panic!("not a text chunk");
+        };
+
+        let max = Limits::default().max_metadata_bytes;
+        assert_eq!(text.text.len() as u64, max);
+        assert_eq!(
+            text.warnings,
+            [format!("compressed text cut off after {max} bytes")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_limit_applies_to_text() -> Result<(), Box<dyn Error>> {
+        let (_, ihdr) = preceded(header, raw_chunk)(PNG)?;
+        let text = [
+            b"Comment\0\x01\0\0\0".as_slice(),
+            &crate::encode::compress(&[b'a'; 100], Default::default())?,
+        ]
+        .concat();
+        let png = crate::encode::write_png([
+            (&ihdr.ty.0, ihdr.data.into()),
+            (b"iTXt", text.as_slice()),
+            (b"IEND", &[]),
+        ]);
+
+        let limits = Limits {
+            max_metadata_bytes: 16,
+            ..Limits::default()
+        };
+        let texts = text_chunks(&png, limits)?;
+        assert_eq!(texts[0].text, "a".repeat(16));
+        Ok(())
+    }
+
+    #[test]
+    fn text_chunks_capped() -> Result<(), Box<dyn Error>> {
+        let (_, ihdr) = preceded(header, raw_chunk)(PNG)?;
+        let comment = b"Comment\0spam".as_slice();
+        let png = crate::encode::write_png(
+            std::iter::once((&ihdr.ty.0, ihdr.data.into()))
+                .chain(std::iter::repeat_n((b"tEXt", comment), 50))
+                .chain([(b"IEND", [].as_slice())]),
+        );
+
+        let limits = Limits {
+            text_chunks: 10,
+            ..Limits::default()
+        };
+        let texts = text_chunks(&png, limits)?;
+        assert_eq!(texts.len(), 10);
+        assert_eq!(
+            texts[9].warnings,
+            ["40 text chunks skipped after the first 10"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_decodes_truncated_file() {
+        // cut off partway through the image data, losing the CRC and IEND
+        let truncated = &PNG[..PNG.len() / 2];
+        assert!(decode(truncated).is_err());
+
+        let mut handlers = Handlers::default();
+        handlers.policy(Policy::Lenient);
+        let image = decode_with(truncated, &mut handlers).expect("lenient");
+        assert_eq!(image.width(), 293);
+        assert!(!handlers.warnings().is_empty());
+    }
+
+    #[test]
+    fn filters_recorded() -> Result<(), error::Error> {
+        let filters = filters(PNG, &mut Handlers::default())?;
+        assert_eq!(filters.sequence().len(), 165);
+        assert_eq!(filters.counts().iter().sum::<usize>(), 165);
+        let runs = filters.runs();
+        assert_eq!(runs.iter().map(|(_, count)| count).sum::<usize>(), 165);
+        assert!(runs.windows(2).all(|pair| pair[0].0 != pair[1].0));
+        Ok(())
+    }
+
+    #[test]
+    fn limits_stop_bombs() {
+        let png = png(1 << 20, 1 << 20, 8, 6, [(b"IDAT", &[][..])]);
+        assert!(matches!(
+            decode(&png),
+            Err(error::Error::LimitExceeded("width", ..))
+        ));
+
+        // a blank image squeezes down to almost nothing
+        let blank = crate::encode::encode(&Image::new(1024, 1024)).expect("encode");
+        assert!(decode(&blank).is_ok());
+        let mut handlers = Handlers::default();
+        handlers.limits(Limits {
+            max_expansion: 100,
+            ..Limits::default()
+        });
+        assert!(matches!(
+            decode_with(&blank, &mut handlers),
+            Err(error::Error::LimitExceeded("IDAT expansion ratio", _, 100))
+        ));
+
+        // 16-bit pixels take twice the room
+        handlers.limits(Limits {
+            max_decoded_bytes: 1024 * 1024 * 4,
+            ..Limits::default()
+        });
+        assert!(decode_with(&blank, &mut handlers).is_ok());
+        assert!(matches!(
+            decode16_with(&blank, &mut handlers),
+            Err(error::Error::LimitExceeded("decoded size", _, _))
+        ));
+    }
+
+    #[test]
+    fn split_idat_rejected() {
+        let idat =
+            crate::encode::compress(&[0, 10, 20, 0, 30, 40], Default::default()).expect("compress");
+        let (first, second) = idat.split_at(idat.len() / 2);
+        let png = png(
+            2,
+            2,
+            8,
+            0,
+            [
+                (b"IDAT", first),
+                (b"tEXt", b"Comment\0between"),
+                (b"IDAT", second),
+            ],
+        );
+
+        assert!(matches!(decode(&png), Err(error::Error::SplitIdat)));
+        let mut handlers = Handlers::default();
+        handlers.policy(Policy::Lenient);
+        let image = decode_with(&png, &mut handlers).expect("lenient");
+        assert_eq!(image.get(1, 1), [40, 40, 40, 255]);
+        assert_eq!(handlers.warnings().len(), 1);
+    }
+
+    #[test]
+    fn palette_checked() {
+        let idat =
+            crate::encode::compress(&[0, 0b0100_0000], Default::default()).expect("compress");
+        let png = |plte: &[u8]| {
+            let mut chunks = vec![];
+            if !plte.is_empty() {
+                chunks.push((b"PLTE", plte));
+            }
+            chunks.push((b"IDAT", idat.as_slice()));
+            png(2, 1, 1, 3, chunks)
+        };
+
+        assert!(matches!(
+            decode(&png(&[])),
+            Err(error::Error::MissingCritical("PLTE"))
+        ));
+        assert!(matches!(
+            decode(&png(&[0; 9])),
+            Err(error::Error::InvalidPaletteSize(9))
+        ));
+        assert!(matches!(
+            decode(&png(&[0; 3])),
+            Err(error::Error::PaletteIndex(1, 1))
+        ));
+        let image = decode(&png(&[0, 0, 0, 255, 0, 0])).expect("decode");
+        assert_eq!(image.get(1, 0), [255, 0, 0, 255]);
+        assert_eq!(image.get(0, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn palette_padding_ignored() {
+        // two pixels of index 0, then six filler bits that would be out of the palette
+        let idat =
+            crate::encode::compress(&[0, 0b0011_1111], Default::default()).expect("compress");
+        let png = png(2, 1, 1, 3, [(b"PLTE", &[0, 0, 255][..]), (b"IDAT", &idat)]);
+
+        let image = decode(&png).expect("decode");
+        assert_eq!(image.get(0, 0), [0, 0, 255, 255]);
+        assert_eq!(image.get(1, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn gray_key_transparent() {
+        let idat =
+            crate::encode::compress(&[0, 0b0001_1000], Default::default()).expect("compress");
+        let png = png(3, 1, 2, 0, [(b"tRNS", &[0, 1][..]), (b"IDAT", &idat)]);
+
+        let image = decode(&png).expect("decode");
+        assert_eq!(image.get(0, 0), [0, 0, 0, 255]);
+        assert_eq!(image.get(1, 0)[3], 0);
+        assert_eq!(image.get(2, 0), [170, 170, 170, 255]);
+    }
+
+    #[test]
+    fn rgb_key_transparent() {
+        let idat = crate::encode::compress(&[0, 255, 0, 255, 255, 0, 0], Default::default())
+            .expect("compress");
+        let png = png(
+            2,
+            1,
+            8,
+            2,
+            [(b"tRNS", &[0, 255, 0, 0, 0, 255][..]), (b"IDAT", &idat)],
+        );
+
+        let image = decode(&png).expect("decode");
+        assert_eq!(image.get(0, 0)[3], 0);
+        assert_eq!(image.get(1, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn colors_converted_only_for_display() {
+        let idat = crate::encode::compress(&[0, 128], Default::default()).expect("compress");
+        // linear samples
+        let png = png(
+            1,
+            1,
+            8,
+            0,
+            [(b"gAMA", &100_000u32.to_be_bytes()[..]), (b"IDAT", &idat)],
+        );
+
+        assert_eq!(
+            decode(&png).expect("decode").get(0, 0),
+            [128, 128, 128, 255]
+        );
+        let display = color::Profile::srgb();
+        let shown = decode_with(&png, Handlers::default().display(&display)).expect("decode");
+        assert_eq!(shown.get(0, 0), [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn ihdr_methods_checked() {
+        let png = |rest: [u8; 5]| {
+            let ihdr = [&16u32.to_be_bytes()[..], &16u32.to_be_bytes(), &rest].concat();
+            crate::encode::write_png([(b"IHDR", ihdr.as_slice()), (b"IDAT", &[]), (b"IEND", &[])])
+        };
+        assert!(matches!(
+            decode(&png([8, 6, 1, 0, 0])),
+            Err(error::Error::InvalidCompressionMethod(1))
+        ));
+        assert!(matches!(
+            decode(&png([8, 6, 0, 2, 0])),
+            Err(error::Error::InvalidFilterMethod(2))
+        ));
+        assert!(matches!(
+            decode(&png([4, 2, 0, 0, 0])),
+            Err(error::Error::InvalidBitColorCombo(4, 2))
+        ));
+    }
+
+    #[test]
+    fn zero_dimensions_rejected() {
+        for (width, height) in [(0u32, 16u32), (16, 0), (1 << 31, 16)] {
+            let png = png(width, height, 8, 6, [(b"IDAT", &[][..])]);
+
+            assert!(matches!(
+                dimensions(&png),
+                Err(error::Error::InvalidDimensions(w, h)) if (w, h) == (width, height)
+            ));
+            assert!(matches!(
+                decode(&png),
+                Err(error::Error::InvalidDimensions(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn paeth_matches_spec() {
+        for left in 0..=255 {
+            for up in 0..=255 {
+                for up_left in 0..=255 {
+                    assert_eq!(paeth(left, up, up_left), paeth_spec(left, up, up_left));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn daylight_is_neutral() {
+        let daylight = color_temperature(6500.0);
+        for (c, row) in daylight.iter().enumerate() {
+            assert!((row[c] - 1.0).abs() < 1e-6);
+        }
+
+        let warm = color_temperature(3400.0);
+        assert!(warm[0][0] >= warm[1][1] && warm[1][1] > warm[2][2]);
+    }
+
+    #[test]
+    fn format_labels() -> Result<(), Box<dyn Error>> {
+        assert_eq!(format_label(PNG)?, "RGB8");
+        Ok(())
+    }
+
+    #[test]
+    fn exact_zoom_clamped() {
+        assert_eq!(Zoom::from_percent_exact(333.0).percent(), 333.0);
+        assert!(matches!(Zoom::from_percent_exact(250.0), Zoom::X2p5));
+        assert!(matches!(Zoom::from_percent_exact(5.0), Zoom::X1));
+        assert!(matches!(Zoom::from_percent_exact(1000.0), Zoom::X4));
+    }
+
+    #[test]
+    fn drag_selects_whole_pixels() {
+        let mut state = State::new(iced::Size::new(20.0, 10.0));
+        state.set_viewport(iced::Size::new(100.0, 100.0));
+        state.set_zoom(Zoom::X2);
+        state.center_on(iced::Point::new(10.0, 5.0));
+
+        let origin = state.to_screen(iced::Point::ORIGIN);
+        let region = state.screen_region(
+            origin + iced::Vector::new(7.0, 3.0),
+            origin + iced::Vector::new(-50.0, 100.0),
+        );
+        assert_eq!(
+            region,
+            iced::Rectangle {
+                x: 0,
+                y: 1,
+                width: 4,
+                height: 9
+            }
+        );
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_in_place() {
+        let mut state = State::new(iced::Size::new(200.0, 200.0));
+        state.set_viewport(iced::Size::new(100.0, 80.0));
+
+        let cursor = iced::Point::new(30.0, 70.0);
+        let under_cursor = state.to_image(cursor);
+        state.zoom_in(Some(cursor));
+        assert!(state.to_screen(under_cursor).distance(cursor) < 1e-3);
+
+        let center = iced::Point::new(50.0, 40.0);
+        let at_center = state.to_image(center);
+        state.zoom_toggle(None);
+        assert!(state.to_screen(at_center).distance(center) < 1e-3);
+    }
+
+    #[test]
+    fn rotation_round_trips() {
+        let mut state = State::new(iced::Size::new(30.0, 20.0));
+        state.set_rotation(Rotation::from_degrees(90));
+        assert_eq!(state.displayed_size(), iced::Size::new(20.0, 30.0));
+
+        let point = iced::Point::new(3.0, 5.0);
+        assert_eq!(state.to_screen(point), iced::Point::new(15.0, 3.0));
+        assert_eq!(state.to_image(state.to_screen(point)), point);
+        assert_eq!(Rotation::from_degrees(-90), Rotation::R270);
+    }
 }
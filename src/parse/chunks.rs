@@ -1,16 +1,18 @@
-use std::fmt::Write;
+use std::{collections::HashMap, fmt::Write, io::Read};
 
 use super::{one_byte_as, Error};
+use crate::color::{Chromaticities, Profile};
 
 use nom::{
-    bytes::complete::{tag, take, take_while_m_n},
+    bytes::complete::{tag, take, take_till, take_while_m_n},
     character::is_alphabetic,
     combinator::all_consuming,
-    number::complete::be_u32,
+    multi::count,
+    number::complete::{be_u32, u8},
     Err, HexDisplay, IResult,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BitDepth {
     One = 1,
@@ -35,7 +37,7 @@ impl TryFrom<u8> for BitDepth {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ColorType {
     GrayScale = 0,
@@ -78,12 +80,35 @@ impl TryFrom<u8> for Interlace {
     }
 }
 
+pub fn bits_per_pixel(bit_depth: BitDepth, color_type: ColorType) -> Result<usize, Error> {
+    use BitDepth as BD;
+    use ColorType as CT;
+
+    Ok(match (bit_depth, color_type) {
+        (BD::One, CT::GrayScale | CT::Palette) => 1,
+        (BD::Two, CT::GrayScale | CT::Palette) => 2,
+        (BD::Four, CT::GrayScale | CT::Palette) => 4,
+        (BD::Eight, CT::GrayScale | CT::Palette) => 8,
+        (BD::Eight, CT::GrayScaleAlpha) | (BD::Sixteen, CT::GrayScale) => 16,
+        (BD::Eight, CT::Rgb) => 24,
+        (BD::Eight, CT::RgbAlpha) | (BD::Sixteen, CT::GrayScaleAlpha) => 32,
+        (BD::Sixteen, CT::Rgb) => 48,
+        (BD::Sixteen, CT::RgbAlpha) => 64,
+        _ => {
+            return Err(Error::InvalidBitColorCombo(
+                bit_depth as u8,
+                color_type as u8,
+            ))
+        }
+    })
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Colors<'data>(&'data [u8]);
 
 impl<'data> Colors<'data> {
     pub fn new(input: &'data [u8]) -> Result<Self, super::Error> {
-        if input.len() % 3 > 0 || input.len() > 256 * 3 {
+        if !input.len().is_multiple_of(3) || input.len() > 256 * 3 {
             Err(super::Error::InvalidPaletteSize(input.len()))
         } else {
             Ok(Self(input))
@@ -134,6 +159,223 @@ impl<'data> From<Bytes<'data>> for &'data [u8] {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkType(pub [u8; 4]);
+
+impl ChunkType {
+    /// The type with its case bits cleared, for matching regardless of chunk properties.
+    pub(super) fn upper(self) -> [u8; 4] {
+        let mut ty = self.0;
+        ty.make_ascii_uppercase();
+        ty
+    }
+
+    /// Whether a decoder that doesn't understand this chunk must give up on the image.
+    pub fn is_critical(self) -> bool {
+        self.0[0].is_ascii_uppercase()
+    }
+}
+
+impl std::fmt::Debug for ChunkType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+/// Callbacks for chunks the decoder doesn't understand itself, keyed by their
+/// case-sensitive type (e.g. `gdAT`), along with the settings the decoder runs under.
+///
+/// Only ancillary chunks are handed to callbacks: a proprietary chunk can add to an
+/// image, but never change how the standard chunks are read.
+#[derive(Default)]
+pub struct Handlers<'a> {
+    handlers: HashMap<ChunkType, Handler<'a>>,
+    pub(super) limits: Limits,
+    pub(super) policy: Policy,
+    pub(super) display: Option<&'a Profile>,
+    warnings: Vec<String>,
+}
+
+type Handler<'a> = Box<dyn FnMut(&[u8]) + 'a>;
+
+impl<'a> Handlers<'a> {
+    pub fn on(&mut self, ty: &[u8; 4], handler: impl FnMut(&[u8]) + 'a) -> &mut Self {
+        self.handlers.insert(ChunkType(*ty), Box::new(handler));
+        self
+    }
+
+    pub fn limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn policy(&mut self, policy: Policy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Converts decoded colors from the file's color space to `profile`, for showing them
+    /// on screen. Without it samples come out as stored, fit to encode again.
+    pub fn display(&mut self, profile: &'a Profile) -> &mut Self {
+        self.display = Some(profile);
+        self
+    }
+
+    /// Problems tolerated while decoding under [`Policy::Lenient`].
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub(super) fn warn(&mut self, warning: String) {
+        tracing::warn!("{warning}");
+        self.warnings.push(warning);
+    }
+
+    pub(super) fn handle(&mut self, ty: ChunkType, data: &[u8]) {
+        if ty.is_critical() {
+            return;
+        }
+        if let Some(handler) = self.handlers.get_mut(&ty) {
+            handler(data);
+        }
+    }
+}
+
+/// How the decoder reacts to damaged files.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Policy {
+    /// Fail on the first problem.
+    #[default]
+    Strict,
+    /// Tolerate bad CRCs, misplaced chunks, truncated image data and a missing IEND,
+    /// rendering as much as possible and recording warnings instead.
+    Lenient,
+}
+
+/// Caps on how many ancillary chunks get parsed, so files padded with thousands of tiny
+/// chunks can't stall decoding or flood the metadata panel, and on how much a small file
+/// may decompress to, so it can't exhaust memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub text_chunks: usize,
+    pub unknown_chunks: usize,
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Size of the decoded pixels: four bytes each, or eight when decoding at 16 bits.
+    pub max_decoded_bytes: u64,
+    /// Inflated bytes per byte of IDAT payload. Deflate itself tops out a little above
+    /// 1000, so lower values also turn away some legitimate, very uniform images.
+    pub max_expansion: u64,
+    /// Inflated size of each zlib stream in an ancillary chunk. Compressed iTXt text is cut
+    /// off there with a warning; a larger iCCP profile is ignored.
+    pub max_metadata_bytes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            text_chunks: 1024,
+            unknown_chunks: 4096,
+            max_width: 1 << 17,
+            max_height: 1 << 17,
+            max_decoded_bytes: 1 << 32,
+            max_expansion: 1100,
+            max_metadata_bytes: 4 << 20,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks the dimensions in IHDR, before anything is allocated for the pixels.
+    pub fn check_dimensions(
+        &self,
+        width: u32,
+        height: u32,
+        bytes_per_pixel: u64,
+    ) -> Result<(), super::Error> {
+        use super::Error::LimitExceeded;
+
+        if width > self.max_width {
+            return Err(LimitExceeded("width", width.into(), self.max_width.into()));
+        }
+        if height > self.max_height {
+            return Err(LimitExceeded(
+                "height",
+                height.into(),
+                self.max_height.into(),
+            ));
+        }
+        let bytes = u64::from(width) * u64::from(height) * bytes_per_pixel;
+        if bytes > self.max_decoded_bytes {
+            return Err(LimitExceeded("decoded size", bytes, self.max_decoded_bytes));
+        }
+        Ok(())
+    }
+}
+
+/// Counts chunks against [`Limits`], remembering how many were skipped.
+#[derive(Debug, Default)]
+pub struct Counts {
+    limits: Limits,
+    text: usize,
+    unknown: usize,
+    skipped_text: usize,
+    skipped_unknown: usize,
+}
+
+impl Counts {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Whether a chunk of this type should still be parsed.
+    pub fn admit(&mut self, ty: ChunkType) -> bool {
+        let (count, limit, skipped) = match &ty.upper() {
+            b"IHDR" | b"PLTE" | b"IDAT" | b"IEND" | b"GAMA" | b"SRGB" | b"CHRM" | b"ICCP"
+            | b"TRNS" => return true,
+            b"TEXT" | b"ITXT" => (
+                &mut self.text,
+                self.limits.text_chunks,
+                &mut self.skipped_text,
+            ),
+            _ => (
+                &mut self.unknown,
+                self.limits.unknown_chunks,
+                &mut self.skipped_unknown,
+            ),
+        };
+
+        if *count < limit {
+            *count += 1;
+            true
+        } else {
+            *skipped += 1;
+            false
+        }
+    }
+
+    /// Describes what was skipped, if anything.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.skipped_text > 0 {
+            warnings.push(format!(
+                "{} text chunks skipped after the first {}",
+                self.skipped_text, self.limits.text_chunks
+            ));
+        }
+        if self.skipped_unknown > 0 {
+            warnings.push(format!(
+                "{} unknown chunks skipped after the first {}",
+                self.skipped_unknown, self.limits.unknown_chunks
+            ));
+        }
+        warnings
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Chunk<'data> {
     Ihdr {
@@ -147,47 +389,142 @@ pub enum Chunk<'data> {
     Idat(Bytes<'data>),
     Iend,
     Gama(f32),
-    Unknown,
+    /// The rendering intent of an sRGB chunk.
+    Srgb(u8),
+    Chrm(Chromaticities),
+    /// An embedded ICC profile, still compressed.
+    Iccp {
+        name: String,
+        profile: Bytes<'data>,
+    },
+    Text(Text),
+    /// Transparency, laid out according to the color type.
+    Trns(Bytes<'data>),
+    Unknown(ChunkType, Bytes<'data>),
+}
+
+/// A tEXt or iTXt entry. Malformed text is decoded lossily, with a warning
+/// describing each problem found.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Text {
+    pub keyword: String,
+    pub language: String,
+    pub translated_keyword: String,
+    pub text: String,
+    pub warnings: Vec<String>,
+}
+
+/// A chunk as stored in the file, before its data is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawChunk<'data> {
+    pub ty: ChunkType,
+    pub data: Bytes<'data>,
+    pub crc: u32,
+}
+
+impl RawChunk<'_> {
+    pub fn computed_crc(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.ty.0);
+        hasher.update(self.data.0);
+        hasher.finalize()
+    }
+
+    pub fn crc_matches(&self) -> bool {
+        self.crc == self.computed_crc()
+    }
 }
 
-pub fn chunk(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
+pub fn raw_chunk(input: &[u8]) -> IResult<&[u8], RawChunk<'_>, Error> {
     let (input, length) = be_u32(input)?;
+    if length > i32::MAX as u32 {
+        return Err(Err::Failure(Error::ChunkTooLarge(length)));
+    }
     let (input, ty) = take_while_m_n(4, 4, is_alphabetic)(input)?;
-    let (input, chunk_data) = take(length)(input)?;
-    let (input, _crc) = take(4usize)(input)?;
+    let (input, data) = take(length)(input)?;
+    let (input, crc) = be_u32(input)?;
 
-    let ty_upper = {
-        let mut ty: [u8; 4] = ty.try_into().expect("just took exactly 4");
-        ty.make_ascii_uppercase();
-        ty
-    };
+    Ok((
+        input,
+        RawChunk {
+            ty: ChunkType(ty.try_into().expect("just took exactly 4")),
+            data: data.into(),
+            crc,
+        },
+    ))
+}
+
+pub fn chunk(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    let (input, raw) = raw_chunk(input)?;
+    Ok((input, parse(raw)?))
+}
+
+/// Like [`chunk`], but also passes unknown ancillary chunks to their callback in
+/// `handlers`, for callers walking the chunks themselves.
+pub fn chunk_with<'data>(
+    input: &'data [u8],
+    handlers: &mut Handlers,
+) -> IResult<&'data [u8], Chunk<'data>, Error> {
+    let (input, raw) = raw_chunk(input)?;
+    let chunk = parse_with(raw, &handlers.limits)?;
+    if let Chunk::Unknown(ty, data) = chunk {
+        handlers.handle(ty, data.into());
+    }
+    Ok((input, chunk))
+}
+
+/// Interprets the payload of a chunk that has already been split off.
+pub fn parse(raw: RawChunk<'_>) -> Result<Chunk<'_>, Err<Error>> {
+    parse_with(raw, &Limits::default())
+}
 
-    let (_, chunk) = all_consuming(match &ty_upper {
+/// Like [`parse`], with `limits` capping how far compressed text may inflate.
+pub fn parse_with<'data>(
+    RawChunk { ty, data, .. }: RawChunk<'data>,
+    limits: &Limits,
+) -> Result<Chunk<'data>, Err<Error>> {
+    let chunk_data: &[u8] = data.into();
+
+    let (_, chunk) = all_consuming(match &ty.upper() {
         b"IHDR" => ihdr,
         b"PLTE" => plte,
         b"IDAT" => idat,
         b"IEND" => iend,
         b"GAMA" => gama,
+        b"SRGB" => srgb,
+        b"CHRM" => chrm,
+        b"ICCP" => iccp,
+        b"TEXT" => text,
+        b"ITXT" => return Ok(itxt(chunk_data, limits.max_metadata_bytes)?.1),
+        b"TRNS" => trns,
         _ => {
-            tracing::debug!("found unknown chunk: {:?}", std::str::from_utf8(&ty_upper));
-            unknown
+            tracing::debug!("found unknown chunk: {ty:?}");
+            return Ok(Chunk::Unknown(ty, data));
         }
     })(chunk_data)?;
 
-    Ok((input, chunk))
+    Ok(chunk)
 }
 
-fn unknown(_input: &[u8]) -> IResult<&[u8], Chunk, Error> {
-    Ok((b"", Chunk::Unknown))
-}
-
-fn ihdr(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
+fn ihdr(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
     let (input, width) = be_u32(input)?;
     let (input, height) = be_u32(input)?;
+    const MAX_DIMENSION: u32 = (1 << 31) - 1;
+    if !(1..=MAX_DIMENSION).contains(&width) || !(1..=MAX_DIMENSION).contains(&height) {
+        return Err(Err::Failure(Error::InvalidDimensions(width, height)));
+    }
     let (input, bit_depth) = one_byte_as::<BitDepth>(input)?;
     let (input, color_type) = one_byte_as::<ColorType>(input)?;
-    let (input, _compression) = tag(b"\x00")(input)?;
-    let (input, _filter) = tag(b"\x00")(input)?;
+    bits_per_pixel(bit_depth, color_type).map_err(Err::Failure)?;
+    // zlib deflate and adaptive filtering are the only methods the spec defines
+    let (input, compression) = u8(input)?;
+    if compression != 0 {
+        return Err(Err::Failure(Error::InvalidCompressionMethod(compression)));
+    }
+    let (input, filter) = u8(input)?;
+    if filter != 0 {
+        return Err(Err::Failure(Error::InvalidFilterMethod(filter)));
+    }
     let (input, interlace) = one_byte_as::<Interlace>(input)?;
 
     Ok((
@@ -202,18 +539,19 @@ fn ihdr(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
     ))
 }
 
-fn plte(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
-    Ok((
-        input,
-        Chunk::Plte(Colors::new(input).map_err(Err::Failure)?),
-    ))
+fn plte(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    Ok((b"", Chunk::Plte(Colors::new(input).map_err(Err::Failure)?)))
 }
 
-fn idat(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
+fn trns(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    Ok((b"", Chunk::Trns(input.into())))
+}
+
+fn idat(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
     Ok((b"", Chunk::Idat(input.into())))
 }
 
-fn iend(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
+fn iend(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
     if input.is_empty() {
         Ok((input, Chunk::Iend))
     } else {
@@ -221,7 +559,166 @@ fn iend(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
     }
 }
 
-fn gama(input: &[u8]) -> IResult<&[u8], Chunk, Error> {
+fn gama(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
     let (input, gamma) = be_u32(input)?;
     Ok((input, Chunk::Gama(gamma as f32 / 100_000.0)))
 }
+
+fn srgb(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    let (input, intent) = u8(input)?;
+    Ok((input, Chunk::Srgb(intent)))
+}
+
+fn chrm(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    let (input, values) = count(be_u32, 8)(input)?;
+    let point = |i: usize| [values[i], values[i + 1]].map(|value| value as f32 / 100_000.0);
+    Ok((
+        input,
+        Chunk::Chrm(Chromaticities {
+            white: point(0),
+            red: point(2),
+            green: point(4),
+            blue: point(6),
+        }),
+    ))
+}
+
+fn iccp(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    let (input, name) = take_till(|byte| byte == 0)(input)?;
+    // the null separator, then deflate as the only compression method
+    let (profile, _) = tag(b"\0\0")(input)?;
+    Ok((
+        b"",
+        Chunk::Iccp {
+            name: name.iter().map(|&byte| byte as char).collect(),
+            profile: profile.into(),
+        },
+    ))
+}
+
+fn split_null<'data>(
+    input: &'data [u8],
+    field: &str,
+    warnings: &mut Vec<String>,
+) -> (&'data [u8], &'data [u8]) {
+    match input.iter().position(|&byte| byte == 0) {
+        Some(null) => (&input[..null], &input[null + 1..]),
+        None => {
+            warnings.push(format!("missing null separator after {field}"));
+            (input, b"")
+        }
+    }
+}
+
+fn latin1(input: &[u8], field: &str, allow_newline: bool, warnings: &mut Vec<String>) -> String {
+    let mut invalid = false;
+    let text = input
+        .iter()
+        .map(|&byte| match byte {
+            b'\n' if allow_newline => '\n',
+            0x20..=0x7E | 0xA1..=0xFF => byte as char,
+            _ => {
+                invalid = true;
+                char::REPLACEMENT_CHARACTER
+            }
+        })
+        .collect();
+    if invalid {
+        warnings.push(format!(
+            "{field} contains characters outside printable Latin-1"
+        ));
+    }
+    text
+}
+
+fn utf8(input: &[u8], field: &str, warnings: &mut Vec<String>) -> String {
+    let text = String::from_utf8_lossy(input);
+    if let std::borrow::Cow::Owned(_) = text {
+        warnings.push(format!("{field} is not valid UTF-8"));
+    }
+    text.into_owned()
+}
+
+fn check_keyword(keyword: &str, warnings: &mut Vec<String>) {
+    let len = keyword.chars().count();
+    if !(1..=79).contains(&len) {
+        warnings.push(format!("keyword must be 1-79 characters, found {len}"));
+    }
+}
+
+fn warn_all(text: Text) -> Text {
+    for warning in &text.warnings {
+        tracing::warn!("text chunk {:?}: {warning}", text.keyword);
+    }
+    text
+}
+
+fn text(input: &[u8]) -> IResult<&[u8], Chunk<'_>, Error> {
+    let mut warnings = Vec::new();
+    let (keyword, text) = split_null(input, "keyword", &mut warnings);
+    let keyword = latin1(keyword, "keyword", false, &mut warnings);
+    let text = latin1(text, "text", true, &mut warnings);
+    check_keyword(&keyword, &mut warnings);
+
+    Ok((
+        b"",
+        Chunk::Text(warn_all(Text {
+            keyword,
+            text,
+            warnings,
+            ..Text::default()
+        })),
+    ))
+}
+
+fn itxt(input: &[u8], max_bytes: u64) -> IResult<&[u8], Chunk<'_>, Error> {
+    let mut warnings = Vec::new();
+    let (keyword, input) = split_null(input, "keyword", &mut warnings);
+    let keyword = latin1(keyword, "keyword", false, &mut warnings);
+    check_keyword(&keyword, &mut warnings);
+
+    let (compressed, input) = match input {
+        [flag, _method, rest @ ..] => (*flag != 0, rest),
+        _ => {
+            warnings.push("missing compression flag and method".into());
+            (false, input)
+        }
+    };
+    let (language, input) = split_null(input, "language tag", &mut warnings);
+    let (translated_keyword, text) = split_null(input, "translated keyword", &mut warnings);
+
+    let text = if compressed {
+        // one byte past the cap is enough to tell the text was cut off
+        let mut inflated = Vec::new();
+        let result = flate2::read::ZlibDecoder::new(text)
+            .take(max_bytes + 1)
+            .read_to_end(&mut inflated);
+        if let Err(error) = result {
+            warnings.push(format!("compressed text failed to inflate: {error}"));
+        }
+        if inflated.len() as u64 > max_bytes {
+            warnings.push(format!("compressed text cut off after {max_bytes} bytes"));
+            inflated.truncate(max_bytes as usize);
+            // without splitting the last character
+            if let Err(error) = std::str::from_utf8(&inflated) {
+                if error.error_len().is_none() {
+                    inflated.truncate(error.valid_up_to());
+                }
+            }
+        }
+        utf8(&inflated, "text", &mut warnings)
+    } else {
+        utf8(text, "text", &mut warnings)
+    };
+
+    Ok((
+        b"",
+        Chunk::Text(warn_all(Text {
+            keyword,
+            language: latin1(language, "language tag", false, &mut warnings),
+            translated_keyword: utf8(translated_keyword, "translated keyword", &mut warnings),
+            text,
+            warnings,
+        })),
+    ))
+}
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use super::{
+    chunks::{self, Handlers},
+    header, ChunkDecoder, Error, Fed, FilterType, Pass, PixelFormat, RowSink,
+};
+
+/// IDAT payload inflated at a time, which bounds how many rows are queued at once.
+const IDAT_PIECE: usize = 8 * 1024;
+
+/// Decodes an in-memory PNG one scanline at a time, leaving what to do with each row to
+/// the caller, rather than drawing every pixel into a [`Render`](super::Render) sink.
+///
+/// Chunks are handled exactly as by [`draw`](super::draw), including the policy and
+/// handlers, so the rows match what would have been drawn.
+pub struct Decoder<'data, 'h, 'a> {
+    input: &'data [u8],
+    /// IDAT payload not yet inflated.
+    idat: &'data [u8],
+    chunks: ChunkDecoder<'h, 'a, Queue>,
+    width: usize,
+    height: usize,
+    stage: Stage,
+    row: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Reading,
+    /// The image data has ended, but hasn't been flushed yet.
+    Ended,
+    Finished,
+}
+
+/// An unfiltered scanline.
+pub struct Row<'r> {
+    /// The image row the scanline belongs to.
+    pub y: usize,
+    /// Which of the row's pixels it holds, for an interlaced image.
+    pub pass: Pass,
+    pub filter: FilterType,
+    /// The row's bytes, without the filter byte.
+    pub data: &'r [u8],
+    /// Pixels in the scanline.
+    width: usize,
+    format: &'r PixelFormat,
+}
+
+impl Row<'_> {
+    pub fn colors(&self) -> Result<Vec<iced::Color>, Error> {
+        self.format.colors(self.data, self.width)
+    }
+}
+
+/// Rows inflated ahead of the caller asking for them.
+#[derive(Default)]
+struct Queue(VecDeque<(usize, Pass, FilterType, Vec<u8>)>);
+
+impl RowSink for Queue {
+    fn row(
+        &mut self,
+        _format: &PixelFormat,
+        y: usize,
+        pass: Pass,
+        filter: FilterType,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.0.push_back((y, pass, filter, data.to_vec()));
+        Ok(())
+    }
+}
+
+impl<'data, 'h, 'a> Decoder<'data, 'h, 'a> {
+    /// Reads the header, ready to decode rows.
+    pub fn new(data: &'data [u8], handlers: &'h mut Handlers<'a>) -> Result<Self, Error> {
+        let (input, _) = header(data)?;
+        let (input, raw) = chunks::raw_chunk(input)?;
+        let crc_matches = raw.crc_matches();
+        let ty = raw.ty;
+
+        let (mut width, mut height) = (0, 0);
+        let mut chunks = ChunkDecoder::new(
+            chunks::parse(raw)?,
+            |w, h, _| {
+                (width, height) = (w, h);
+                Queue::default()
+            },
+            handlers,
+        )?;
+        if !crc_matches {
+            chunks.tolerate(Error::CrcMismatch(ty))?;
+        }
+
+        Ok(Self {
+            input,
+            idat: &[],
+            chunks,
+            width,
+            height,
+            stage: Stage::Reading,
+            row: Vec::new(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The next scanline, or `None` once the image data has run out.
+    pub fn next_row(&mut self) -> Result<Option<Row<'_>>, Error> {
+        loop {
+            if let Some((y, pass, filter, data)) =
+                self.chunks.rows_mut().and_then(|q| q.0.pop_front())
+            {
+                self.row = data;
+                return Ok(Some(Row {
+                    y,
+                    pass,
+                    filter,
+                    data: &self.row,
+                    width: pass.width(self.width),
+                    format: self.chunks.format(),
+                }));
+            }
+
+            match self.stage {
+                Stage::Finished => return Ok(None),
+                Stage::Ended => {
+                    self.chunks.end([])?;
+                    self.stage = Stage::Finished;
+                }
+                Stage::Reading if !self.idat.is_empty() => {
+                    let (piece, rest) = self.idat.split_at(self.idat.len().min(IDAT_PIECE));
+                    self.idat = rest;
+                    if self.chunks.write_idat(piece)? {
+                        self.stage = Stage::Ended;
+                    }
+                }
+                Stage::Reading => self.read_chunk()?,
+            }
+        }
+    }
+
+    fn read_chunk(&mut self) -> Result<(), Error> {
+        match chunks::raw_chunk(self.input) {
+            Ok((rest, raw)) => {
+                self.input = rest;
+                if !raw.crc_matches() {
+                    self.chunks.tolerate(Error::CrcMismatch(raw.ty))?;
+                }
+                match self.chunks.interpret(raw)? {
+                    Fed::More => {}
+                    Fed::Idat(data) => self.idat = data,
+                    Fed::End => self.stage = Stage::Ended,
+                }
+            }
+            Err(nom::Err::Failure(error)) => return Err(error),
+            Err(_) => {
+                self.chunks.tolerate(Error::MissingCritical("IEND"))?;
+                self.chunks.feed_truncated(self.input);
+                self.stage = Stage::Ended;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use nom::HexDisplay;
 
+use super::chunks::ChunkType;
+
 type NomError = nom::error::Error<DbgString>;
 
 #[derive(Debug, thiserror::Error, Default)]
@@ -11,6 +13,9 @@ pub enum Error {
     #[error("file parsing failed with error: {}; data:{:?}", .0.code.description(), .0.input)]
     NomFailed(NomError),
 
+    #[error("invalid image dimensions {0}x{1}: each must be from 1 to 2^31-1")]
+    InvalidDimensions(u32, u32),
+
     #[error("invalid bit depth: {0}")]
     InvalidBitDepth(u8),
 
@@ -20,12 +25,27 @@ pub enum Error {
     #[error("invalid bit depth ({0}) and color type ({1}) combination")]
     InvalidBitColorCombo(u8, u8),
 
+    #[error("unknown compression method {0}: only 0 (deflate) is defined")]
+    InvalidCompressionMethod(u8),
+
+    #[error("unknown filter method {0}: only 0 (adaptive) is defined")]
+    InvalidFilterMethod(u8),
+
     #[error("invalid interlace method: {0}")]
     InvalidInterlace(u8),
 
     #[error("invalid palette size: {0}")]
     InvalidPaletteSize(usize),
 
+    #[error("palette index {0} is past the {1} palette entries")]
+    PaletteIndex(u8, usize),
+
+    #[error("{0} palette entries can't be indexed at {1} bits")]
+    PaletteDepth(usize, u8),
+
+    #[error("tRNS chunk of {0} bytes doesn't fit the image's color type")]
+    InvalidTransparency(usize),
+
     #[error("invalid filter type: {0}")]
     InvalidFilterType(u8),
 
@@ -35,13 +55,51 @@ pub enum Error {
     #[error("invalid IEND chunk found")]
     InvalidIEnd,
 
+    #[error("chunk length {0} exceeds the 2^31-1 maximum")]
+    ChunkTooLarge(u32),
+
+    #[error("CRC mismatch in {0:?} chunk")]
+    CrcMismatch(ChunkType),
+
+    #[error("{0:?} chunk must come before the first IDAT")]
+    ChunkOrder(ChunkType),
+
+    #[error("IDAT chunks must be consecutive")]
+    SplitIdat,
+
+    #[error("image data ends after {0} of {1} rows")]
+    Truncated(usize, usize),
+
     #[error("duplicate IHDR chunk found")]
     DuplicateIhdr,
 
+    #[error("unsupported ICC profile: {0}")]
+    Icc(&'static str),
+
+    #[error("{0} of {1} exceeds the limit of {2}")]
+    LimitExceeded(&'static str, u64, u64),
+
+    #[error("invalid deflate stream at byte {0}: {1}")]
+    InvalidDeflate(usize, &'static str),
+
+    #[error("invalid APNG frame {0}: {1}")]
+    InvalidFrame(usize, &'static str),
+
+    #[cfg(feature = "webp")]
+    #[error("WebP decoding failed: {0}")]
+    WebP(String),
+
     #[error("io error found: {0}")]
     IoError(#[source] std::io::Error),
 }
 
+impl Error {
+    /// Whether the file just ends early, so the rows before the cut are still good.
+    pub fn is_truncation(&self) -> bool {
+        matches!(self, Self::Truncated(..) | Self::MissingCritical("IEND"))
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         match error.get_ref() {
@@ -0,0 +1,813 @@
+use super::{ColorInfo, Pass, Render, Rgba, State};
+
+/// Per-pixel differences between two images of the same size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    /// Number of pixels whose largest channel delta exceeds the tolerance.
+    pub count: usize,
+    pub max_delta: u8,
+    pub bounds: Option<iced::Rectangle<usize>>,
+    /// Largest channel delta of each pixel.
+    pub deltas: Vec<u8>,
+}
+
+impl Diff {
+    /// The deltas as an image `width` pixels wide: black where they're within `tolerance`,
+    /// and from blue through red to yellow as they grow.
+    pub fn heatmap(&self, width: usize, tolerance: u8) -> Image {
+        let pixels = self
+            .deltas
+            .iter()
+            .map(|&delta| {
+                if delta <= tolerance {
+                    return [0, 0, 0, 255];
+                }
+                let t = delta as f32 / 255.0;
+                let color = if t < 0.5 {
+                    [2.0 * t, 0.0, 1.0 - 2.0 * t]
+                } else {
+                    [1.0, 2.0 * t - 1.0, 0.0]
+                };
+                let [r, g, b] = color.map(|c| (c * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+        Raster::from_pixels(width, self.deltas.len() / width.max(1), pixels)
+            .unwrap_or_else(|| Image::new(0, 0))
+    }
+}
+
+/// Per-channel (RGBA) summary of an image's pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub mean: [f64; 4],
+    pub std_dev: [f64; 4],
+    pub min: [u8; 4],
+    pub max: [u8; 4],
+    /// Percentage of pixels with full alpha.
+    pub opaque_percent: f64,
+}
+
+impl Statistics {
+    pub fn to_json(&self) -> String {
+        let floats = |values: [f64; 4]| values.map(|value| format!("{value:.3}")).join(", ");
+        let bytes = |values: [u8; 4]| values.map(|value| value.to_string()).join(", ");
+        format!(
+            "{{\"mean\": [{}], \"std_dev\": [{}], \"min\": [{}], \"max\": [{}], \"opaque_percent\": {:.3}}}",
+            floats(self.mean),
+            floats(self.std_dev),
+            bytes(self.min),
+            bytes(self.max),
+            self.opaque_percent
+        )
+    }
+}
+
+/// How [`Raster::resample`] weighs the source pixels around each output pixel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+    Lanczos,
+}
+
+impl Filter {
+    pub const ALL: [Self; 3] = [Self::Nearest, Self::Bilinear, Self::Lanczos];
+
+    /// How far the kernel reaches either side, in pixels at 1:1.
+    fn support(self) -> f32 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            Self::Nearest => f32::from(u8::from((-0.5..0.5).contains(&x))),
+            Self::Bilinear => (1.0 - x.abs()).max(0.0),
+            Self::Lanczos if x == 0.0 => 1.0,
+            Self::Lanczos if x.abs() < 3.0 => {
+                let x = std::f32::consts::PI * x;
+                3.0 * x.sin() * (x / 3.0).sin() / (x * x)
+            }
+            Self::Lanczos => 0.0,
+        }
+    }
+
+    /// For each of `to` output pixels, the first of the `from` source pixels it reads and
+    /// how much each one from there on counts.
+    fn weights(self, from: usize, to: usize) -> Vec<(usize, Vec<f32>)> {
+        let ratio = from as f32 / to as f32;
+        // widened when shrinking, so every source pixel contributes
+        let scale = ratio.max(1.0);
+        let support = self.support() * scale;
+        (0..to)
+            .map(|i| {
+                let center = (i as f32 + 0.5) * ratio;
+                let start = (center - support).floor().max(0.0) as usize;
+                let end = ((center + support).ceil() as usize).clamp(start + 1, from);
+                let mut weights: Vec<_> = (start..end)
+                    .map(|j| self.weight((j as f32 + 0.5 - center) / scale))
+                    .collect();
+                let total: f32 = weights.iter().sum();
+                if total != 0.0 {
+                    weights.iter_mut().for_each(|weight| *weight /= total);
+                }
+                (start, weights)
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Nearest => "Nearest",
+            Self::Bilinear => "Bilinear",
+            Self::Lanczos => "Lanczos",
+        })
+    }
+}
+
+/// A channel of a [`Raster`]: 8 or 16 bits.
+pub trait Sample: Copy + Default + PartialOrd + Into<u32> + std::fmt::Debug {
+    const BITS: u8;
+    const MAX: u32 = (1 << Self::BITS) - 1;
+
+    /// Narrows a value known to fit.
+    fn from_u32(value: u32) -> Self;
+
+    /// The channels of `pixels` as big-endian bytes, the way PNG stores them.
+    fn to_be_bytes(pixels: &[[Self; 4]]) -> Vec<u8>;
+
+    /// Scales a channel from 0 to 1 to the full range.
+    fn from_unit(value: f32) -> Self {
+        Self::from_u32((value.clamp(0.0, 1.0) * Self::MAX as f32).round() as u32)
+    }
+}
+
+impl Sample for u8 {
+    const BITS: u8 = 8;
+
+    fn from_u32(value: u32) -> Self {
+        value as u8
+    }
+
+    fn to_be_bytes(pixels: &[[Self; 4]]) -> Vec<u8> {
+        pixels.as_flattened().to_vec()
+    }
+}
+
+impl Sample for u16 {
+    const BITS: u8 = 16;
+
+    fn from_u32(value: u32) -> Self {
+        value as u16
+    }
+
+    fn to_be_bytes(pixels: &[[Self; 4]]) -> Vec<u8> {
+        pixels
+            .as_flattened()
+            .iter()
+            .flat_map(|channel| channel.to_be_bytes())
+            .collect()
+    }
+}
+
+/// Straight-alpha RGBA pixels, with channels of `S`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster<S> {
+    width: usize,
+    height: usize,
+    pixels: Vec<[S; 4]>,
+}
+
+/// 8 bits per channel, which is what the viewer and most tools work with.
+pub type Image = Raster<u8>;
+/// 16 bits per channel, for keeping the full precision of 16-bit files.
+pub type Image16 = Raster<u16>;
+
+impl<S: Sample> Raster<S> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[S::default(); 4]; width * height],
+        }
+    }
+
+    /// Wraps row-major RGBA pixels, or returns `None` if there aren't `width * height`.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<[S; 4]>) -> Option<Self> {
+        (pixels.len() == width * height).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[S; 4]] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [S; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn row(&self, y: usize) -> &[[S; 4]] {
+        &self.pixels[y * self.width..][..self.width]
+    }
+
+    pub fn crop(&self, region: iced::Rectangle<usize>) -> Self {
+        let mut cropped = Self::new(region.width, region.height);
+        for y in 0..region.height {
+            let row = &self.row(region.y + y)[region.x..][..region.width];
+            cropped.pixels[y * region.width..][..region.width].copy_from_slice(row);
+        }
+        cropped
+    }
+
+    /// Bounding box of all pixels that are not fully transparent.
+    pub fn opaque_bounds(&self) -> Option<iced::Rectangle<usize>> {
+        let opaque = |x: usize, y: usize| self.get(x, y)[3] > S::default();
+        let top = (0..self.height).find(|&y| (0..self.width).any(|x| opaque(x, y)))?;
+        let bottom = (top..self.height).rfind(|&y| (0..self.width).any(|x| opaque(x, y)))? + 1;
+        let left = (0..self.width).find(|&x| (top..bottom).any(|y| opaque(x, y)))?;
+        let right = (left..self.width).rfind(|&x| (top..bottom).any(|y| opaque(x, y)))? + 1;
+
+        Some(iced::Rectangle {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    /// Resamples to the given size, averaging the source pixels under each output pixel.
+    pub fn resize(&self, width: usize, height: usize) -> Self {
+        let mut resized = Self::new(width, height);
+        if self.pixels.is_empty() {
+            return resized;
+        }
+        for y in 0..height {
+            let y0 = y * self.height / height;
+            let y1 = ((y + 1) * self.height / height).max(y0 + 1);
+            for x in 0..width {
+                let x0 = x * self.width / width;
+                let x1 = ((x + 1) * self.width / width).max(x0 + 1);
+                let mut sum = [0u64; 4];
+                for row in y0..y1 {
+                    for pixel in &self.row(row)[x0..x1] {
+                        for c in 0..4 {
+                            sum[c] += u64::from(pixel[c].into());
+                        }
+                    }
+                }
+                let count = ((x1 - x0) * (y1 - y0)) as u64;
+                resized.pixels[y * width + x] = sum.map(|sum| S::from_u32((sum / count) as u32));
+            }
+        }
+        resized
+    }
+
+    /// Resamples to the given size with `filter`, which unlike [`Self::resize`] also
+    /// interpolates when enlarging.
+    pub fn resample(&self, width: usize, height: usize, filter: Filter) -> Self {
+        if self.pixels.is_empty() || width == 0 || height == 0 {
+            return Self::new(width, height);
+        }
+        if filter == Filter::Nearest {
+            let pixels = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| self.get(x * self.width / width, y * self.height / height))
+                .collect();
+            return Self {
+                width,
+                height,
+                pixels,
+            };
+        }
+
+        // premultiplied, so transparent pixels don't bleed their color into neighbors
+        let max = S::MAX as f32;
+        let source: Vec<[f32; 4]> = self
+            .pixels
+            .iter()
+            .map(|pixel| {
+                let alpha = pixel[3].into() as f32 / max;
+                let [r, g, b, _] = pixel.map(|channel| channel.into() as f32 / max * alpha);
+                [r, g, b, alpha]
+            })
+            .collect();
+        let convolve = |pixels: &mut dyn Iterator<Item = (&[f32; 4], f32)>| {
+            pixels.fold([0.0; 4], |mut sum, (pixel, weight)| {
+                for c in 0..4 {
+                    sum[c] += pixel[c] * weight;
+                }
+                sum
+            })
+        };
+
+        let columns = filter.weights(self.width, width);
+        let mut wide = Vec::with_capacity(width * self.height);
+        for y in 0..self.height {
+            let row = &source[y * self.width..][..self.width];
+            for (start, weights) in &columns {
+                wide.push(convolve(
+                    &mut row[*start..].iter().zip(weights.iter().copied()),
+                ));
+            }
+        }
+        let rows = filter.weights(self.height, height);
+        let mut resampled = Self::new(width, height);
+        for (y, (start, weights)) in rows.iter().enumerate() {
+            for x in 0..width {
+                let column = wide[start * width + x..].iter().step_by(width);
+                let [r, g, b, alpha] = convolve(&mut column.zip(weights.iter().copied()));
+                let alpha = alpha.clamp(0.0, 1.0);
+                let color = |c: f32| S::from_unit(if alpha > 0.0 { c / alpha } else { 0.0 });
+                resampled.pixels[y * width + x] =
+                    [color(r), color(g), color(b), S::from_unit(alpha)];
+            }
+        }
+        resampled
+    }
+
+    /// Resamples the image as it is currently shown in the viewport.
+    pub fn view(&self, state: &State) -> Self {
+        let viewport = state.viewport();
+        let mut view = Self::new(viewport.width as usize, viewport.height as usize);
+        for y in 0..view.height {
+            for x in 0..view.width {
+                let point = state.to_image(iced::Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                if point.x >= 0.0 && point.y >= 0.0 {
+                    let (source_x, source_y) = (point.x as usize, point.y as usize);
+                    if source_x < self.width && source_y < self.height {
+                        view.pixels[y * view.width + x] = self.get(source_x, source_y);
+                    }
+                }
+            }
+        }
+        view
+    }
+}
+
+impl Image {
+    /// Finds the region left after trimming borders that match the top-left pixel (or are
+    /// fully transparent, if that pixel is). Returns `None` if there is nothing to trim.
+    pub fn auto_crop(&self) -> Option<iced::Rectangle<usize>> {
+        if self.pixels.is_empty() {
+            return None;
+        }
+
+        let border = self.get(0, 0);
+        let is_border = |pixel: &[u8; 4]| {
+            if border[3] == 0 {
+                pixel[3] == 0
+            } else {
+                *pixel == border
+            }
+        };
+        let row_is_border = |y: usize| self.row(y).iter().all(is_border);
+        let column_is_border = |x: usize, rows: std::ops::Range<usize>| {
+            rows.map(|y| self.get(x, y)).all(|pixel| is_border(&pixel))
+        };
+
+        let top = (0..self.height).find(|&y| !row_is_border(y))?;
+        let bottom = (top..self.height).rfind(|&y| !row_is_border(y))? + 1;
+        let left = (0..self.width).find(|&x| !column_is_border(x, top..bottom))?;
+        let right = (left..self.width).rfind(|&x| !column_is_border(x, top..bottom))? + 1;
+
+        let region = iced::Rectangle {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        };
+        (region.width < self.width || region.height < self.height).then_some(region)
+    }
+
+    /// Applies `exponent` to the color channels, leaving alpha alone.
+    pub fn apply_gamma(&mut self, exponent: f32) {
+        let table: Vec<u8> = (0..=255)
+            .map(|value| ((value as f32 / 255.0).powf(exponent) * 255.0).round() as u8)
+            .collect();
+        for pixel in &mut self.pixels {
+            for c in 0..3 {
+                pixel[c] = table[pixel[c] as usize];
+            }
+        }
+    }
+
+    /// Composites `layer` over this image with straight alpha. Both must be the same size.
+    pub fn blend(&mut self, layer: &Self) {
+        for (below, above) in self.pixels.iter_mut().zip(&layer.pixels) {
+            over(below, *above);
+        }
+    }
+
+    /// Puts `layer` on this image with its top-left corner at `(x, y)`, replacing the pixels
+    /// underneath or, with `blend`, compositing over them. `layer` must fit.
+    pub fn paste(&mut self, layer: &Self, x: usize, y: usize, blend: bool) {
+        for row in 0..layer.height {
+            let below = &mut self.pixels[(y + row) * self.width + x..][..layer.width];
+            let above = layer.row(row);
+            if blend {
+                below
+                    .iter_mut()
+                    .zip(above)
+                    .for_each(|(below, above)| over(below, *above));
+            } else {
+                below.copy_from_slice(above);
+            }
+        }
+    }
+
+    /// Summarizes the pixels channel by channel. Returns `None` for an empty image.
+    pub fn statistics(&self) -> Option<Statistics> {
+        if self.pixels.is_empty() {
+            return None;
+        }
+
+        let mut sum = [0u64; 4];
+        let mut sum_squares = [0u64; 4];
+        let mut min = [u8::MAX; 4];
+        let mut max = [0; 4];
+        let mut opaque = 0;
+        for pixel in &self.pixels {
+            for c in 0..4 {
+                let value = pixel[c] as u64;
+                sum[c] += value;
+                sum_squares[c] += value * value;
+                min[c] = min[c].min(pixel[c]);
+                max[c] = max[c].max(pixel[c]);
+            }
+            if pixel[3] == u8::MAX {
+                opaque += 1;
+            }
+        }
+
+        let count = self.pixels.len() as f64;
+        let mean = sum.map(|sum| sum as f64 / count);
+        let mut std_dev = [0.0; 4];
+        for c in 0..4 {
+            let variance = sum_squares[c] as f64 / count - mean[c] * mean[c];
+            std_dev[c] = variance.max(0.0).sqrt();
+        }
+
+        Some(Statistics {
+            mean,
+            std_dev,
+            min,
+            max,
+            opaque_percent: opaque as f64 / count * 100.0,
+        })
+    }
+
+    /// Compares against an image of the same size, ignoring deltas up to `tolerance`.
+    /// Returns `None` if the dimensions differ.
+    pub fn diff(&self, other: &Self, tolerance: u8) -> Option<Diff> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+
+        let deltas: Vec<u8> = self
+            .pixels
+            .iter()
+            .zip(&other.pixels)
+            .map(|(a, b)| (0..4).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0))
+            .collect();
+
+        let mut count = 0;
+        let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+        for (i, _) in deltas.iter().enumerate().filter(|(_, &d)| d > tolerance) {
+            let (x, y) = (i % self.width, i / self.width);
+            count += 1;
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+
+        Some(Diff {
+            count,
+            max_delta: deltas.iter().copied().max().unwrap_or(0),
+            bounds: (count > 0).then(|| iced::Rectangle {
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            }),
+            deltas,
+        })
+    }
+}
+
+impl<S: Sample> Render for Raster<S> {
+    fn bytes_per_pixel(_info: ColorInfo) -> u64 {
+        4 * std::mem::size_of::<S>() as u64
+    }
+
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        if y < self.height {
+            let pixels = &mut self.pixels[y * self.width..][..self.width];
+            for (pixel, color) in pixels.iter_mut().zip(row) {
+                *pixel = [color.r, color.g, color.b, color.a].map(S::from_unit);
+            }
+        }
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, color: iced::Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] =
+                [color.r, color.g, color.b, color.a].map(S::from_unit);
+        }
+    }
+}
+
+/// Decoded pixels at the precision of the file, so 16-bit images can still be dithered
+/// when drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pixels {
+    Eight(Image),
+    Sixteen(Image16),
+}
+
+impl Pixels {
+    pub fn width(&self) -> usize {
+        match self {
+            Self::Eight(image) => image.width(),
+            Self::Sixteen(image) => image.width(),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self {
+            Self::Eight(image) => image.height(),
+            Self::Sixteen(image) => image.height(),
+        }
+    }
+
+    /// The pixels at 8 bits per channel.
+    pub fn to_eight(&self) -> Image {
+        match self {
+            Self::Eight(image) => image.clone(),
+            Self::Sixteen(image) => Raster {
+                width: image.width,
+                height: image.height,
+                pixels: image
+                    .pixels
+                    .iter()
+                    .map(|pixel| pixel.map(|channel| (channel >> 8) as u8))
+                    .collect(),
+            },
+        }
+    }
+
+    /// The channels of one pixel, at the precision they were decoded with.
+    pub fn get(&self, x: usize, y: usize) -> [u32; 4] {
+        match self {
+            Self::Eight(image) => image.get(x, y).map(Into::into),
+            Self::Sixteen(image) => image.get(x, y).map(Into::into),
+        }
+    }
+}
+
+/// Straight-alpha "over" compositing of one pixel.
+fn over(below: &mut [u8; 4], above: [u8; 4]) {
+    let alpha = above[3] as u32;
+    for c in 0..3 {
+        below[c] = ((above[c] as u32 * alpha + below[c] as u32 * (255 - alpha)) / 255) as u8;
+    }
+    below[3] = (alpha + below[3] as u32 * (255 - alpha) / 255) as u8;
+}
+
+/// Downsamples while decoding, keeping every `step`th pixel of every `step`th row so the
+/// longer side fits in `max_side`. Skipped rows are never converted to colors.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    max_side: usize,
+    step: usize,
+    image: Image,
+}
+
+impl Thumbnail {
+    pub fn new(max_side: usize) -> Self {
+        Self {
+            max_side: max_side.max(1),
+            step: 1,
+            image: Image::new(0, 0),
+        }
+    }
+
+    pub fn into_image(self) -> Image {
+        self.image
+    }
+}
+
+impl Render for Thumbnail {
+    fn begin_image(&mut self, width: usize, height: usize, _info: ColorInfo) {
+        self.step = width.max(height).div_ceil(self.max_side).max(1);
+        self.image = Image::new(width.div_ceil(self.step), height.div_ceil(self.step));
+    }
+
+    fn wants_row(&self, y: usize) -> bool {
+        y.is_multiple_of(self.step)
+    }
+
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        let sampled: Vec<_> = row.iter().step_by(self.step).copied().collect();
+        self.image.draw_row(y / self.step, &sampled);
+    }
+}
+
+/// The image as it looks after each pass of an interlaced file, coarse to fine, each
+/// pixel decoded so far filling the block it stands for. Empty unless interlaced.
+#[derive(Debug, Clone)]
+pub struct Refinement {
+    image: Image,
+    passes: Vec<(Pass, Image)>,
+}
+
+impl Default for Refinement {
+    fn default() -> Self {
+        Self {
+            image: Image::new(0, 0),
+            passes: vec![],
+        }
+    }
+}
+
+impl Refinement {
+    pub fn into_passes(self) -> Vec<(Pass, Image)> {
+        self.passes
+    }
+}
+
+impl Render for Refinement {
+    fn begin_image(&mut self, width: usize, height: usize, _info: ColorInfo) {
+        self.image = Image::new(width, height);
+    }
+
+    fn wants_passes(&self) -> bool {
+        true
+    }
+
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        self.image.draw_row(y, row);
+    }
+
+    fn end_pass(&mut self, pass: Pass) {
+        self.passes.push((pass, self.image.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thumbnail_samples_full_image() -> Result<(), crate::parse::error::Error> {
+        let data = include_bytes!("../../assets/xkcd.png");
+        let full = crate::parse::decode(data)?;
+        let thumbnail = crate::parse::draw(
+            data,
+            |_, _| Thumbnail::new(64),
+            &mut crate::parse::chunks::Handlers::default(),
+        )?
+        .into_image();
+
+        let step = full.width().max(full.height()).div_ceil(64);
+        assert!(thumbnail.width().max(thumbnail.height()) <= 64);
+        assert_eq!(thumbnail.width(), full.width().div_ceil(step));
+        for (x, y) in [
+            (0, 0),
+            (10, 20),
+            (thumbnail.width() - 1, thumbnail.height() - 1),
+        ] {
+            assert_eq!(thumbnail.get(x, y), full.get(x * step, y * step));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn refinement_goes_coarse_to_fine() -> Result<(), crate::parse::error::Error> {
+        let full = crate::parse::decode(include_bytes!("../../assets/xkcd.png"))?;
+        let options = crate::encode::Options {
+            interlace: crate::parse::chunks::Interlace::Adam7,
+            ..Default::default()
+        };
+        let data = crate::encode::encode_with(&full, &options)?;
+        let passes = crate::parse::draw(
+            &data,
+            |_, _| Refinement::default(),
+            &mut crate::parse::chunks::Handlers::default(),
+        )?
+        .into_passes();
+
+        assert_eq!(passes.len(), 7);
+        let (pass, first) = &passes[0];
+        assert_eq!(pass.block(), (8, 8));
+        assert_eq!(first.get(7, 7), full.get(0, 0));
+        assert_eq!(first.get(9, 17), full.get(8, 16));
+        assert_eq!(passes[6].1, full);
+        Ok(())
+    }
+
+    #[test]
+    fn resample_filters() {
+        let flat = Image::from_pixels(3, 2, vec![[10, 20, 30, 255]; 6]).unwrap();
+        for filter in Filter::ALL {
+            let resampled = flat.resample(7, 5, filter);
+            assert!(resampled
+                .pixels()
+                .iter()
+                .all(|&pixel| pixel == [10, 20, 30, 255]));
+        }
+
+        let edge = Image::from_pixels(2, 1, vec![[0, 0, 0, 255], [255, 255, 255, 255]]).unwrap();
+        let reds = |filter| -> Vec<u8> {
+            let resampled = edge.resample(4, 1, filter);
+            resampled.row(0).iter().map(|[r, ..]| *r).collect()
+        };
+        assert_eq!(reds(Filter::Nearest), [0, 0, 255, 255]);
+        let bilinear = reds(Filter::Bilinear);
+        assert!(
+            bilinear.windows(2).all(|pair| pair[0] < pair[1]),
+            "{bilinear:?}"
+        );
+    }
+
+    #[test]
+    fn auto_crop_uniform_border() {
+        let mut image = Image::new(5, 4);
+        image.draw_pixel(1, 1, iced::Color::WHITE);
+        image.draw_pixel(3, 2, iced::Color::BLACK);
+
+        let region = image.auto_crop().expect("border is transparent");
+        assert_eq!(
+            region,
+            iced::Rectangle {
+                x: 1,
+                y: 1,
+                width: 3,
+                height: 2
+            }
+        );
+        assert_eq!(image.crop(region).get(2, 1), [0, 0, 0, 255]);
+        assert_eq!(Image::new(3, 3).auto_crop(), None);
+    }
+
+    #[test]
+    fn statistics() {
+        let mut image = Image::new(2, 1);
+        image.draw_pixel(0, 0, iced::Color::from_rgb8(10, 20, 30));
+        image.draw_pixel(1, 0, iced::Color::from_rgba8(30, 20, 10, 0.0));
+
+        let stats = image.statistics().expect("not empty");
+        assert_eq!(stats.mean, [20.0, 20.0, 20.0, 127.5]);
+        assert_eq!(stats.std_dev[0], 10.0);
+        assert_eq!(stats.std_dev[1], 0.0);
+        assert_eq!(stats.min, [10, 20, 10, 0]);
+        assert_eq!(stats.max, [30, 20, 30, 255]);
+        assert_eq!(stats.opaque_percent, 50.0);
+        assert_eq!(Image::new(0, 0).statistics(), None);
+    }
+
+    #[test]
+    fn diff_bounds() {
+        let a = Image::new(4, 4);
+        let mut b = a.clone();
+        b.draw_pixel(1, 2, iced::Color::from_rgba8(10, 0, 0, 0.0));
+        b.draw_pixel(3, 0, iced::Color::from_rgba8(0, 0, 2, 0.0));
+
+        let diff = a.diff(&b, 0).expect("same size");
+        assert_eq!((diff.count, diff.max_delta), (2, 10));
+        assert_eq!(
+            diff.bounds,
+            Some(iced::Rectangle {
+                x: 1,
+                y: 0,
+                width: 3,
+                height: 3
+            })
+        );
+        assert_eq!(a.diff(&b, 2).expect("same size").count, 1);
+        assert_eq!(a.diff(&Image::new(4, 3), 0), None);
+
+        let heatmap = diff.heatmap(4, 2);
+        assert_eq!((heatmap.width(), heatmap.height()), (4, 4));
+        assert_eq!(heatmap.get(3, 0), [0, 0, 0, 255]);
+        assert_eq!(heatmap.get(1, 2), [20, 0, 235, 255]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use png_viewer::{
+    color,
+    parse::{
+        self,
+        chunks::{Handlers, Policy},
+        image::{Image, Refinement},
+        Pass,
+    },
+};
+
+/// How long each pass stays up while playing, slow enough to see the refinement.
+pub const PASS_DELAY: Duration = Duration::from_millis(400);
+
+/// The passes of an interlaced image, shown one at a time from coarse to fine.
+#[derive(Debug)]
+pub struct Passes {
+    passes: Vec<(Pass, Image)>,
+    current: usize,
+    /// Moving on to the next pass by itself, rather than stepped through by hand.
+    playing: bool,
+}
+
+impl Passes {
+    /// Decodes `data` pass by pass, returning `None` unless it's interlaced.
+    pub fn new(data: &[u8], policy: Policy) -> Option<Self> {
+        let passes = parse::draw(
+            data,
+            |_, _| Refinement::default(),
+            Handlers::default()
+                .policy(policy)
+                .display(color::display_profile()),
+        )
+        .map_err(|error| tracing::error!("from parse::draw: {error}"))
+        .ok()?
+        .into_passes();
+        (!passes.is_empty()).then_some(Self {
+            passes,
+            current: 0,
+            playing: true,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Shows the pass at `index`, returning whether that changed anything.
+    pub fn seek(&mut self, index: usize) -> bool {
+        let index = index.min(self.passes.len() - 1);
+        let changed = index != self.current;
+        self.current = index;
+        changed
+    }
+
+    /// Starts again from the first pass.
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.current = 0;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Moves on to the next pass while playing, stopping at the last. Returns whether it
+    /// moved.
+    pub fn advance(&mut self) -> bool {
+        if !self.playing || !self.seek(self.current + 1) {
+            self.playing = false;
+            return false;
+        }
+        true
+    }
+
+    /// The image as it looks once the current pass is done.
+    pub fn image(&self) -> &Image {
+        &self.passes[self.current].1
+    }
+
+    /// Which pixels the current pass added, e.g. "columns 4, 12, 20… of rows 0, 8, 16…".
+    pub fn describe(&self) -> String {
+        let (pass, _) = &self.passes[self.current];
+        let every = |what: &str, from: usize, step: usize| match step {
+            1 => format!("every {what}"),
+            _ => format!("{what}s {}, {}, {}…", from, from + step, from + 2 * step),
+        };
+        format!(
+            "{} of {}",
+            every("column", pass.x, pass.dx),
+            every("row", pass.y, pass.dy)
+        )
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use png_viewer::{
+    encode::{self, Options},
+    parse::{
+        chunks::{bits_per_pixel, BitDepth, ColorType, Interlace},
+        error::Error,
+        image::{Image, Raster},
+    },
+};
+
+const COLOR_TYPES: [ColorType; 5] = [
+    ColorType::GrayScale,
+    ColorType::GrayScaleAlpha,
+    ColorType::Rgb,
+    ColorType::RgbAlpha,
+    ColorType::Palette,
+];
+const BIT_DEPTHS: [BitDepth; 5] = [
+    BitDepth::One,
+    BitDepth::Two,
+    BitDepth::Four,
+    BitDepth::Eight,
+    BitDepth::Sixteen,
+];
+
+/// Pixels waiting to be written, at the precision they were decoded with.
+#[derive(Debug)]
+pub enum Unsaved {
+    Eight(Image),
+    Sixteen(Raster<u16>),
+}
+
+impl From<Image> for Unsaved {
+    fn from(image: Image) -> Self {
+        Self::Eight(image)
+    }
+}
+
+impl From<Raster<u16>> for Unsaved {
+    fn from(image: Raster<u16>) -> Self {
+        Self::Sixteen(image)
+    }
+}
+
+impl Unsaved {
+    fn palette_len(&self) -> Option<usize> {
+        match self {
+            Self::Eight(image) => encode::palette_len(image),
+            Self::Sixteen(image) => encode::palette_len(image),
+        }
+    }
+
+    pub fn encode(&self, options: &Options) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Eight(image) => encode::encode_with(image, options),
+            Self::Sixteen(image) => encode::encode_with(image, options),
+        }
+    }
+}
+
+/// An encoder setting, or `Auto` to let the encoder pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Auto<T>(pub Option<T>);
+
+impl fmt::Display for Auto<ColorType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.0 {
+            None => "Auto",
+            Some(ColorType::GrayScale) => "Gray",
+            Some(ColorType::GrayScaleAlpha) => "Gray + alpha",
+            Some(ColorType::Rgb) => "RGB",
+            Some(ColorType::RgbAlpha) => "RGBA",
+            Some(ColorType::Palette) => "Palette",
+        })
+    }
+}
+
+impl fmt::Display for Auto<BitDepth> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => f.write_str("Auto"),
+            Some(depth) => write!(f, "{}-bit", depth as u8),
+        }
+    }
+}
+
+/// The image being saved and the options picked for it so far.
+#[derive(Debug)]
+pub struct SaveAs {
+    image: Unsaved,
+    /// Entries a palette of the image needs, if it fits one, to rule out depths too shallow.
+    palette_len: Option<usize>,
+    options: Options,
+}
+
+impl SaveAs {
+    pub fn new(image: Unsaved) -> Self {
+        Self {
+            palette_len: image.palette_len(),
+            image,
+            options: Options {
+                color_type: None,
+                ..Options::default()
+            },
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn color_types() -> Vec<Auto<ColorType>> {
+        std::iter::once(Auto(None))
+            .chain(COLOR_TYPES.map(|color_type| Auto(Some(color_type))))
+            .collect()
+    }
+
+    /// Depths that go with the chosen color type, and for a palette can index every color.
+    /// The encoder picks its own for `Auto`.
+    pub fn bit_depths(&self) -> Vec<Auto<BitDepth>> {
+        let valid = BIT_DEPTHS.into_iter().filter(|&depth| {
+            self.options.color_type.is_some_and(|color_type| {
+                bits_per_pixel(depth, color_type).is_ok()
+                    && (color_type != ColorType::Palette
+                        || self.palette_len.is_some_and(|len| len <= 1 << depth as u8))
+            })
+        });
+        std::iter::once(Auto(None))
+            .chain(valid.map(|depth| Auto(Some(depth))))
+            .collect()
+    }
+
+    pub fn set_color_type(&mut self, color_type: Option<ColorType>) {
+        self.options.color_type = color_type;
+        if !self.bit_depths().contains(&Auto(self.options.bit_depth)) {
+            self.options.bit_depth = None;
+        }
+    }
+
+    pub fn set_bit_depth(&mut self, bit_depth: Option<BitDepth>) {
+        self.options.bit_depth = bit_depth;
+    }
+
+    pub fn set_interlaced(&mut self, interlaced: bool) {
+        self.options.interlace = if interlaced {
+            Interlace::Adam7
+        } else {
+            Interlace::None
+        };
+    }
+
+    pub fn set_compression(&mut self, level: u32) {
+        self.options.compression = level;
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        self.image.encode(&self.options)
+    }
+}
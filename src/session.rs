@@ -0,0 +1,826 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    future::Future,
+    hash::{Hash, Hasher},
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use iced::Size;
+use png_viewer::{
+    apng, color,
+    exif::{self, Orientation},
+    parse::{
+        self,
+        chunks::BitDepth,
+        chunks::{Handlers, Policy, Text},
+        error::Error,
+        image::{Image, Image16, Pixels, Statistics},
+        ColorInfo, Pass, Render, Rgba,
+    },
+};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::{
+    cache::{self, Cache},
+    minimap::Minimap,
+    timeline::Timeline,
+};
+
+const READ_BLOCK: usize = 64 * 1024;
+/// How long a download may wait to connect, or for its next bytes.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest download that will be read before giving up on it.
+const MAX_DOWNLOAD: u64 = 256 * 1024 * 1024;
+/// Decoded rows sent to the viewer at a time.
+const BATCH_ROWS: usize = 64;
+
+/// A file being decoded on a blocking thread.
+///
+/// Dropping the session cancels the decode: the thread stops at the next block it reads
+/// and frees everything it has allocated, rather than finishing an image nobody will see.
+#[derive(Debug)]
+pub struct Session {
+    path: PathBuf,
+    /// Scanlines decoded so far and the image height.
+    progress: watch::Receiver<(usize, usize)>,
+    rows: mpsc::UnboundedReceiver<Rows>,
+    /// The rows decoded so far, shown until the whole image is ready.
+    preview: Option<Image>,
+    decoded_recv: oneshot::Receiver<Result<Decoded, Error>>,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+}
+
+/// Consecutive decoded rows, starting at row `y` of an image `width` by `height`.
+#[derive(Debug)]
+struct Rows {
+    y: usize,
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgba>,
+}
+
+/// Draws into an image, at 16 bits per channel for a 16-bit file, while passing its rows
+/// on in batches, so the top of a large image can be shown before the rest has decoded.
+struct Streamed<'s> {
+    pixels: Pixels,
+    batch: Rows,
+    send: &'s mpsc::UnboundedSender<Rows>,
+}
+
+impl<'s> Streamed<'s> {
+    fn new(width: usize, height: usize, send: &'s mpsc::UnboundedSender<Rows>) -> Self {
+        Self {
+            // allocated once the bit depth is known
+            pixels: Pixels::Eight(Image::new(0, 0)),
+            batch: Rows {
+                y: 0,
+                width,
+                height,
+                pixels: Vec::new(),
+            },
+            send,
+        }
+    }
+
+    /// Sends the rows batched so far. The viewer may already have moved on, so a
+    /// closed channel is fine.
+    fn send(&mut self) {
+        if self.batch.pixels.is_empty() {
+            return;
+        }
+        let pixels = std::mem::take(&mut self.batch.pixels);
+        let _ = self.send.send(Rows {
+            pixels,
+            ..self.batch
+        });
+    }
+}
+
+impl Render for Streamed<'_> {
+    fn bytes_per_pixel(info: ColorInfo) -> u64 {
+        match info.bit_depth {
+            BitDepth::Sixteen => 8,
+            _ => 4,
+        }
+    }
+
+    fn begin_image(&mut self, width: usize, height: usize, info: ColorInfo) {
+        self.pixels = match info.bit_depth {
+            BitDepth::Sixteen => Pixels::Sixteen(Image16::new(width, height)),
+            _ => Pixels::Eight(Image::new(width, height)),
+        };
+    }
+
+    /// Interlaced images are drawn after every pass, so they sharpen as they load.
+    fn wants_passes(&self) -> bool {
+        true
+    }
+
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        match &mut self.pixels {
+            Pixels::Eight(image) => image.draw_row(y, row),
+            Pixels::Sixteen(image) => image.draw_row(y, row),
+        }
+        let width = self.batch.width.max(1);
+        if y != self.batch.y + self.batch.pixels.len() / width {
+            self.send();
+            self.batch.y = y;
+        }
+        self.batch
+            .pixels
+            .extend_from_slice(&row[..row.len().min(width)]);
+        if self.batch.pixels.len() >= BATCH_ROWS * width {
+            self.send();
+            self.batch.y = y + 1;
+        }
+    }
+
+    fn end_pass(&mut self, _pass: Pass) {
+        self.send();
+    }
+
+    fn finish(&mut self) {
+        self.send();
+    }
+}
+
+/// Everything the viewer needs from a freshly loaded file.
+#[derive(Debug)]
+pub struct Decoded {
+    pub data: Data,
+    pub image_size: Size,
+    /// Pixel format label, e.g. `RGBA8`.
+    pub format: String,
+    pub metadata: Vec<Text>,
+    pub statistics: Option<Box<Statistics>>,
+    pub policy: Policy,
+    /// Every frame, if the file is an animated PNG.
+    pub timeline: Option<Timeline>,
+    pub minimap: Minimap,
+    pub pixels: Pixels,
+    /// How to turn the image upright, from its EXIF data.
+    pub orientation: Option<Orientation>,
+    /// Why only part of the image could be shown, for a file that's cut short.
+    pub partial: Option<String>,
+}
+
+/// The bytes of the file being viewed. A file on disk is read again whenever they're
+/// needed rather than kept in memory; an image with no file behind it, like an edited copy
+/// or a converted WebP, is held instead.
+#[derive(Debug, Clone)]
+pub enum Data {
+    File(PathBuf),
+    Memory(Vec<u8>),
+}
+
+impl Data {
+    pub fn read(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::File(path) => std::fs::read(path).map(Cow::Owned),
+            Self::Memory(data) => Ok(Cow::Borrowed(data)),
+        }
+    }
+
+    /// Size in bytes, without reading a file.
+    pub fn size(&self) -> io::Result<u64> {
+        match self {
+            Self::File(path) => Ok(std::fs::metadata(path)?.len()),
+            Self::Memory(data) => Ok(data.len() as u64),
+        }
+    }
+}
+
+impl Session {
+    /// Starts decoding `path`. The returned future completes once the result is ready.
+    ///
+    /// An HTTP address is downloaded instead, decoding the bytes as they arrive, and saved
+    /// to a temporary file that then stands in for it.
+    pub fn start(path: PathBuf, policy: Policy) -> (Self, impl Future<Output = ()>) {
+        let (session, task) = match url(&path) {
+            Some(url) => {
+                let url = url.to_string();
+                let saved = download_path(&url);
+                Self::spawn(saved.clone(), move |progress, rows, cancelled| {
+                    Decoded::download(&url, &saved, policy, progress, rows, cancelled)
+                })
+            }
+            None => Self::spawn(path.clone(), move |progress, rows, cancelled| {
+                Decoded::read(&path, policy, cache::global(), progress, rows, cancelled)
+            }),
+        };
+        let done = async move {
+            if let Err(error) = task.await {
+                tracing::error!("from tokio::task::spawn_blocking: {error}");
+            }
+        };
+        (session, done)
+    }
+
+    fn spawn(
+        path: PathBuf,
+        read: impl FnOnce(
+                &watch::Sender<(usize, usize)>,
+                &mpsc::UnboundedSender<Rows>,
+                &AtomicBool,
+            ) -> Result<Decoded, Error>
+            + Send
+            + 'static,
+    ) -> (Self, tokio::task::JoinHandle<()>) {
+        let (progress_send, progress) = watch::channel((0, 0));
+        let (rows_send, rows) = mpsc::unbounded_channel();
+        let (decoded_send, decoded_recv) = oneshot::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let task = {
+            let cancelled = cancelled.clone();
+            tokio::task::spawn_blocking(move || {
+                let result = read(&progress_send, &rows_send, &cancelled);
+                let _ = decoded_send.send(result);
+            })
+        };
+        let session = Self {
+            path,
+            progress,
+            rows,
+            preview: None,
+            decoded_recv,
+            cancelled,
+            started: Instant::now(),
+        };
+        (session, task)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        *self.progress.borrow()
+    }
+
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Draws the rows decoded since last time into the preview, returning whether
+    /// there were any.
+    pub fn receive_rows(&mut self) -> bool {
+        let mut received = false;
+        while let Ok(rows) = self.rows.try_recv() {
+            let preview = self
+                .preview
+                .get_or_insert_with(|| Image::new(rows.width, rows.height));
+            for (y, row) in (rows.y..).zip(rows.pixels.chunks(rows.width.max(1))) {
+                preview.draw_row(y, row);
+            }
+            received = true;
+        }
+        received
+    }
+
+    /// The rows decoded so far, once there are any.
+    pub fn preview(&self) -> Option<&Image> {
+        self.preview.as_ref()
+    }
+
+    /// The result, once the decode has finished.
+    pub fn try_result(&mut self) -> Option<Result<Decoded, Error>> {
+        match self.decoded_recv.try_recv() {
+            Ok(result) => Some(result),
+            Err(oneshot::error::TryRecvError::Empty) => None,
+            Err(oneshot::error::TryRecvError::Closed) => Some(Err(Error::Unknown)),
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether `path` was given as an HTTP or HTTPS address rather than a file.
+fn url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// Where a download from `url` is saved, named after the last part of its path behind a
+/// hash of the whole address, so different addresses never share a file.
+fn download_path(url: &str) -> PathBuf {
+    let mut hasher = std::hash::DefaultHasher::new();
+    url.hash(&mut hasher);
+    let address = url.split(['?', '#']).next().unwrap_or_default();
+    let file = address
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split_once('/'))
+        .map_or("", |(_, file)| file);
+    let name = file
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.trim_matches('.').is_empty() && !name.contains('\\'))
+        .unwrap_or("download.png");
+    std::env::temp_dir()
+        .join("png-viewer-downloads")
+        .join(format!("{:016x}-{name}", hasher.finish()))
+}
+
+/// A reader that copies everything read through it to `copy`.
+struct Tee<R> {
+    inner: R,
+    copy: File,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+/// A reader that fails once more than `left` bytes have come through, rather than ending
+/// quietly like [`Read::take`], so an oversized download isn't taken for a truncated file.
+struct Capped<R> {
+    inner: R,
+    left: u64,
+}
+
+impl<R: Read> Read for Capped<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.left = self.left.checked_sub(read as u64).ok_or_else(|| {
+            io::Error::other(format!("download larger than {} MiB", MAX_DOWNLOAD >> 20))
+        })?;
+        Ok(read)
+    }
+}
+
+impl Decoded {
+    /// Feeds the file to the decoder block by block as it's read, unless its pixels are
+    /// already in the cache.
+    fn read(
+        path: &Path,
+        policy: Policy,
+        cache: Option<&Cache>,
+        progress: &watch::Sender<(usize, usize)>,
+        rows: &mpsc::UnboundedSender<Rows>,
+        cancelled: &AtomicBool,
+    ) -> Result<Self, Error> {
+        if let Some(pixels) = cache.and_then(|cache| cache.load(path)) {
+            tracing::debug!("Using cached pixels: {}", path.display());
+            progress.send_replace((pixels.height(), pixels.height()));
+            return Self::new(Data::File(path.to_path_buf()), pixels, policy);
+        }
+
+        let file = File::open(path)?;
+        Self::read_from(file, path, cache, policy, progress, rows, cancelled)
+    }
+
+    /// Fetches `url` over HTTP, decoding it as it arrives and saving it to `path`.
+    fn download(
+        url: &str,
+        path: &Path,
+        policy: Policy,
+        progress: &watch::Sender<(usize, usize)>,
+        rows: &mpsc::UnboundedSender<Rows>,
+        cancelled: &AtomicBool,
+    ) -> Result<Self, Error> {
+        let response = ureq::AgentBuilder::new()
+            .timeout_connect(DOWNLOAD_TIMEOUT)
+            .timeout_read(DOWNLOAD_TIMEOUT)
+            .build()
+            .get(url)
+            .set(
+                "User-Agent",
+                concat!("png-viewer/", env!("CARGO_PKG_VERSION")),
+            )
+            .call()
+            .map_err(io::Error::other)?;
+        // saved as it arrives, so reloading, saving and history treat it like any other file
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let source = Tee {
+            inner: Capped {
+                inner: response.into_reader(),
+                left: MAX_DOWNLOAD,
+            },
+            copy: File::create(path)?,
+        };
+        let decoded = Self::read_from(source, path, None, policy, progress, rows, cancelled);
+        if decoded.is_err() {
+            if let Err(error) = std::fs::remove_file(path) {
+                tracing::error!("from std::fs::remove_file: {error}");
+            }
+        }
+        decoded
+    }
+
+    /// Feeds `source`, the file at `path`, to the decoder block by block as it's read. The
+    /// pixels are cached if they were slow to decode.
+    fn read_from(
+        mut source: impl Read,
+        path: &Path,
+        cache: Option<&Cache>,
+        policy: Policy,
+        progress: &watch::Sender<(usize, usize)>,
+        rows: &mpsc::UnboundedSender<Rows>,
+        cancelled: &AtomicBool,
+    ) -> Result<Self, Error> {
+        let started = Instant::now();
+        let mut handlers = Handlers::default();
+        handlers.policy(policy).display(color::display_profile());
+        let mut stream = parse::Stream::new(
+            |width, height| {
+                progress.send_replace((0, height));
+                parse::Progress::new(Streamed::new(width, height, rows), move |rows| {
+                    progress.send_replace((rows, height));
+                })
+            },
+            &mut handlers,
+        );
+
+        let mut block = vec![0; READ_BLOCK];
+        #[cfg(feature = "webp")]
+        let mut first = true;
+        loop {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "decode cancelled").into());
+            }
+            let read = source.read(&mut block)?;
+            if read == 0 {
+                break;
+            }
+            #[cfg(feature = "webp")]
+            if std::mem::replace(&mut first, false) && png_viewer::webp::is_webp(&block[..read]) {
+                return Self::read_webp(source, &block[..read], policy, progress);
+            }
+            stream.write_all(&block[..read])?;
+        }
+        let pixels = match stream.finish() {
+            Ok(progress) => progress.inner.pixels,
+            // keep the rows that made it, e.g. from a file still downloading
+            Err(error) if policy == Policy::Strict && error.is_truncation() => {
+                let mut handlers = Handlers::default();
+                handlers
+                    .policy(Policy::Lenient)
+                    .display(color::display_profile());
+                let data = std::fs::read(path)?;
+                let pixels = if parse::color_info(&data)?.bit_depth == BitDepth::Sixteen {
+                    Pixels::Sixteen(parse::decode16_with(&data, &mut handlers)?)
+                } else {
+                    Pixels::Eight(parse::decode_with(&data, &mut handlers)?)
+                };
+                progress.send_replace((pixels.height(), pixels.height()));
+                return Ok(Self {
+                    partial: Some(handlers.warnings().join("; ")),
+                    ..Self::new(Data::File(path.to_path_buf()), pixels, Policy::Lenient)?
+                });
+            }
+            Err(error) => return Err(error),
+        };
+
+        // a lenient decode may have papered over damage that a strict one would report
+        if let Some(cache) =
+            cache.filter(|_| policy == Policy::Strict && started.elapsed() >= cache::MIN_DECODE)
+        {
+            if let Err(error) = cache.store(path, &pixels) {
+                tracing::error!("from Cache::store: {error}");
+            }
+        }
+        Self::new(Data::File(path.to_path_buf()), pixels, policy)
+    }
+
+    /// Decodes a WebP in one go, standing in a PNG encoding of it for the original so the
+    /// rest of the viewer only ever deals with PNGs.
+    #[cfg(feature = "webp")]
+    fn read_webp(
+        mut file: impl Read,
+        start: &[u8],
+        policy: Policy,
+        progress: &watch::Sender<(usize, usize)>,
+    ) -> Result<Self, Error> {
+        let mut data = start.to_vec();
+        file.read_to_end(&mut data)?;
+        let image = png_viewer::webp::decode(&data)?;
+        progress.send_replace((image.height(), image.height()));
+        Ok(Self {
+            format: "WebP".into(),
+            ..Self::new(
+                Data::Memory(png_viewer::encode::encode(&image)?),
+                Pixels::Eight(image),
+                policy,
+            )?
+        })
+    }
+
+    fn new(data: Data, pixels: Pixels, policy: Policy) -> Result<Self, Error> {
+        let chunks = match &data {
+            Data::File(path) => Cow::Owned(without_image_data(path)?),
+            Data::Memory(data) => Cow::Borrowed(data.as_slice()),
+        };
+        let image_size = parse::dimensions(&chunks)?;
+        let format = parse::format_label(&chunks)?;
+        let metadata = parse::text_chunks(&chunks, Default::default()).unwrap_or_else(|error| {
+            tracing::error!("from parse::text_chunks: {error}");
+            vec![]
+        });
+        // a broken animation still shows its default image
+        let timeline = if apng::is_animated(&chunks) {
+            data.read()
+                .map_err(Error::from)
+                .and_then(|data| apng::frames(&data))
+                .and_then(Timeline::new)
+                .unwrap_or_else(|error| {
+                    tracing::error!("from apng::frames: {error}");
+                    None
+                })
+        } else {
+            None
+        };
+
+        // statistics and the minimap work at 8 bits
+        let eight;
+        let image = match &pixels {
+            Pixels::Eight(image) => image,
+            Pixels::Sixteen(_) => {
+                eight = pixels.to_eight();
+                &eight
+            }
+        };
+
+        Ok(Self {
+            statistics: image.statistics().map(Box::new),
+            minimap: Minimap::new(image),
+            orientation: exif::orientation(&chunks),
+            pixels,
+            partial: None,
+            data,
+            image_size,
+            format,
+            metadata,
+            policy,
+            timeline,
+        })
+    }
+}
+
+/// Every chunk of the file but IDAT, skipped over rather than read, which is all that's
+/// needed of it besides the pixels.
+fn without_image_data(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut data = vec![0; 8];
+    file.read_exact(&mut data)?;
+    let mut header = [0; 8];
+    loop {
+        match file.read_exact(&mut header) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        let [l0, l1, l2, l3, ty @ ..] = header;
+        // the data and its CRC
+        let len = u64::from(u32::from_be_bytes([l0, l1, l2, l3])) + 4;
+        if ty == *b"IDAT" {
+            file.seek_relative(len as i64)?;
+        } else {
+            data.extend(header);
+            (&mut file).take(len).read_to_end(&mut data)?;
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PNG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/xkcd.png");
+
+    #[test]
+    fn cancelled_before_reading() {
+        let (progress, _) = watch::channel((0, 0));
+        let result = Decoded::read(
+            Path::new(PNG),
+            Policy::Strict,
+            None,
+            &progress,
+            &mpsc::unbounded_channel().0,
+            &AtomicBool::new(true),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::IoError(error)) if error.kind() == io::ErrorKind::Interrupted
+        ));
+    }
+
+    #[test]
+    fn truncated_shows_partial() {
+        let data = std::fs::read(PNG).expect("read");
+        let path = std::env::temp_dir().join(format!("truncated-{}.png", std::process::id()));
+        std::fs::write(&path, &data[..data.len() / 2]).expect("write");
+        let (progress, _) = watch::channel((0, 0));
+        let result = Decoded::read(
+            &path,
+            Policy::Strict,
+            None,
+            &progress,
+            &mpsc::unbounded_channel().0,
+            &AtomicBool::new(false),
+        );
+        std::fs::remove_file(&path).expect("remove");
+
+        let decoded = result.expect("partial image");
+        assert_eq!(decoded.policy, Policy::Lenient);
+        assert!(decoded
+            .partial
+            .is_some_and(|warning| warning.contains("rows")));
+    }
+
+    #[test]
+    fn streams_rows_in_batches() {
+        let (progress, _) = watch::channel((0, 0));
+        let (rows_send, mut rows) = mpsc::unbounded_channel();
+        let decoded = Decoded::read(
+            Path::new(PNG),
+            Policy::Strict,
+            None,
+            &progress,
+            &rows_send,
+            &AtomicBool::new(false),
+        )
+        .expect("decoded");
+
+        let mut preview = Image::new(0, 0);
+        let mut batches = 0;
+        while let Ok(batch) = rows.try_recv() {
+            assert!(batch.pixels.len() <= BATCH_ROWS * batch.width);
+            if batches == 0 {
+                preview = Image::new(batch.width, batch.height);
+            }
+            for (y, row) in (batch.y..).zip(batch.pixels.chunks(batch.width)) {
+                preview.draw_row(y, row);
+            }
+            batches += 1;
+        }
+        // xkcd.png is 165 rows tall
+        assert_eq!(batches, 3);
+        assert_eq!(Pixels::Eight(preview), decoded.pixels);
+        // the file itself isn't held on to
+        assert!(matches!(decoded.data, Data::File(path) if path == Path::new(PNG)));
+    }
+
+    #[test]
+    fn sixteen_bit_streams_at_full_precision() -> Result<(), Error> {
+        let mut image = Image16::new(16, 4);
+        for x in 0..16 {
+            image.draw_pixel(x, 1, iced::Color::from_rgb(x as f32 / 15.0, 0.3, 0.7));
+        }
+        let path = std::env::temp_dir().join(format!("sixteen-{}.png", std::process::id()));
+        std::fs::write(&path, png_viewer::encode::encode(&image)?)?;
+        let (progress, _) = watch::channel((0, 0));
+        let decoded = Decoded::read(
+            &path,
+            Policy::Strict,
+            None,
+            &progress,
+            &mpsc::unbounded_channel().0,
+            &AtomicBool::new(false),
+        );
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(decoded?.pixels, Pixels::Sixteen(image));
+        Ok(())
+    }
+
+    #[test]
+    fn cache_hit_skips_decoding() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("session-cache-{}", std::process::id()));
+        let cache = Cache::new(dir.clone());
+        // pixels no decode of the file would produce
+        let cached = Pixels::Eight(Image::new(2, 1));
+        cache.store(Path::new(PNG), &cached)?;
+        let (progress, _) = watch::channel((0, 0));
+        let decoded = Decoded::read(
+            Path::new(PNG),
+            Policy::Strict,
+            Some(&cache),
+            &progress,
+            &mpsc::unbounded_channel().0,
+            &AtomicBool::new(false),
+        );
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(decoded.expect("cached").pixels, cached);
+        Ok(())
+    }
+
+    #[test]
+    fn skips_image_data() -> io::Result<()> {
+        let chunks = without_image_data(Path::new(PNG))?;
+        // the signature, IHDR, pHYs and IEND
+        assert_eq!(chunks.len(), 8 + 25 + 21 + 12);
+        assert_eq!(
+            parse::dimensions(&chunks).ok(),
+            Some(Size::new(293.0, 165.0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn downloads_and_saves() {
+        let data = std::fs::read(PNG).expect("read");
+        let server = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/comic.png", server.local_addr().expect("address"));
+        let serve = std::thread::spawn(move || {
+            let (mut client, _) = server.accept().expect("accept");
+            let mut request = [0; 1024];
+            let _ = client.read(&mut request);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                data.len()
+            );
+            client.write_all(header.as_bytes()).expect("header");
+            // in pieces, the way a slow connection delivers it
+            for piece in data.chunks(4096) {
+                client.write_all(piece).expect("body");
+            }
+        });
+
+        let path = std::env::temp_dir().join(format!("download-{}.png", std::process::id()));
+        let (progress, _) = watch::channel((0, 0));
+        let decoded = Decoded::download(
+            &url,
+            &path,
+            Policy::Strict,
+            &progress,
+            &mpsc::unbounded_channel().0,
+            &AtomicBool::new(false),
+        )
+        .expect("downloaded");
+        serve.join().expect("served");
+        let saved = std::fs::read(&path).expect("saved");
+        std::fs::remove_file(&path).expect("remove");
+
+        assert_eq!(saved, std::fs::read(PNG).expect("read"));
+        assert_eq!(decoded.image_size, Size::new(293.0, 165.0));
+        assert_eq!(*progress.borrow(), (165, 165));
+    }
+
+    #[test]
+    fn oversized_download_fails() {
+        let mut capped = Capped {
+            inner: &[0; 10][..],
+            left: 4,
+        };
+        assert!(capped.read_to_end(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn names_downloads() {
+        let name = |url| {
+            let path = download_path(url);
+            let name = path.file_name().expect("named").to_string_lossy();
+            // past the hash
+            name.split_once('-').expect("hashed").1.to_owned()
+        };
+        assert_eq!(
+            name("https://example.com/images/cat.png?size=large"),
+            "cat.png"
+        );
+        assert_eq!(name("https://example.com"), "download.png");
+        assert_eq!(name("http://example.com/a/.."), "download.png");
+        assert_ne!(
+            download_path("https://example.com/a/image.png"),
+            download_path("https://example.com/b/image.png")
+        );
+        assert_eq!(
+            url(Path::new("https://example.com/cat.png")),
+            Some("https://example.com/cat.png")
+        );
+        assert_eq!(url(Path::new("cat.png")), None);
+    }
+
+    #[test]
+    fn dropping_cancels() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime");
+        runtime.block_on(async {
+            let (session, done) = Session::start(PNG.into(), Policy::Strict);
+            let cancelled = session.cancelled.clone();
+            drop(session);
+            assert!(cancelled.load(Ordering::Relaxed));
+            // the thread still runs to a clean stop
+            done.await;
+        });
+    }
+}
@@ -0,0 +1,38 @@
+use std::{io, path::Path, process};
+
+/// Writes the image to a temp file and hands it to the platform's mail client (or file
+/// manager, where there is no scriptable share mechanism).
+pub async fn share(data: Vec<u8>) -> io::Result<()> {
+    let path = std::env::temp_dir().join(format!("png-viewer-share-{}.png", process::id()));
+    tokio::fs::write(&path, data).await?;
+    tracing::debug!("Sharing: {}", path.display());
+    launch(&path)
+}
+
+#[cfg(target_os = "macos")]
+fn launch(path: &Path) -> io::Result<()> {
+    process::Command::new("open")
+        .args(["-a", "Mail"])
+        .arg(path)
+        .spawn()
+        .map(drop)
+}
+
+#[cfg(target_os = "windows")]
+fn launch(path: &Path) -> io::Result<()> {
+    let mut select = std::ffi::OsString::from("/select,");
+    select.push(path);
+    process::Command::new("explorer")
+        .arg(select)
+        .spawn()
+        .map(drop)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn launch(path: &Path) -> io::Result<()> {
+    process::Command::new("xdg-email")
+        .arg("--attach")
+        .arg(path)
+        .spawn()
+        .map(drop)
+}
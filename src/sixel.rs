@@ -0,0 +1,99 @@
+use std::fmt::Write;
+
+use crate::parse::image::Image;
+
+// levels per channel of the fixed palette: 6 * 7 * 6 = 252 registers
+const RED: usize = 6;
+const GREEN: usize = 7;
+const BLUE: usize = 6;
+const COLORS: usize = RED * GREEN * BLUE;
+
+/// Encodes the image as a Sixel escape sequence using a fixed 252-color palette.
+/// Partially transparent pixels are composited over black; fully transparent ones are
+/// left unpainted.
+pub fn encode(image: &Image) -> String {
+    let mut out = String::new();
+    // DCS with transparent background, then raster attributes
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{};{}", image.width(), image.height());
+    for index in 0..COLORS {
+        let (r, g, b) = (index / (GREEN * BLUE), index / BLUE % GREEN, index % BLUE);
+        let percent = |level: usize, levels: usize| level * 100 / (levels - 1);
+        let _ = write!(
+            out,
+            "#{index};2;{};{};{}",
+            percent(r, RED),
+            percent(g, GREEN),
+            percent(b, BLUE)
+        );
+    }
+
+    let mut bands: Vec<Option<Vec<u8>>> = vec![None; COLORS];
+    for band in (0..image.height()).step_by(6) {
+        for y in band..(band + 6).min(image.height()) {
+            for (x, &pixel) in image.row(y).iter().enumerate() {
+                let Some(index) = quantize(pixel) else {
+                    continue;
+                };
+                let columns = bands[index].get_or_insert_with(|| vec![0; image.width()]);
+                columns[x] |= 1 << (y - band);
+            }
+        }
+
+        for (index, columns) in bands.iter_mut().enumerate() {
+            let Some(columns) = columns.take() else {
+                continue;
+            };
+            let _ = write!(out, "#{index}");
+            write_runs(&mut out, &columns);
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+fn quantize([r, g, b, a]: [u8; 4]) -> Option<usize> {
+    if a == 0 {
+        return None;
+    }
+    let level = |value: u8, levels: usize| {
+        let value = value as usize * a as usize / 255;
+        (value * (levels - 1) + 127) / 255
+    };
+    Some(level(r, RED) * GREEN * BLUE + level(g, GREEN) * BLUE + level(b, BLUE))
+}
+
+/// Writes one sixel per column, collapsing repeats into `!count` runs.
+fn write_runs(out: &mut String, columns: &[u8]) {
+    let mut rest = columns;
+    while let Some(&bits) = rest.first() {
+        let run = rest.iter().take_while(|&&other| other == bits).count();
+        let sixel = char::from(63 + bits);
+        if run > 3 {
+            let _ = write!(out, "!{run}{sixel}");
+        } else {
+            out.extend(std::iter::repeat_n(sixel, run));
+        }
+        rest = &rest[run..];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::Render;
+
+    #[test]
+    fn encodes_runs() {
+        let mut image = Image::new(8, 2);
+        for x in 0..8 {
+            image.draw_pixel(x, 0, iced::Color::WHITE);
+        }
+
+        let sixel = encode(&image);
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;8;2"));
+        assert!(sixel.ends_with("#251!8@$-\x1b\\"));
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use png_viewer::features;
+use rand::seq::SliceRandom;
+
+pub const INTERVAL: Duration = Duration::from_secs(5);
+
+/// The PNGs in a folder, cycled through in name order or shuffled. The folder is rescanned
+/// on every advance so added, removed and replaced files show up without a restart.
+#[derive(Debug)]
+pub struct Slideshow {
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    index: usize,
+    pub interval: Duration,
+    pub paused: bool,
+    shuffle: bool,
+    /// Shuffled files not shown yet this round, so none repeats before all have been seen.
+    queue: Vec<PathBuf>,
+}
+
+impl Slideshow {
+    pub fn from_dir(dir: &Path) -> io::Result<Self> {
+        let paths = scan(dir)?;
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no PNG files in {}", dir.display()),
+            ));
+        }
+        Ok(Self {
+            paths,
+            ..Self::empty(dir)
+        })
+    }
+
+    /// A slideshow of a folder with no PNGs yet, which shows them once they're added.
+    pub fn empty(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            paths: vec![],
+            index: 0,
+            interval: INTERVAL,
+            paused: false,
+            shuffle: false,
+            queue: vec![],
+        }
+    }
+
+    /// Continues from `path` rather than the first file, if it's one of the slides.
+    pub fn seek(&mut self, path: &Path) {
+        if let Some(index) = self.paths.iter().position(|slide| slide == path) {
+            self.index = index;
+        }
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        self.queue.clear();
+    }
+
+    /// `None` while the folder has no PNGs.
+    pub fn current(&self) -> Option<&Path> {
+        self.paths.get(self.index).map(PathBuf::as_path)
+    }
+
+    pub fn advance(&mut self) -> Option<&Path> {
+        match scan(&self.dir) {
+            Ok(paths) if !paths.is_empty() => {
+                // the next file by name, even if the current one was removed
+                let current = self.current().map(Path::to_path_buf);
+                self.index = if self.shuffle {
+                    self.queue.retain(|path| paths.contains(path));
+                    if self.queue.is_empty() {
+                        self.queue = paths.clone();
+                        self.queue.shuffle(&mut rand::thread_rng());
+                        // a new round never starts with the slide just shown
+                        if self.queue.len() > 1 && self.queue.last() == current.as_ref() {
+                            let last = self.queue.len() - 1;
+                            self.queue.swap(0, last);
+                        }
+                    }
+                    let next = self.queue.pop().expect("refilled");
+                    paths
+                        .iter()
+                        .position(|path| *path == next)
+                        .expect("retained")
+                } else {
+                    current.map_or(0, |current| {
+                        paths.iter().position(|path| *path > current).unwrap_or(0)
+                    })
+                };
+                self.paths = paths;
+            }
+            Ok(_) if self.paths.is_empty() => {}
+            Ok(_) => {
+                tracing::warn!("{} has no PNG files left", self.dir.display());
+                self.index = (self.index + 1) % self.paths.len();
+            }
+            Err(error) => {
+                tracing::error!("from slideshow::scan: {error}");
+                if !self.paths.is_empty() {
+                    self.index = (self.index + 1) % self.paths.len();
+                }
+            }
+        }
+        self.current()
+    }
+}
+
+/// The images directly in `dir` that this build can open, in name order.
+pub fn scan(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| features::is_supported_file(path))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shuffle_shows_every_slide_once_per_round() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("slideshow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        for name in ["a.png", "b.png", "c.png", "d.png"] {
+            std::fs::write(dir.join(name), b"")?;
+        }
+        let mut slideshow = Slideshow::from_dir(&dir)?;
+        slideshow.set_shuffle(true);
+        let mut rounds: Vec<Vec<_>> = (0..3)
+            .map(|_| {
+                (0..4)
+                    .map(|_| slideshow.advance().unwrap().to_path_buf())
+                    .collect()
+            })
+            .collect();
+        std::fs::remove_dir_all(&dir)?;
+
+        for round in &mut rounds {
+            round.sort();
+            round.dedup();
+            assert_eq!(round.len(), 4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn empty_slideshow_picks_up_added_files() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("slideshow-empty-{}", std::process::id()));
+        let mut slideshow = Slideshow::empty(&dir);
+        assert_eq!(slideshow.advance(), None);
+
+        std::fs::create_dir_all(&dir)?;
+        assert_eq!(slideshow.advance(), None);
+        std::fs::write(dir.join("a.png"), b"")?;
+        let shown = slideshow.advance().map(Path::to_path_buf);
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(shown, Some(dir.join("a.png")));
+        Ok(())
+    }
+}
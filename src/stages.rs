@@ -0,0 +1,91 @@
+//! The decoding pipeline split into stages that run on their own, so that `png-bench`
+//! and the criterion benches can time each one separately.
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use nom::combinator::iterator;
+
+use crate::parse::{
+    self,
+    chunks::{self, Chunk},
+    error::Error,
+    header, PixelFormat,
+};
+
+/// What the later stages need from the chunks.
+pub struct Parsed {
+    pub width: usize,
+    pub format: PixelFormat,
+    /// Every IDAT payload, concatenated.
+    pub idat: Vec<u8>,
+}
+
+impl Parsed {
+    /// Length of a scanline, including its filter byte.
+    pub fn row_len(&self) -> usize {
+        (self.width * self.format.bits_per_pixel()).div_ceil(8) + 1
+    }
+}
+
+/// Parses every chunk in the file.
+pub fn parse_chunks(data: &[u8]) -> Result<Parsed, Error> {
+    let (data, _) = header(data)?;
+    let mut ihdr = None;
+    let mut idat = Vec::new();
+    let mut iter = iterator(data, chunks::chunk);
+    for chunk in &mut iter {
+        match chunk {
+            Chunk::Ihdr {
+                width,
+                bit_depth,
+                color_type,
+                ..
+            } => ihdr = Some((width as usize, PixelFormat::new(bit_depth, color_type)?)),
+            Chunk::Plte(colors) => {
+                if let Some((_, format)) = &mut ihdr {
+                    format.set_palette(colors);
+                }
+            }
+            Chunk::Idat(data) => idat.extend_from_slice(data.into()),
+            _ => {}
+        }
+    }
+    iter.finish()?;
+
+    let (width, format) = ihdr.ok_or(Error::MissingCritical("IHDR"))?;
+    Ok(Parsed {
+        width,
+        format,
+        idat,
+    })
+}
+
+/// Inflates the image data into filtered scanlines.
+pub fn inflate(parsed: &Parsed) -> Result<Vec<u8>, Error> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(parsed.idat.as_slice()).read_to_end(&mut inflated)?;
+    Ok(inflated)
+}
+
+/// Unfilters the scanlines in place.
+pub fn unfilter(parsed: &Parsed, scanlines: &mut [u8]) -> Result<(), Error> {
+    let row_len = parsed.row_len();
+    let bytes_per_pixel = parsed.format.bits_per_pixel().div_ceil(8);
+    for start in (0..scanlines.len() / row_len).map(|y| y * row_len) {
+        let (above, rest) = scanlines.split_at_mut(start);
+        let prev = above
+            .get(start.saturating_sub(row_len)..)
+            .unwrap_or_default();
+        parse::unfilter(&mut rest[..row_len], prev, bytes_per_pixel)?;
+    }
+    Ok(())
+}
+
+/// Converts unfiltered scanlines to colors, returning the number of pixels.
+pub fn convert(parsed: &Parsed, scanlines: &[u8]) -> Result<usize, Error> {
+    scanlines
+        .chunks_exact(parsed.row_len())
+        .map(|row| Ok(parsed.format.colors(&row[1..], parsed.width)?.len()))
+        .sum()
+}
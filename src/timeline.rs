@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use iced::widget::image::Handle;
+use png_viewer::{
+    apng, encode,
+    parse::{error::Error, image::Image},
+};
+
+/// Longer side of each frame's thumbnail in the timeline.
+pub const THUMBNAIL_SIDE: usize = 48;
+
+/// The frames of an animated PNG and the one being shown.
+#[derive(Debug)]
+pub struct Timeline {
+    frames: Vec<Frame>,
+    current: usize,
+}
+
+#[derive(Debug)]
+struct Frame {
+    /// Encoded as a still PNG, for exporting and editing like any other file.
+    data: Vec<u8>,
+    image: Image,
+    thumbnail: Handle,
+    delay: Duration,
+}
+
+impl Timeline {
+    /// Returns `None` for an animation without frames.
+    pub fn new(frames: Vec<apng::Frame>) -> Result<Option<Self>, Error> {
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let image = frame.image;
+                let step = image
+                    .width()
+                    .max(image.height())
+                    .div_ceil(THUMBNAIL_SIDE)
+                    .max(1);
+                let thumbnail =
+                    image.resize(image.width().div_ceil(step), image.height().div_ceil(step));
+                Ok(Frame {
+                    data: encode::encode(&image)?,
+                    thumbnail: Handle::from_pixels(
+                        thumbnail.width() as u32,
+                        thumbnail.height() as u32,
+                        thumbnail.pixels().as_flattened().to_vec(),
+                    ),
+                    image,
+                    delay: frame.delay,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok((!frames.is_empty()).then_some(Self { frames, current: 0 }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Shows the frame at `index`, returning whether that changed anything.
+    pub fn seek(&mut self, index: usize) -> bool {
+        let index = index.min(self.frames.len() - 1);
+        let changed = index != self.current;
+        self.current = index;
+        changed
+    }
+
+    /// The frame being shown, as a PNG.
+    pub fn data(&self) -> &[u8] {
+        &self.frames[self.current].data
+    }
+
+    /// The frame being shown, decoded.
+    pub fn image(&self) -> &Image {
+        &self.frames[self.current].image
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.frames[self.current].delay
+    }
+
+    pub fn thumbnails(&self) -> impl Iterator<Item = &Handle> {
+        self.frames.iter().map(|frame| &frame.thumbnail)
+    }
+}
@@ -0,0 +1,122 @@
+use std::{io, path::PathBuf, process, time::Duration};
+
+const RELEASES: &str = "https://api.github.com/repos/k4g4/png-viewer/releases/latest";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A published release newer than the running build.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    pub url: String,
+    /// First line of the changelog, if there is one.
+    pub summary: Option<String>,
+}
+
+/// Whether the user has agreed to the startup update check. Nothing is sent over the
+/// network until they have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consent {
+    Unasked,
+    Granted,
+    Declined,
+}
+
+/// Reads the stored answer from the config directory.
+pub fn consent() -> Consent {
+    let answer = consent_file().and_then(|file| std::fs::read_to_string(file).ok());
+    match answer.as_deref().map(str::trim) {
+        Some("yes") => Consent::Granted,
+        Some("no") => Consent::Declined,
+        _ => Consent::Unasked,
+    }
+}
+
+pub fn set_consent(granted: bool) -> io::Result<()> {
+    let file = consent_file()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(file, if granted { "yes\n" } else { "no\n" })
+}
+
+/// Asks the releases endpoint for the latest version, returning it if it's newer.
+pub async fn check() -> io::Result<Option<Release>> {
+    tokio::task::spawn_blocking(latest)
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn latest() -> io::Result<Option<Release>> {
+    let release: serde_json::Value = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+        .get(RELEASES)
+        .set(
+            "User-Agent",
+            concat!("png-viewer/", env!("CARGO_PKG_VERSION")),
+        )
+        .call()
+        .map_err(io::Error::other)?
+        .into_json()?;
+
+    let field = |name| release.get(name).and_then(serde_json::Value::as_str);
+    let version = field("tag_name")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "release has no tag"))?;
+    if !is_newer(version, env!("CARGO_PKG_VERSION")) {
+        return Ok(None);
+    }
+    Ok(Some(Release {
+        version: version.trim_start_matches('v').into(),
+        url: field("html_url").unwrap_or(RELEASES).into(),
+        summary: field("body")
+            .and_then(|body| body.lines().map(str::trim).find(|line| !line.is_empty()))
+            .map(Into::into),
+    }))
+}
+
+/// Compares dotted versions numerically, ignoring a leading `v` and any pre-release suffix.
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parts(latest) > parts(current)
+}
+
+fn consent_file() -> Option<PathBuf> {
+    Some(crate::options::config_dir()?.join("update-check"))
+}
+
+/// Opens the release page in the default browser.
+pub fn open(url: &str) -> io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    process::Command::new(program).arg(url).spawn().map(drop)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_ordering() {
+        assert!(is_newer("v0.2.0", "0.1.0"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0-beta", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+    }
+}
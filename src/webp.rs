@@ -0,0 +1,52 @@
+//! WebP input, decoded with the pure-Rust decoder from the `image` crate. Only built with
+//! the `webp` feature.
+
+use image::{codecs::webp::WebPDecoder, DynamicImage};
+
+use crate::parse::{error::Error, image::Image};
+
+/// Whether `data` starts like a WebP file: a RIFF container of type `WEBP`.
+pub fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+/// Decodes a still lossy or lossless WebP. Animations decode to their first frame.
+pub fn decode(data: &[u8]) -> Result<Image, Error> {
+    let decoder = WebPDecoder::new(std::io::Cursor::new(data))
+        .map_err(|error| Error::WebP(error.to_string()))?;
+    let rgba = DynamicImage::from_decoder(decoder)
+        .map_err(|error| Error::WebP(error.to_string()))?
+        .into_rgba8();
+    let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+    let pixels = rgba
+        .into_raw()
+        .chunks_exact(4)
+        .map(|pixel| pixel.try_into().expect("chunks of 4"))
+        .collect();
+    Image::from_pixels(width, height, pixels).ok_or_else(|| Error::WebP("short pixel data".into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lossless_and_lossy() -> Result<(), Error> {
+        let lossless = include_bytes!("../assets/lossless.webp");
+        let lossy = include_bytes!("../assets/lossy.webp");
+        assert!(is_webp(lossless) && is_webp(lossy));
+        assert!(!is_webp(include_bytes!("../assets/xkcd.png")));
+
+        let image = decode(lossless)?;
+        assert!(image.width() > 0 && image.height() > 0);
+        // a two-color image
+        let mut colors = image.pixels().to_vec();
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), 2);
+
+        let image = decode(lossy)?;
+        assert!(image.width() > 0 && image.height() > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! Renders small synthetic images of each pixel format and compares every pixel against a
+//! snapshot in `tests/golden`, so any change to the rendered output shows up in review.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the snapshots after an intended change.
+
+use std::{fmt::Write as _, path::PathBuf};
+
+use flate2::Compression;
+use png_viewer::{
+    encode::{compress, filter_scanlines, write_png},
+    parse::{self, chunks::Handlers, Render, Rgba},
+};
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 4;
+
+/// Captures every pixel drawn, as 8-bit RGBA, without going through iced.
+struct MockRender {
+    width: usize,
+    pixels: Vec<Option<[u8; 4]>>,
+}
+
+impl MockRender {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            pixels: vec![None; width * height],
+        }
+    }
+
+    fn snapshot(&self) -> String {
+        let height = self.pixels.len() / self.width;
+        let mut out = format!("{}x{height}\n", self.width);
+        for row in self.pixels.chunks(self.width) {
+            let row: Vec<_> = row
+                .iter()
+                .map(|pixel| match pixel {
+                    Some([r, g, b, a]) => format!("{r:02x}{g:02x}{b:02x}{a:02x}"),
+                    None => "--------".into(),
+                })
+                .collect();
+            writeln!(out, "{}", row.join(" ")).unwrap();
+        }
+        out
+    }
+}
+
+impl Render for MockRender {
+    fn draw_row(&mut self, y: usize, row: &[Rgba]) {
+        assert!(row.len() <= self.width, "row {y} is {} pixels", row.len());
+        for (x, color) in row.iter().enumerate() {
+            let pixel = &mut self.pixels[y * self.width + x];
+            assert!(pixel.is_none(), "pixel ({x}, {y}) drawn twice");
+            *pixel = Some(color.to_rgba8());
+        }
+    }
+}
+
+/// Builds a PNG from raw scanlines, filtered the way the encoder would.
+fn png(bit_depth: u8, color_type: u8, extra: &[(&[u8; 4], Vec<u8>)], rows: &[Vec<u8>]) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+
+    let row_bits = rows[0].len() * 8 / WIDTH;
+    let filtered = filter_scanlines(rows.iter().map(Vec::as_slice), (row_bits / 8).max(1));
+    let idat = compress(&filtered, Compression::default()).expect("compresses");
+
+    let mut chunks = vec![(b"IHDR", ihdr.as_slice())];
+    chunks.extend(extra.iter().map(|(ty, data)| (*ty, data.as_slice())));
+    chunks.push((b"IDAT", &idat));
+    chunks.push((b"IEND", &[]));
+    write_png(chunks)
+}
+
+/// A value that varies across both axes, so filters and row order both matter.
+fn ramp(x: usize, y: usize) -> u8 {
+    ((x * 32 + y * 48) % 256) as u8
+}
+
+fn rows(pixel: impl Fn(usize, usize) -> Vec<u8>) -> Vec<Vec<u8>> {
+    (0..HEIGHT)
+        .map(|y| (0..WIDTH).flat_map(|x| pixel(x, y)).collect())
+        .collect()
+}
+
+fn check(name: &str, data: &[u8]) {
+    let rendered = parse::draw(data, MockRender::new, &mut Handlers::default())
+        .unwrap_or_else(|error| panic!("{name}: {error}"))
+        .snapshot();
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &rendered).expect("writes snapshot");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("{}: {error} (run with UPDATE_GOLDEN=1)", path.display()));
+    assert_eq!(rendered, expected, "{name} differs from its snapshot");
+}
+
+#[test]
+fn grayscale() {
+    check("gray8", &png(8, 0, &[], &rows(|x, y| vec![ramp(x, y)])));
+}
+
+#[test]
+fn grayscale_alpha() {
+    let data = png(8, 4, &[], &rows(|x, y| vec![ramp(x, y), 255 - ramp(y, x)]));
+    check("gray-alpha8", &data);
+}
+
+#[test]
+fn palette() {
+    let plte = vec![0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+    // four 2-bit indices per byte
+    let indices = rows(|x, y| vec![((x + y) % 4) as u8]);
+    let packed: Vec<_> = indices
+        .iter()
+        .map(|row| {
+            row.chunks(4)
+                .map(|four| four.iter().fold(0, |byte, index| byte << 2 | index))
+                .collect()
+        })
+        .collect();
+    check("palette2", &png(2, 3, &[(b"PLTE", plte)], &packed));
+}
+
+#[test]
+fn rgb16() {
+    let data = png(
+        16,
+        2,
+        &[],
+        &rows(|x, y| {
+            let value = (ramp(x, y) as u16) << 8 | (x * 37) as u16;
+            [value, u16::MAX - value, (y * 0x4000) as u16]
+                .iter()
+                .flat_map(|channel| channel.to_be_bytes())
+                .collect()
+        }),
+    );
+    check("rgb16", &data);
+}
+
+#[test]
+fn rgba8() {
+    let data = png(
+        8,
+        6,
+        &[],
+        &rows(|x, y| vec![ramp(x, y), ramp(y, x), 128, (x * 36) as u8]),
+    );
+    check("rgba8", &data);
+}
@@ -0,0 +1,79 @@
+//! Decodes under a counting allocator so that regressions in peak memory use show up as
+//! test failures instead of silently growing.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use png_viewer::{
+    encode::encode,
+    parse::{decode, image::Image, Render},
+};
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Peak bytes allocated while running `f`, above what was live beforehand.
+fn peak_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed) - baseline)
+}
+
+fn checkerboard(width: usize, height: usize) -> Image {
+    let mut image = Image::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let shade = if (x / 8 + y / 8) % 2 == 0 { 0.0 } else { 1.0 };
+            image.draw_pixel(x, y, iced::Color::from_rgb(shade, shade, shade));
+        }
+    }
+    image
+}
+
+// The allocator is process-wide, so every measurement lives in one test to keep other tests
+// from allocating concurrently.
+#[test]
+fn peak_allocation_stays_bounded() {
+    let files: [(&str, Vec<u8>); 2] = [
+        ("xkcd.png", include_bytes!("../assets/xkcd.png").to_vec()),
+        (
+            "checkerboard",
+            encode(&checkerboard(512, 512)).expect("encodes"),
+        ),
+    ];
+
+    for (name, data) in files {
+        let (image, peak) = peak_during(|| decode(&data).expect("decodes"));
+        let pixels = image.width() * image.height() * 4;
+        // the output buffer plus one inflated copy of the scanlines, with some headroom
+        let limit = pixels * 2 + data.len() + 256 * 1024;
+        assert!(
+            peak <= limit,
+            "{name}: peak allocation {peak} bytes exceeds {limit} for {pixels} bytes of pixels"
+        );
+    }
+}